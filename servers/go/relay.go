@@ -3,16 +3,22 @@ package main
 import (
 	"context"
 	"encoding/json"
+	"errors"
 	"fmt"
 	"log"
+	"net"
 	"net/http"
+	"os"
 	"strconv"
+	"sync"
 	"time"
+	"unicode/utf8"
 
 	"regexp"
 	"strings"
 
 	"golang.org/x/crypto/ssh"
+	"golang.org/x/crypto/ssh/agent"
 )
 
 type SSHConfig struct {
@@ -20,8 +26,24 @@ type SSHConfig struct {
     User     string `json:"user"`
     Port     int    `json:"port"`
     Password string `json:"password,omitempty"`
+    // PEM-ключ (rust-server сам читает файлы ключей и присылает содержимое)
+    PrivateKey string `json:"private_key,omitempty"`
+    Passphrase string `json:"passphrase,omitempty"`
+    // ssh-agent: сокет из agent_socket или SSH_AUTH_SOCK процесса relay
+    Agent       bool   `json:"agent,omitempty"`
+    AgentSocket string `json:"agent_socket,omitempty"`
+    // Jump host'ы по порядку подключения; их собственные Jumps не смотрятся
+    Jumps []SSHConfig `json:"jumps,omitempty"`
 }
 
+// hopError — на каком hop'е оборвалась цепочка: индекс в Jumps, цель — len(Jumps)
+type hopError struct {
+    index int
+    err   error
+}
+
+func (e *hopError) Error() string { return e.err.Error() }
+
 type SSHExecuteReq struct {
     Command string `json:"command"`
 }
@@ -88,12 +110,17 @@ func sshConnectHandler(w http.ResponseWriter, r *http.Request) {
     }
     if cfg.Port == 0 { cfg.Port = 22 }
     lastSSH = cfg
-    log.Printf("received SSH config: host=%s user=%s port=%d pwd_set=%t", cfg.Host, cfg.User, cfg.Port, cfg.Password != "")
+    log.Printf("received SSH config: host=%s user=%s port=%d pwd_set=%t key_set=%t agent=%t jumps=%d", cfg.Host, cfg.User, cfg.Port, cfg.Password != "", cfg.PrivateKey != "", cfg.Agent, len(cfg.Jumps))
     // Тестовое подключение
     connected := false
     msg := ""
+    failedHop := -1
     if err := testSSH(cfg); err != nil {
         msg = err.Error()
+        var he *hopError
+        if errors.As(err, &he) && len(cfg.Jumps) > 0 {
+            failedHop = he.index
+        }
         log.Printf("ssh test failed: %v", err)
     } else {
         connected = true
@@ -104,8 +131,12 @@ func sshConnectHandler(w http.ResponseWriter, r *http.Request) {
         // establish persistent client and keepalive
         _ = establishSSH(lastSSH)
     }
+    resp := map[string]any{"ok": true, "connected": connected, "message": msg}
+    if failedHop >= 0 {
+        resp["failed_hop"] = failedHop
+    }
     w.Header().Set("Content-Type", "application/json")
-    _ = json.NewEncoder(w).Encode(map[string]any{"ok": true, "connected": connected, "message": msg})
+    _ = json.NewEncoder(w).Encode(resp)
 }
 
 // WS полностью отключён в pull-модели
@@ -134,20 +165,7 @@ func sshStatusHandler(w http.ResponseWriter, r *http.Request) {
 
 // testSSH — пробует выполнить короткое SSH-подключение и команду 'echo ok'
 func testSSH(cfg SSHConfig) error {
-    var auths []ssh.AuthMethod
-    if cfg.Password != "" {
-        auths = append(auths, ssh.Password(cfg.Password))
-    } else {
-        return fmt.Errorf("no password provided")
-    }
-    conf := &ssh.ClientConfig{
-        User:            cfg.User,
-        Auth:            auths,
-        HostKeyCallback: ssh.InsecureIgnoreHostKey(),
-        Timeout:         5 * time.Second,
-    }
-    addr := fmt.Sprintf("%s:%d", cfg.Host, cfg.Port)
-    client, err := ssh.Dial("tcp", addr, conf)
+    client, err := dialChain(cfg)
     if err != nil {
         return err
     }
@@ -192,6 +210,101 @@ func sshExecuteHandler(w http.ResponseWriter, r *http.Request) {
     fmt.Fprint(w, output)
 }
 
+// Потоковый execute: NDJSON-строки {"stream":"stdout|stderr","data"} по мере вывода, в конце {"exit":code}
+// или {"error"}. Закрытие соединения клиентом (отмена в rust-server) убивает команду
+func sshExecuteStreamHandler(w http.ResponseWriter, r *http.Request) {
+    if r.Method != http.MethodPost {
+        http.Error(w, "method not allowed", http.StatusMethodNotAllowed)
+        return
+    }
+    var req SSHExecuteReq
+    if err := json.NewDecoder(r.Body).Decode(&req); err != nil {
+        http.Error(w, err.Error(), http.StatusBadRequest)
+        return
+    }
+    if sshClient == nil {
+        http.Error(w, "ssh not connected", http.StatusBadGateway)
+        return
+    }
+    flusher, ok := w.(http.Flusher)
+    if !ok {
+        http.Error(w, "streaming unsupported", http.StatusInternalServerError)
+        return
+    }
+    lastActivity = time.Now()
+    fullCmd := req.Command
+    if currentWorkingDir != "" {
+        fullCmd = fmt.Sprintf("cd %s && %s", currentWorkingDir, req.Command)
+    }
+    session, err := sshClient.NewSession()
+    if err != nil {
+        http.Error(w, err.Error(), http.StatusBadGateway)
+        return
+    }
+    defer session.Close()
+
+    w.Header().Set("Content-Type", "application/x-ndjson")
+    w.WriteHeader(http.StatusOK)
+    var mu sync.Mutex
+    emit := func(v map[string]any) {
+        mu.Lock()
+        defer mu.Unlock()
+        b, _ := json.Marshal(v)
+        w.Write(append(b, '\n'))
+        flusher.Flush()
+        lastActivity = time.Now()
+    }
+    session.Stdout = &streamWriter{name: "stdout", emit: emit}
+    session.Stderr = &streamWriter{name: "stderr", emit: emit}
+    if err := session.Start(fullCmd); err != nil {
+        emit(map[string]any{"error": err.Error()})
+        return
+    }
+    done := make(chan error, 1)
+    go func() { done <- session.Wait() }()
+    select {
+    case <-r.Context().Done():
+        _ = session.Signal(ssh.SIGKILL)
+        _ = session.Close()
+        // Wait дожидается и копирования вывода: после него в w уже никто не пишет
+        <-done
+        log.Printf("ssh_execute_stream: cancelled by client")
+    case err := <-done:
+        var exitErr *ssh.ExitError
+        switch {
+        case err == nil:
+            emit(map[string]any{"exit": 0})
+        case errors.As(err, &exitErr):
+            emit(map[string]any{"exit": exitErr.ExitStatus(), "signal": exitErr.Signal()})
+        default:
+            emit(map[string]any{"exit": nil, "error": err.Error()})
+        }
+    }
+}
+
+// Пишет вывод команды строками NDJSON; неполный UTF-8 символ в конце куска ждёт следующего
+type streamWriter struct {
+    name string
+    emit func(map[string]any)
+    rest []byte
+}
+
+func (s *streamWriter) Write(p []byte) (int, error) {
+    buf := append(s.rest, p...)
+    cut := len(buf)
+    for i := len(buf) - 1; i >= 0 && i >= len(buf)-3; i-- {
+        if utf8.RuneStart(buf[i]) {
+            if !utf8.FullRune(buf[i:]) { cut = i }
+            break
+        }
+    }
+    s.rest = append([]byte(nil), buf[cut:]...)
+    if cut > 0 {
+        s.emit(map[string]any{"stream": s.name, "data": string(buf[:cut])})
+    }
+    return len(p), nil
+}
+
 // establish persistent ssh client and keepalive pinger
 func establishSSH(cfg SSHConfig) error {
     if sshClient != nil { _ = sshClient.Close(); sshClient = nil }
@@ -199,13 +312,7 @@ func establishSSH(cfg SSHConfig) error {
     currentWorkingDir = ""
     // Reset network stats for new connection
     lastNetworkStats = NetworkStats{}
-    conf := &ssh.ClientConfig{
-        User: cfg.User,
-        Auth: buildAuth(cfg),
-        HostKeyCallback: ssh.InsecureIgnoreHostKey(),
-        Timeout: 5 * time.Second,
-    }
-    c, err := ssh.Dial("tcp", fmt.Sprintf("%s:%d", cfg.Host, cfg.Port), conf)
+    c, err := dialChain(cfg)
     if err != nil { return err }
     sshClient = c
     if keepAliveStop != nil { close(keepAliveStop) }
@@ -237,12 +344,102 @@ func establishSSH(cfg SSHConfig) error {
     return nil
 }
 
-func buildAuth(cfg SSHConfig) []ssh.AuthMethod {
+// dialChain — подключение через jump host'ы: каждый следующий hop идёт через TCP-туннель предыдущего.
+// Промежуточные клиенты закрываются вместе с итоговым
+func dialChain(cfg SSHConfig) (*ssh.Client, error) {
+    hops := append(append([]SSHConfig{}, cfg.Jumps...), cfg)
+    var opened []*ssh.Client
+    closeAll := func() {
+        for i := len(opened) - 1; i >= 0; i-- { _ = opened[i].Close() }
+    }
+    for i, hop := range hops {
+        if hop.Port == 0 { hop.Port = 22 }
+        var via *ssh.Client
+        if len(opened) > 0 { via = opened[len(opened)-1] }
+        c, err := dialHop(hop, via)
+        if err != nil {
+            closeAll()
+            if len(hops) > 1 {
+                role := fmt.Sprintf("jump %d", i+1)
+                if i == len(hops)-1 { role = "target" }
+                err = fmt.Errorf("%s %s@%s:%d: %v", role, hop.User, hop.Host, hop.Port, err)
+            }
+            return nil, &hopError{index: i, err: err}
+        }
+        opened = append(opened, c)
+    }
+    client := opened[len(opened)-1]
+    if jumps := opened[:len(opened)-1]; len(jumps) > 0 {
+        go func() {
+            _ = client.Wait()
+            for i := len(jumps) - 1; i >= 0; i-- { _ = jumps[i].Close() }
+        }()
+    }
+    return client, nil
+}
+
+// dialHop — один hop: напрямую или через уже открытый клиент (direct-tcpip)
+func dialHop(cfg SSHConfig, via *ssh.Client) (*ssh.Client, error) {
+    auths, closeAuth, err := buildAuth(cfg)
+    if err != nil { return nil, err }
+    defer closeAuth()
+    if len(auths) == 0 {
+        return nil, fmt.Errorf("no password, key or agent provided")
+    }
+    conf := &ssh.ClientConfig{
+        User: cfg.User,
+        Auth: auths,
+        HostKeyCallback: ssh.InsecureIgnoreHostKey(),
+        Timeout: 5 * time.Second,
+    }
+    addr := fmt.Sprintf("%s:%d", cfg.Host, cfg.Port)
+    if via == nil {
+        return ssh.Dial("tcp", addr, conf)
+    }
+    conn, err := via.Dial("tcp", addr)
+    if err != nil { return nil, err }
+    c, chans, reqs, err := ssh.NewClientConn(conn, addr, conf)
+    if err != nil {
+        _ = conn.Close()
+        return nil, err
+    }
+    return ssh.NewClient(c, chans, reqs), nil
+}
+
+// Соединение с агентом нужно только на время handshake, его закрывает возвращаемая функция
+func buildAuth(cfg SSHConfig) ([]ssh.AuthMethod, func(), error) {
     var auths []ssh.AuthMethod
+    closeAuth := func() {}
+    if cfg.PrivateKey != "" {
+        var signer ssh.Signer
+        var err error
+        if cfg.Passphrase != "" {
+            signer, err = ssh.ParsePrivateKeyWithPassphrase([]byte(cfg.PrivateKey), []byte(cfg.Passphrase))
+        } else {
+            signer, err = ssh.ParsePrivateKey([]byte(cfg.PrivateKey))
+        }
+        if err != nil {
+            return nil, closeAuth, fmt.Errorf("private key: %v", err)
+        }
+        auths = append(auths, ssh.PublicKeys(signer))
+    }
+    if cfg.Agent {
+        sock := cfg.AgentSocket
+        if sock == "" { sock = os.Getenv("SSH_AUTH_SOCK") }
+        if sock == "" {
+            return nil, closeAuth, fmt.Errorf("ssh-agent: no agent_socket and SSH_AUTH_SOCK is not set")
+        }
+        conn, err := net.Dial("unix", sock)
+        if err != nil {
+            return nil, closeAuth, fmt.Errorf("ssh-agent: %v", err)
+        }
+        closeAuth = func() { _ = conn.Close() }
+        auths = append(auths, ssh.PublicKeysCallback(agent.NewClient(conn).Signers))
+    }
     if cfg.Password != "" { 
         auths = append(auths, ssh.Password(cfg.Password)) 
     }
-    return auths
+    return auths, closeAuth, nil
 }
 
 func runSSH(cmd string, timeout time.Duration) (string, error) {
@@ -7,6 +7,7 @@ func registerRoutes(mux *http.ServeMux) {
     mux.Handle("/go/ssh/connect", withLogging("ssh_connect", withCORS(http.HandlerFunc(sshConnectHandler))))
     mux.Handle("/go/ssh/disconnect", withLogging("ssh_disconnect", withCORS(http.HandlerFunc(sshDisconnectHandler))))
     mux.Handle("/go/ssh/execute", withLogging("ssh_execute", withCORS(http.HandlerFunc(sshExecuteHandler))))
+    mux.Handle("/go/ssh/execute/stream", withLogging("ssh_execute_stream", withCORS(http.HandlerFunc(sshExecuteStreamHandler))))
     mux.Handle("/go/data/processes", withLogging("processes", withCORS(http.HandlerFunc(processesHandler))))
     mux.Handle("/go/data/ports", withLogging("ports", withCORS(http.HandlerFunc(portsHandler))))
     mux.Handle("/go/data/connections", withLogging("connections", withCORS(http.HandlerFunc(connectionsHandler))))
@@ -1,9 +1,10 @@
-use axum::{response::Html, Router};
+use axum::{middleware, response::Html, Router};
 use std::net::SocketAddr;
 use tower::ServiceBuilder;
 use tower_http::trace::TraceLayer;
-use tracing::{info, Level};
+use tracing::info;
 
+mod config;
 mod routes;
 
 async fn handler() -> Html<&'static str> {
@@ -13,24 +14,39 @@ async fn handler() -> Html<&'static str> {
 
 #[tokio::main]
 async fn main() {
+    let (config, print_config) = match config::load() {
+        Ok(loaded) => loaded,
+        Err(e) => {
+            eprintln!("config error: {}", e);
+            std::process::exit(2);
+        }
+    };
+    if print_config {
+        print!("{}", config.to_toml());
+        return;
+    }
+
     // Инициализация логирования
     tracing_subscriber::fmt()
-        .with_max_level(Level::INFO)
+        .with_max_level(config.level())
         .init();
 
     let app = Router::new()
-        .merge(routes::create_task_routes())  // Подключаем роуты заданий
+        .merge(routes::create_task_routes(&config))  // Подключаем роуты заданий
         .fallback(handler)
         .layer(
             ServiceBuilder::new()
                 .layer(TraceLayer::new_for_http())
+                .layer(middleware::from_fn(routes::metrics::track))
         );
 
-    let addr = SocketAddr::from(([127, 0, 0, 1], 3001));
+    let addr = config.addr();
     info!("🚀 Starting Dobrunia's Rust server on http://{}", addr);
+    info!("Serving static files from {}", config.public_dir.display());
 
     let listener = tokio::net::TcpListener::bind(addr).await.unwrap();
     info!("✅ Server successfully started and listening...");
     
-    axum::serve(listener, app).await.unwrap();
+    // Адрес клиента нужен журналу аудита
+    axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>()).await.unwrap();
 }
@@ -11,27 +11,87 @@ pub mod navigation;
 pub mod ajax;
 #[path = "routes/snippets.rs"]
 pub mod snippets;
+#[path = "routes/relay.rs"]
+pub mod relay;
+#[path = "routes/relay_models.rs"]
+pub mod relay_models;
+#[path = "routes/history.rs"]
+pub mod history;
+#[path = "routes/session_store.rs"]
+pub mod session_store;
+#[path = "routes/servers.rs"]
+pub mod servers;
+#[path = "routes/ssh_auth.rs"]
+pub mod ssh_auth;
+#[path = "routes/ssh_sessions.rs"]
+pub mod ssh_sessions;
+#[path = "routes/terminal.rs"]
+pub mod terminal;
+#[path = "routes/exec_stream.rs"]
+pub mod exec_stream;
+#[path = "routes/policy.rs"]
+pub mod policy;
+#[path = "routes/audit.rs"]
+pub mod audit;
+#[path = "routes/auth.rs"]
+pub mod auth;
+#[path = "routes/runbooks.rs"]
+pub mod runbooks;
+#[path = "routes/scheduler.rs"]
+pub mod scheduler;
+#[path = "routes/alerts.rs"]
+pub mod alerts;
+#[path = "routes/metrics.rs"]
+pub mod metrics;
+#[path = "routes/live.rs"]
+pub mod live;
+#[path = "routes/export.rs"]
+pub mod export;
+#[path = "routes/inventory.rs"]
+pub mod inventory;
+#[path = "routes/files.rs"]
+pub mod files;
+#[path = "routes/logtail.rs"]
+pub mod logtail;
+#[cfg(feature = "native-ssh")]
+#[path = "routes/native_parse.rs"]
+pub mod native_parse;
+#[cfg(feature = "native-ssh")]
+#[path = "routes/native_ssh.rs"]
+pub mod native_ssh;
 // #[path = "routes/network/network.rs"]
 // pub mod network;
 
-use axum::{Router, routing::get_service};
+use axum::{middleware, Router, routing::get_service};
+use std::sync::Arc;
 use tower_http::services::ServeFile;
 
+use crate::config::Config;
+
 // Функция для подключения всех роутов заданий
-pub fn create_task_routes() -> Router {
+pub fn create_task_routes(config: &Config) -> Router {
+    let page = |name: &str| get_service(ServeFile::new(config.public_file(name)));
+    // Один журнал аудита на дашборд и вход
+    let audit = Arc::new(audit::AuditLog::open(config.data_path("audit.jsonl"), config.audit.clone()));
+    audit::set_trusted_proxies(&config.auth.trusted_proxies);
+    let auth = Arc::new(auth::Auth::open(config.data_path("users.json"), config.auth.clone(), audit.clone()));
     // Здесь будут добавляться новые модули заданий
     Router::new()
-        .merge(dashboard::create_routes())
+        .merge(dashboard::create_routes(config, audit))
+        .merge(auth::create_routes(auth.clone()))
         .merge(navigation::create_routes())
         .merge(ajax::create_routes())
-        .merge(snippets::create_routes())
+        .merge(snippets::create_routes(config))
         // .merge(network::network_routes())
         .merge(week2::create_routes())
         .merge(week3::create_routes())
-        .route_service("/dashboard", get_service(ServeFile::new("public/dashboard.html")))
-        .route_service("/navigation", get_service(ServeFile::new("public/navigation.html")))
-        .route_service("/ajax", get_service(ServeFile::new("public/ajax.html")))
-        .route_service("/snippets", get_service(ServeFile::new("public/snippets.html")))
-        .route_service("/snippets.json", get_service(ServeFile::new("public/snippets.json")))
-        .route_service("/network", get_service(ServeFile::new("public/network.html")))
+        .route_service("/dashboard", page("dashboard.html"))
+        .route_service("/navigation", page("navigation.html"))
+        .route_service("/ajax", page("ajax.html"))
+        .route_service("/snippets", page("snippets.html"))
+        .route_service("/snippets.json", get_service(ServeFile::new(config.snippets_path())))
+        .route_service("/network", page("network.html"))
+        .route_service("/signin", page("signin.html"))
+        // Вход и роли — для всех маршрутов выше
+        .route_layer(middleware::from_fn_with_state(auth, auth::require))
 }
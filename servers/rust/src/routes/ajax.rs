@@ -6,6 +6,8 @@ use std::time::Instant;
 use reqwest::{Client, Method};
 use tracing::{info, debug, error};
 
+use super::metrics;
+
 #[derive(Clone)]
 struct AjaxState { http: Client }
 
@@ -41,7 +43,7 @@ async fn proxy(State(state): State<Arc<AjaxState>>, Json(req): Json<ProxyReq>) -
     info!("ajax_proxy request: method={} url={} headers={} body_bytes={}", req.method, req.url, hdrs_count, body_bytes);
 
     let method = req.method.parse::<Method>().unwrap_or(Method::GET);
-    let mut builder = state.http.request(method, &req.url);
+    let mut builder = state.http.request(method.clone(), &req.url);
 
     if let Some(hs) = req.headers.as_ref().and_then(|v| v.as_object()) {
         for (k, v) in hs.iter() {
@@ -58,6 +60,7 @@ async fn proxy(State(state): State<Arc<AjaxState>>, Json(req): Json<ProxyReq>) -
         Ok(r) => r,
         Err(e) => {
             error!("ajax_proxy error sending request: {}", e);
+            metrics::ajax_upstream(method.as_str(), None, started.elapsed());
             return Json(json!({"error": format!("request error: {}", e)}));
         },
     };
@@ -68,6 +71,7 @@ async fn proxy(State(state): State<Arc<AjaxState>>, Json(req): Json<ProxyReq>) -
         headers_obj.insert(k.to_string(), json!(v.to_str().unwrap_or("")));
     }
     let body_text = res.text().await.unwrap_or_default();
+    metrics::ajax_upstream(method.as_str(), Some(status), started.elapsed());
     let elapsed_ms = started.elapsed().as_millis();
     debug!("ajax_proxy response: status={} bytes={} headers={} in {}ms", status, body_text.len(), headers_obj.len(), elapsed_ms);
 
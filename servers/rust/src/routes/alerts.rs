@@ -0,0 +1,564 @@
+// Алерты: фоновая проверка правил [alerts] по последним точкам поллера (/rust/history) и по /data/services
+// подключённых хостов. Срабатывания и снятия пишутся в <data_dir>/alerts.jsonl и уходят на webhook'и
+use axum::{http::StatusCode, Json};
+use chrono::{DateTime, Utc};
+use futures_util::future::join_all;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::{BTreeMap, VecDeque};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::time::MissedTickBehavior;
+use tracing::{debug, error, info, warn};
+
+use crate::config::{AlertRule, AlertsConfig, WebhookSink};
+use super::history::{self, MetricStore};
+use super::relay::{RelayRegistry, RelayTarget};
+use super::relay_models::{ServiceList, SshStatus};
+use super::session_store::write_atomic;
+
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum AlertState {
+    // Условие выполняется, но меньше for_secs; в журнал не попадает
+    Pending,
+    Firing,
+    Resolved,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct AlertEvent {
+    pub ts: DateTime<Utc>,
+    pub rule: String,
+    pub host: String,
+    pub relay: String,
+    pub state: AlertState,
+    pub severity: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub value: Option<f64>,
+    pub message: String,
+    // С какого момента выполняется условие
+    pub since: DateTime<Utc>,
+}
+
+// Текущий алерт (pending или firing) по паре правило + хост
+#[derive(Clone, Serialize)]
+struct Active {
+    rule: String,
+    host: String,
+    relay: String,
+    state: AlertState,
+    severity: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    value: Option<f64>,
+    message: String,
+    since: DateTime<Utc>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    fired_at: Option<DateTime<Utc>>,
+    // Последние данные, в которых условие выполнялось
+    last_seen: DateTime<Utc>,
+}
+
+// Результат одной проверки правила на хосте
+struct Observation {
+    rule: usize,
+    host: String,
+    relay: String,
+    holds: bool,
+    value: Option<f64>,
+    message: String,
+    // Время точки поллера или опроса сервисов
+    at: DateTime<Utc>,
+}
+
+struct Journal {
+    events: VecDeque<AlertEvent>,
+    // Строк в файле; при 2×keep_events файл переписывается последними keep_events
+    lines: usize,
+}
+
+pub struct Alerts {
+    config: AlertsConfig,
+    path: PathBuf,
+    relays: Arc<RelayRegistry>,
+    store: Arc<MetricStore>,
+    http: Client,
+    // Точки старше этого не считаются: хост отключён или поллер отстал
+    stale_ms: i64,
+    active: Mutex<BTreeMap<(String, String), Active>>,
+    journal: Mutex<Journal>,
+}
+
+#[derive(Deserialize)]
+pub struct EventsQuery {
+    pub rule: Option<String>,
+    pub host: Option<String>,
+    pub limit: Option<usize>,
+}
+
+#[derive(Deserialize)]
+pub struct TestReq {
+    // Без имени — на все webhook'и
+    pub sink: Option<String>,
+}
+
+impl Alerts {
+    pub fn open(path: PathBuf, config: AlertsConfig, poll_secs: u64, relays: Arc<RelayRegistry>, store: Arc<MetricStore>, http: Client) -> Alerts {
+        let keep = config.keep_events.max(1);
+        let events: VecDeque<AlertEvent> = fs::read_to_string(&path).unwrap_or_default()
+            .lines()
+            .filter_map(|l| serde_json::from_str(l).ok())
+            .collect();
+        let lines = events.len();
+        let events: VecDeque<_> = events.into_iter().skip(lines.saturating_sub(keep)).collect();
+        // Алерты, которые горели до перезапуска, остаются firing, пока не придут данные или не истечёт stale_ms
+        let now = Utc::now();
+        let mut active = BTreeMap::new();
+        for e in &events {
+            let key = (e.rule.clone(), e.host.clone());
+            if e.state == AlertState::Firing && config.rules.iter().any(|r| r.name == e.rule) {
+                active.insert(key, Active {
+                    rule: e.rule.clone(),
+                    host: e.host.clone(),
+                    relay: e.relay.clone(),
+                    state: AlertState::Firing,
+                    severity: e.severity.clone(),
+                    value: e.value,
+                    message: e.message.clone(),
+                    since: e.since,
+                    fired_at: Some(e.ts),
+                    last_seen: now,
+                });
+            } else {
+                active.remove(&key);
+            }
+        }
+        let stale_ms = 3 * 1000 * poll_secs.max(config.interval_secs).max(1) as i64;
+        Alerts {
+            config,
+            path,
+            relays,
+            store,
+            http,
+            stale_ms,
+            active: Mutex::new(active),
+            journal: Mutex::new(Journal { events, lines }),
+        }
+    }
+
+    pub fn spawn(self: &Arc<Self>) {
+        if !self.config.enabled || self.config.interval_secs == 0 || self.config.rules.is_empty() {
+            info!("alerts disabled ({} rules)", self.config.rules.len());
+            return;
+        }
+        info!("alerts: {} rules, {} webhooks, every {}s", self.config.rules.len(), self.config.webhooks.len(), self.config.interval_secs);
+        let alerts = self.clone();
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(Duration::from_secs(alerts.config.interval_secs));
+            ticker.set_missed_tick_behavior(MissedTickBehavior::Skip);
+            loop {
+                ticker.tick().await;
+                let observations = alerts.observe().await;
+                let events = alerts.apply(observations, Utc::now());
+                alerts.notify(events);
+            }
+        });
+    }
+
+    // В журнал и на webhook'и
+    fn notify(self: &Arc<Self>, events: Vec<AlertEvent>) {
+        for event in events {
+            self.record(&event);
+            let alerts = self.clone();
+            tokio::spawn(async move {
+                for sink in &alerts.config.webhooks {
+                    if let Err(e) = alerts.send(sink, &event).await {
+                        warn!("alerts: webhook {}: {}", sink.name, e);
+                    }
+                }
+            });
+        }
+    }
+
+    async fn observe(&self) -> Vec<Observation> {
+        let now = Utc::now().timestamp_millis();
+        let mut out = vec![];
+        let hosts = self.store.hosts();
+        for (i, rule) in self.config.rules.iter().enumerate().filter(|(_, r)| r.metric != "service") {
+            for h in hosts.iter().filter(|h| applies(rule, &h.host)) {
+                let Some(sample) = self.store.latest(&h.host, &rule.metric) else { continue };
+                if sample.ts < now - self.stale_ms {
+                    continue;
+                }
+                out.push(Observation {
+                    rule: i,
+                    host: h.host.clone(),
+                    relay: h.relay.clone(),
+                    holds: rule.op.holds(sample.value, rule.threshold),
+                    value: Some(sample.value),
+                    message: format!("{} {:.1} {} {} on {}", rule.metric, sample.value, rule.op.as_str(), rule.threshold, h.host),
+                    at: DateTime::from_timestamp_millis(sample.ts).unwrap_or_else(Utc::now),
+                });
+            }
+        }
+        if self.config.rules.iter().any(|r| r.metric == "service") {
+            let targets = history::poll_targets(&self.relays);
+            for batch in join_all(targets.iter().map(|t| self.observe_services(t))).await {
+                out.extend(batch);
+            }
+        }
+        out
+    }
+
+    // Без ответа relay наблюдений нет, и прежнее состояние держится до stale_ms (см. apply)
+    async fn observe_services(&self, target: &RelayTarget) -> Vec<Observation> {
+        let backend = &target.backend;
+        let status: SshStatus = match self.relays.get_json(target, "/ssh/status").await {
+            Ok(status) => status,
+            Err(e) => {
+                debug!("alerts: relay {} status failed: {}", backend.name, e.message());
+                return vec![];
+            }
+        };
+        if !status.connected || status.host.is_empty() {
+            return vec![];
+        }
+        let list: ServiceList = match self.relays.get_json(target, "/data/services").await {
+            Ok(list) => list,
+            Err(e) => {
+                debug!("alerts: relay {} services failed: {}", backend.name, e.message());
+                return vec![];
+            }
+        };
+        self.config.rules.iter().enumerate()
+            .filter(|(_, r)| r.metric == "service" && applies(r, &status.host))
+            .map(|(i, rule)| {
+                let want = rule.service.as_deref().unwrap_or("");
+                // nginx совпадает и с nginx.service
+                let row = list.services.iter().find(|s| s.name == want || s.name.strip_suffix(".service") == Some(want));
+                let (holds, message) = match row {
+                    Some(s) if s.sub == "running" => (false, format!("service {} is running on {}", want, status.host)),
+                    Some(s) => (true, format!("service {} is {}/{} on {}", want, s.active, s.sub, status.host)),
+                    None => (true, format!("service {} is not running on {}", want, status.host)),
+                };
+                Observation { rule: i, host: status.host.clone(), relay: backend.name.clone(), holds, value: None, message, at: Utc::now() }
+            })
+            .collect()
+    }
+
+    // Переходы состояний; возвращает события для журнала и webhook'ов
+    fn apply(&self, observations: Vec<Observation>, now: DateTime<Utc>) -> Vec<AlertEvent> {
+        let Ok(mut active) = self.active.lock() else { return vec![] };
+        let mut seen = vec![];
+        let mut events = vec![];
+        for o in observations {
+            let rule = &self.config.rules[o.rule];
+            let key = (rule.name.clone(), o.host.clone());
+            seen.push(key.clone());
+            if !o.holds {
+                if let Some(a) = active.remove(&key).filter(|a| a.state == AlertState::Firing) {
+                    events.push(event(&a, AlertState::Resolved, now, o.value, o.message));
+                }
+                continue;
+            }
+            let a = active.entry(key).or_insert_with(|| Active {
+                rule: rule.name.clone(),
+                host: o.host.clone(),
+                relay: o.relay.clone(),
+                state: AlertState::Pending,
+                severity: rule.severity.clone(),
+                value: None,
+                message: String::new(),
+                since: now,
+                fired_at: None,
+                last_seen: o.at,
+            });
+            a.last_seen = o.at;
+            a.value = o.value;
+            a.message = o.message;
+            a.relay = o.relay;
+            if a.state == AlertState::Pending && now - a.since >= chrono::Duration::seconds(rule.for_secs as i64) {
+                a.state = AlertState::Firing;
+                a.fired_at = Some(now);
+                events.push(event(a, AlertState::Firing, now, a.value, a.message.clone()));
+            }
+        }
+        // Разовый сбой опроса алерт не снимает; нет данных дольше stale_ms (хост отключился, relay не отвечает) — снимается
+        let stale = chrono::Duration::milliseconds(self.stale_ms);
+        let gone: Vec<_> = active.iter()
+            .filter(|(k, a)| !seen.contains(k) && now - a.last_seen > stale)
+            .map(|(k, _)| k.clone())
+            .collect();
+        for key in gone {
+            if let Some(a) = active.remove(&key).filter(|a| a.state == AlertState::Firing) {
+                let message = format!("no data from {}", a.host);
+                events.push(event(&a, AlertState::Resolved, now, None, message));
+            }
+        }
+        events
+    }
+
+    fn record(&self, event: &AlertEvent) {
+        match event.state {
+            AlertState::Firing => warn!("alert {} firing: {}", event.rule, event.message),
+            _ => info!("alert {} {:?}: {}", event.rule, event.state, event.message),
+        }
+        let Ok(mut journal) = self.journal.lock() else { return };
+        let keep = self.config.keep_events.max(1);
+        journal.events.push_back(event.clone());
+        while journal.events.len() > keep {
+            journal.events.pop_front();
+        }
+        journal.lines += 1;
+        let result = if journal.lines > 2 * keep {
+            journal.lines = journal.events.len();
+            let text: String = journal.events.iter()
+                .filter_map(|e| serde_json::to_string(e).ok())
+                .map(|l| l + "\n")
+                .collect();
+            write_atomic(&self.path, text.as_bytes())
+        } else {
+            self.append(event)
+        };
+        if let Err(e) = result {
+            error!("alerts: {}: {}", self.path.display(), e);
+        }
+    }
+
+    fn append(&self, event: &AlertEvent) -> std::io::Result<()> {
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let mut line = serde_json::to_string(event).map_err(std::io::Error::other)?;
+        line.push('\n');
+        let mut file = OpenOptions::new().create(true).append(true).open(&self.path)?;
+        file.write_all(line.as_bytes())
+    }
+
+    async fn send(&self, sink: &WebhookSink, event: &AlertEvent) -> Result<u16, String> {
+        let mut req = self.http.post(&sink.url)
+            .timeout(Duration::from_millis(sink.timeout_ms))
+            .json(&json!({"source": "rust-server", "alert": event}));
+        for (name, value) in &sink.headers {
+            req = req.header(name, value);
+        }
+        let resp = req.send().await.map_err(|e| e.to_string())?;
+        let status = resp.status();
+        if !status.is_success() {
+            return Err(format!("HTTP {}", status.as_u16()));
+        }
+        Ok(status.as_u16())
+    }
+
+    pub fn describe(&self) -> serde_json::Value {
+        let active: Vec<Active> = self.active.lock().map(|a| a.values().cloned().collect()).unwrap_or_default();
+        // Заголовки могут содержать секреты
+        let webhooks: Vec<_> = self.config.webhooks.iter().map(|w| json!({"name": w.name, "url": w.url})).collect();
+        json!({
+            "enabled": self.config.enabled,
+            "interval_secs": self.config.interval_secs,
+            "rules": self.config.rules,
+            "webhooks": webhooks,
+            "active": active,
+        })
+    }
+
+    // Новые сначала
+    pub fn events(&self, q: &EventsQuery) -> Vec<AlertEvent> {
+        let Ok(journal) = self.journal.lock() else { return vec![] };
+        journal.events.iter().rev()
+            .filter(|e| q.rule.as_deref().is_none_or(|r| r == e.rule))
+            .filter(|e| q.host.as_deref().is_none_or(|h| h == e.host))
+            .take(q.limit.unwrap_or(100))
+            .cloned()
+            .collect()
+    }
+
+    // Пробное событие на webhook'и; в журнал не пишется
+    pub async fn test(&self, q: &TestReq) -> Result<Json<serde_json::Value>, (StatusCode, Json<serde_json::Value>)> {
+        let sinks: Vec<_> = self.config.webhooks.iter()
+            .filter(|w| q.sink.as_deref().is_none_or(|s| s == w.name))
+            .collect();
+        if sinks.is_empty() {
+            let msg = match &q.sink {
+                Some(name) => format!("unknown webhook {}", name),
+                None => "no webhooks configured".to_string(),
+            };
+            return Err((StatusCode::NOT_FOUND, Json(json!({"error": msg}))));
+        }
+        let now = Utc::now();
+        let event = AlertEvent {
+            ts: now,
+            rule: "test".into(),
+            host: "rust-server".into(),
+            relay: String::new(),
+            state: AlertState::Firing,
+            severity: "info".into(),
+            value: None,
+            message: "test alert from rust-server".into(),
+            since: now,
+        };
+        let results = join_all(sinks.iter().map(|sink| async {
+            match self.send(sink, &event).await {
+                Ok(status) => json!({"name": sink.name, "ok": true, "status": status}),
+                Err(e) => json!({"name": sink.name, "ok": false, "error": e}),
+            }
+        })).await;
+        Ok(Json(json!({"results": results})))
+    }
+}
+
+fn applies(rule: &AlertRule, host: &str) -> bool {
+    rule.hosts.is_empty() || rule.hosts.iter().any(|h| h == host)
+}
+
+fn event(a: &Active, state: AlertState, ts: DateTime<Utc>, value: Option<f64>, message: String) -> AlertEvent {
+    AlertEvent {
+        ts,
+        rule: a.rule.clone(),
+        host: a.host.clone(),
+        relay: a.relay.clone(),
+        state,
+        severity: a.severity.clone(),
+        value,
+        message,
+        since: a.since,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{extract::State, http::HeaderMap, routing::post, Router};
+    use crate::config::{AlertOp, Config};
+    use tokio::sync::mpsc;
+
+    type Received = (HeaderMap, serde_json::Value);
+
+    async fn receiver() -> (String, mpsc::UnboundedReceiver<Received>) {
+        let (tx, rx) = mpsc::unbounded_channel();
+        let app = Router::new()
+            .route("/hook", post(|State(tx): State<mpsc::UnboundedSender<Received>>, headers: HeaderMap, Json(body): Json<serde_json::Value>| async move {
+                let _ = tx.send((headers, body));
+                StatusCode::NO_CONTENT
+            }))
+            .with_state(tx);
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        (url, rx)
+    }
+
+    // Правило "nginx не running" с for_secs; stale_ms = 3 × interval_secs = 3s
+    fn alerts(name: &str, url: &str, for_secs: u64) -> (Arc<Alerts>, PathBuf) {
+        let dir = std::env::temp_dir().join(format!("rust-server-alerts-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let config = AlertsConfig {
+            interval_secs: 1,
+            rules: vec![AlertRule {
+                name: "nginx_down".into(),
+                metric: "service".into(),
+                op: AlertOp::Gt,
+                threshold: 0.0,
+                for_secs,
+                service: Some("nginx".into()),
+                hosts: vec![],
+                severity: "critical".into(),
+            }],
+            webhooks: vec![WebhookSink {
+                name: "hook".into(),
+                url: url.into(),
+                headers: BTreeMap::from([("Authorization".to_string(), "Bearer t0ken".to_string())]),
+                timeout_ms: 2_000,
+            }],
+            ..AlertsConfig::default()
+        };
+        let http = Client::new();
+        let relays = Arc::new(RelayRegistry::from_config(&Config::default(), http.clone()));
+        let alerts = Alerts::open(dir.join("alerts.jsonl"), config, 0, relays, Arc::new(MetricStore::new(10)), http);
+        (Arc::new(alerts), dir)
+    }
+
+    fn seen(holds: bool, at: DateTime<Utc>) -> Observation {
+        let message = if holds { "service nginx is inactive/dead on web1" } else { "service nginx is running on web1" };
+        Observation { rule: 0, host: "web1".into(), relay: "default".into(), holds, value: None, message: message.into(), at }
+    }
+
+    fn state(alerts: &Alerts) -> Option<AlertState> {
+        alerts.active.lock().unwrap().get(&("nginx_down".to_string(), "web1".to_string())).map(|a| a.state)
+    }
+
+    async fn next(rx: &mut mpsc::UnboundedReceiver<Received>) -> Received {
+        tokio::time::timeout(Duration::from_secs(5), rx.recv()).await.expect("webhook not called").expect("receiver closed")
+    }
+
+    #[tokio::test]
+    async fn pending_firing_resolved_reach_webhook() {
+        let (url, mut rx) = receiver().await;
+        let (alerts, dir) = alerts("transitions", &url, 60);
+        let t0 = Utc::now();
+
+        assert!(alerts.apply(vec![seen(true, t0)], t0).is_empty());
+        assert_eq!(state(&alerts), Some(AlertState::Pending));
+        let t1 = t0 + chrono::Duration::seconds(30);
+        assert!(alerts.apply(vec![seen(true, t1)], t1).is_empty());
+        assert_eq!(state(&alerts), Some(AlertState::Pending));
+
+        let t2 = t0 + chrono::Duration::seconds(60);
+        let events = alerts.apply(vec![seen(true, t2)], t2);
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].state, AlertState::Firing);
+        assert_eq!(events[0].since, t0);
+        assert_eq!(state(&alerts), Some(AlertState::Firing));
+        alerts.notify(events);
+        let (headers, body) = next(&mut rx).await;
+        assert_eq!(headers.get("authorization").unwrap(), "Bearer t0ken");
+        assert_eq!(headers.get("content-type").unwrap(), "application/json");
+        assert_eq!(body["source"], "rust-server");
+        assert_eq!(body["alert"]["rule"], "nginx_down");
+        assert_eq!(body["alert"]["host"], "web1");
+        assert_eq!(body["alert"]["state"], "firing");
+        assert_eq!(body["alert"]["severity"], "critical");
+        assert_eq!(body["alert"]["message"], "service nginx is inactive/dead on web1");
+
+        let t3 = t2 + chrono::Duration::seconds(30);
+        let events = alerts.apply(vec![seen(false, t3)], t3);
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].state, AlertState::Resolved);
+        assert_eq!(state(&alerts), None);
+        alerts.notify(events);
+        let (_, body) = next(&mut rx).await;
+        assert_eq!(body["alert"]["state"], "resolved");
+        assert_eq!(body["alert"]["message"], "service nginx is running on web1");
+
+        let journal: Vec<_> = alerts.events(&EventsQuery { rule: None, host: None, limit: None }).iter().map(|e| e.state).collect();
+        assert_eq!(journal, vec![AlertState::Resolved, AlertState::Firing]);
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[tokio::test]
+    async fn missed_poll_keeps_firing_until_stale() {
+        let (alerts, dir) = alerts("stale", "http://127.0.0.1:9/hook", 0);
+        let t0 = Utc::now();
+        assert_eq!(alerts.apply(vec![seen(true, t0)], t0).len(), 1);
+
+        // /ssh/status или /data/services не ответили — наблюдений нет
+        let t1 = t0 + chrono::Duration::seconds(2);
+        assert!(alerts.apply(vec![], t1).is_empty());
+        assert_eq!(state(&alerts), Some(AlertState::Firing));
+        let t2 = t0 + chrono::Duration::seconds(3);
+        assert!(alerts.apply(vec![seen(true, t2)], t2).is_empty());
+        assert_eq!(state(&alerts), Some(AlertState::Firing));
+
+        let t3 = t2 + chrono::Duration::seconds(4);
+        let events = alerts.apply(vec![], t3);
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].state, AlertState::Resolved);
+        assert_eq!(events[0].message, "no data from web1");
+        let _ = fs::remove_dir_all(dir);
+    }
+}
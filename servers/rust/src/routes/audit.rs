@@ -0,0 +1,349 @@
+// Журнал аудита: кто, когда и что выполнил на каком хосте. JSON-строки, только дозапись, ротация по размеру
+use axum::{
+    async_trait,
+    extract::{ConnectInfo, FromRequestParts},
+    http::{request::Parts, StatusCode},
+    Json,
+};
+use chrono::{DateTime, Utc};
+use once_cell::sync::OnceCell;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::convert::Infallible;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::net::{IpAddr, SocketAddr};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{mpsc, Arc};
+use std::time::{Duration, Instant};
+use tracing::{error, info, warn};
+
+use crate::config::AuditConfig;
+use super::auth::AuthUser;
+use super::history::parse_since;
+use super::relay::{RelayRegistry, RelayTarget};
+
+const MAX_LIMIT: usize = 5000;
+
+// [auth].trusted_proxies; задаётся один раз при старте
+static TRUSTED_PROXIES: OnceCell<Vec<IpAddr>> = OnceCell::new();
+
+pub fn set_trusted_proxies(proxies: &[String]) {
+    let parsed = proxies.iter().filter_map(|p| match p.parse::<IpAddr>() {
+        Ok(ip) => Some(ip),
+        Err(_) => {
+            warn!("trusted_proxies: {:?} is not an IP address, ignored", p);
+            None
+        }
+    }).collect();
+    let _ = TRUSTED_PROXIES.set(parsed);
+}
+
+pub fn is_trusted_proxy(ip: &str) -> bool {
+    let Ok(ip) = ip.parse::<IpAddr>() else { return false };
+    TRUSTED_PROXIES.get().is_some_and(|p| p.contains(&ip))
+}
+
+// Адрес клиента: сокет и X-Forwarded-For, если соединение пришло от доверенного прокси; плюс вошедший пользователь
+#[derive(Clone)]
+pub struct ClientAddr {
+    pub ip: Option<String>,
+    pub forwarded_for: Option<String>,
+    pub user: Option<AuthUser>,
+}
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for ClientAddr {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let ip = parts.extensions.get::<ConnectInfo<SocketAddr>>().map(|c| c.0.ip().to_string());
+        // От остальных заголовок не берётся: иначе любой клиент впишет в журнал чужой адрес
+        let forwarded_for = parts.headers.get("x-forwarded-for")
+            .filter(|_| ip.as_deref().is_some_and(is_trusted_proxy))
+            .and_then(|v| v.to_str().ok())
+            .map(|s| s.to_string());
+        let user = parts.extensions.get::<AuthUser>().cloned();
+        Ok(ClientAddr { ip, forwarded_for, user })
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditOutcome {
+    Ok,
+    Error,
+    // Отклонено политикой, в relay не ушло
+    Denied,
+    Cancelled,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct AuditEntry {
+    pub ts: DateTime<Utc>,
+    // ssh_execute, ssh_execute_stream, process_action, ssh_connect, server_save, server_delete, server_policy,
+    // login, user_save, user_delete, user_password, token_create, token_revoke
+    pub action: String,
+    pub client_ip: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub forwarded_for: Option<String>,
+    // Пользователь rust-server (не SSH); None — вход выключен
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub actor: Option<String>,
+    // Имя API-токена, если запрос шёл по нему
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub token: Option<String>,
+    pub relay: Option<String>,
+    pub session: Option<String>,
+    pub host: Option<String>,
+    pub user: Option<String>,
+    // Имя сохранённого сервера
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub server: Option<String>,
+    // Полная команда; для process_action — "kill 1234"
+    pub command: Option<String>,
+    // Что изменилось у сохранённого сервера (без секретов)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub details: Option<serde_json::Value>,
+    pub outcome: AuditOutcome,
+    pub exit_status: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    pub duration_ms: u64,
+    pub bytes_in: u64,
+    pub bytes_out: u64,
+    #[serde(skip)]
+    started: Option<Instant>,
+}
+
+impl AuditEntry {
+    pub fn new(action: &str, client: &ClientAddr) -> AuditEntry {
+        AuditEntry {
+            ts: Utc::now(),
+            action: action.to_string(),
+            client_ip: client.ip.clone(),
+            forwarded_for: client.forwarded_for.clone(),
+            actor: client.user.as_ref().map(|u| u.name.clone()),
+            token: client.user.as_ref().and_then(|u| u.token.clone()),
+            relay: None,
+            session: None,
+            host: None,
+            user: None,
+            server: None,
+            command: None,
+            details: None,
+            outcome: AuditOutcome::Ok,
+            exit_status: None,
+            error: None,
+            duration_ms: 0,
+            bytes_in: 0,
+            bytes_out: 0,
+            started: Some(Instant::now()),
+        }
+    }
+
+    // Relay, сессия и её хост
+    pub fn on(mut self, registry: &RelayRegistry, target: &RelayTarget) -> AuditEntry {
+        self.relay = Some(target.backend.name.clone());
+        self.session = target.session.clone();
+        if let Some(info) = target.session.as_deref().and_then(|id| registry.sessions.get(id)) {
+            self.host = Some(info.host);
+            self.user = Some(info.user);
+            self.server = info.server;
+        }
+        self
+    }
+
+    pub fn command(mut self, command: &str) -> AuditEntry {
+        self.bytes_in = command.len() as u64;
+        self.command = Some(command.to_string());
+        self
+    }
+
+    pub fn ok(mut self, bytes_out: usize) -> AuditEntry {
+        self.outcome = AuditOutcome::Ok;
+        self.bytes_out = bytes_out as u64;
+        self
+    }
+
+    pub fn exit(mut self, code: Option<i64>) -> AuditEntry {
+        self.exit_status = code;
+        self
+    }
+
+    // Код возврата достаём из сообщения relay "Process exited with status N"
+    pub fn failed(mut self, outcome: AuditOutcome, error: &str) -> AuditEntry {
+        self.outcome = outcome;
+        self.exit_status = exit_status_of(error);
+        self.error = Some(error.to_string());
+        self
+    }
+}
+
+pub fn exit_status_of(msg: &str) -> Option<i64> {
+    let rest = &msg[msg.rfind("exited with status ")? + "exited with status ".len()..];
+    rest.split(|c: char| !c.is_ascii_digit()).next()?.parse().ok()
+}
+
+pub struct AuditLog {
+    path: PathBuf,
+    config: AuditConfig,
+    // Строки пишет отдельный поток: обработчики не ждут диска
+    lines: Option<mpsc::Sender<String>>,
+    rotations: Arc<AtomicU64>,
+}
+
+// Единственный, кто пишет в файлы журнала
+struct Writer {
+    path: PathBuf,
+    config: AuditConfig,
+    // Нечётное — идёт ротация; query перечитывает файлы, если число изменилось, пока он читал
+    rotations: Arc<AtomicU64>,
+}
+
+fn rotated(path: &Path, n: u32) -> PathBuf {
+    let mut name = path.to_path_buf().into_os_string();
+    name.push(format!(".{}", n));
+    name.into()
+}
+
+#[derive(Deserialize)]
+pub struct AuditQuery {
+    pub host: Option<String>,
+    pub action: Option<String>,
+    pub actor: Option<String>,
+    pub since: Option<String>,
+    pub until: Option<String>,
+    pub limit: Option<usize>,
+}
+
+impl AuditLog {
+    pub fn open(path: PathBuf, config: AuditConfig) -> AuditLog {
+        let rotations = Arc::new(AtomicU64::new(0));
+        if !config.enabled {
+            info!("audit log disabled");
+            return AuditLog { path, config, lines: None, rotations };
+        }
+        info!("audit log: {}", path.display());
+        let (tx, rx) = mpsc::channel::<String>();
+        let writer = Writer { path: path.clone(), config: config.clone(), rotations: rotations.clone() };
+        let spawned = std::thread::Builder::new().name("audit-writer".into()).spawn(move || {
+            for line in rx {
+                if let Err(e) = writer.append(line.as_bytes()) {
+                    error!("audit log {}: {}", writer.path.display(), e);
+                }
+            }
+        });
+        let lines = match spawned {
+            Ok(_) => Some(tx),
+            Err(e) => {
+                error!("audit log: writer thread: {}", e);
+                None
+            }
+        };
+        AuditLog { path, config, lines, rotations }
+    }
+
+    pub fn record(&self, mut entry: AuditEntry) {
+        if !self.config.enabled {
+            return;
+        }
+        if let Some(started) = entry.started.take() {
+            entry.duration_ms = started.elapsed().as_millis() as u64;
+        }
+        let Ok(mut line) = serde_json::to_string(&entry) else { return };
+        line.push('\n');
+        if let Some(lines) = &self.lines {
+            if lines.send(line).is_err() {
+                error!("audit log {}: writer stopped", self.path.display());
+            }
+        }
+    }
+
+    // GET /rust/audit?host=&action=&actor=&since=&until=&limit= — новые записи первыми
+    pub fn query(&self, q: &AuditQuery) -> Result<Json<serde_json::Value>, (StatusCode, Json<serde_json::Value>)> {
+        let bad = |msg: String| (StatusCode::BAD_REQUEST, Json(json!({"error": msg})));
+        let since = parse_since(q.since.as_deref()).map_err(bad)?;
+        let until = match q.until.as_deref().filter(|s| !s.trim().is_empty()) {
+            Some(u) => parse_since(Some(u)).map_err(|e| bad(e.replace("since", "until")))?,
+            None => i64::MAX,
+        };
+        let limit = q.limit.unwrap_or(200).clamp(1, MAX_LIMIT);
+        let matches = |e: &AuditEntry| {
+            let ts = e.ts.timestamp_millis();
+            ts >= since && ts <= until
+                && q.host.as_deref().is_none_or(|h| e.host.as_deref() == Some(h))
+                && q.action.as_deref().is_none_or(|a| e.action == a)
+                && q.actor.as_deref().is_none_or(|a| e.actor.as_deref() == Some(a))
+        };
+        // Файлы читаются без блокировки писателя; если за это время прошла ротация — читаем заново
+        let mut attempt = 0;
+        loop {
+            attempt += 1;
+            let before = self.rotations.load(Ordering::SeqCst);
+            if before % 2 == 1 && attempt < 5 {
+                std::thread::sleep(Duration::from_millis(10));
+                continue;
+            }
+            let (entries, truncated) = self.scan(&matches, limit);
+            if self.rotations.load(Ordering::SeqCst) == before || attempt >= 5 {
+                return Ok(Json(json!({"entries": entries, "count": entries.len(), "truncated": truncated})));
+            }
+        }
+    }
+
+    fn scan(&self, matches: &impl Fn(&AuditEntry) -> bool, limit: usize) -> (Vec<AuditEntry>, bool) {
+        let files = std::iter::once(self.path.clone()).chain((1..=self.config.keep_files).map(|n| rotated(&self.path, n)));
+        let mut entries = vec![];
+        let mut truncated = false;
+        'files: for path in files {
+            let Ok(text) = fs::read_to_string(&path) else { continue };
+            for line in text.lines().rev() {
+                let Ok(entry) = serde_json::from_str::<AuditEntry>(line) else { continue };
+                // ts — начало действия, а пишется запись по окончании: порядок в файле почти, но не строго по времени
+                if matches(&entry) {
+                    if entries.len() == limit {
+                        truncated = true;
+                        break 'files;
+                    }
+                    entries.push(entry);
+                }
+            }
+        }
+        (entries, truncated)
+    }
+}
+
+impl Writer {
+    fn append(&self, line: &[u8]) -> std::io::Result<()> {
+        let size = fs::metadata(&self.path).map(|m| m.len()).unwrap_or(0);
+        if size > 0 && size + line.len() as u64 > self.config.max_file_mb * 1024 * 1024 {
+            self.rotations.fetch_add(1, Ordering::SeqCst);
+            let result = self.rotate();
+            self.rotations.fetch_add(1, Ordering::SeqCst);
+            result?;
+        }
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let mut file = OpenOptions::new().create(true).append(true).open(&self.path)?;
+        file.write_all(line)
+    }
+
+    // audit.jsonl -> .1 -> .2 ...; последний удаляется
+    fn rotate(&self) -> std::io::Result<()> {
+        let keep = self.config.keep_files;
+        if keep == 0 {
+            return fs::remove_file(&self.path);
+        }
+        for n in (1..keep).rev() {
+            let from = rotated(&self.path, n);
+            if from.exists() {
+                fs::rename(&from, rotated(&self.path, n + 1))?;
+            }
+        }
+        info!("audit log: rotating {}", self.path.display());
+        fs::rename(&self.path, rotated(&self.path, 1))
+    }
+}
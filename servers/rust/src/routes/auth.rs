@@ -0,0 +1,679 @@
+// Вход в rust-server: локальные пользователи (пароли — Argon2id), API-токены и cookie-сессии для страниц public/*.html.
+// Роли вложены: viewer < operator < admin
+use argon2::{password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString}, Argon2};
+use axum::{
+    async_trait,
+    extract::{FromRequestParts, Request, State},
+    http::{header, request::Parts, HeaderMap, HeaderValue, Method, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Redirect, Response},
+    routing::{get, post},
+    Json, Router,
+};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD as B64URL, Engine};
+use blake2::{Blake2b512, Digest};
+use chrono::{DateTime, Utc};
+use rand::{distributions::Alphanumeric, Rng, RngCore};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tracing::{debug, error, info, warn};
+
+use crate::config::AuthConfig;
+use super::audit::{is_trusted_proxy, AuditEntry, AuditLog, AuditOutcome, ClientAddr};
+use super::session_store::write_atomic;
+
+const FORMAT_VERSION: u32 = 1;
+const SESSION_COOKIE: &str = "rust_session";
+// rst_<id>_<secret>: по id ищется запись, хранится только хеш secret
+const TOKEN_PREFIX: &str = "rst_";
+const FAILED_LOGIN_WINDOW: Duration = Duration::from_secs(15 * 60);
+const MIN_PASSWORD_LEN: usize = 8;
+// Случайный пароль admin при первом запуске, рядом с users.json
+const ADMIN_PASSWORD_FILE: &str = "admin-password.txt";
+
+type ApiError = (StatusCode, Json<serde_json::Value>);
+type ApiResult = Result<Json<serde_json::Value>, ApiError>;
+
+fn fail(status: StatusCode, msg: impl Into<String>) -> ApiError {
+    (status, Json(json!({"error": msg.into()})))
+}
+
+// Текст ошибки для аудита
+fn outcome(result: &Result<(), ApiError>) -> Result<(), &str> {
+    result.as_ref().map(|_| ()).map_err(|(_, e)| e.0["error"].as_str().unwrap_or(""))
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    // /rust/data/*, статус, история, списки
+    Viewer,
+    // Команды, process_action, терминал, подключение
+    Operator,
+    // Сохранённые серверы, аудит, пользователи
+    Admin,
+}
+
+impl fmt::Display for Role {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Role::Viewer => "viewer",
+            Role::Operator => "operator",
+            Role::Admin => "admin",
+        })
+    }
+}
+
+// Минимальная роль для маршрута; None — открыт без входа
+pub fn required_role(path: &str) -> Option<Role> {
+    let role = match path {
+        "/signin" | "/rust/auth/login" | "/rust/auth/logout" => return None,
+        "/rust/servers/list" | "/rust/ssh/execute/running" => Role::Viewer,
+        "/rust/audit" | "/rust/runbooks/save" | "/rust/runbooks/delete" | "/rust/jobs/save" | "/rust/jobs/delete"
+        | "/rust/alerts/test" | "/rust/inventory/update" | "/rust/inventory/import" => Role::Admin,
+        p if p.starts_with("/rust/servers/") || p.starts_with("/rust/auth/users") => Role::Admin,
+        "/rust/ssh/connect" | "/rust/ssh/disconnect" | "/rust/ssh/terminal" | "/rust/process/action"
+        | "/rust/session/save" | "/rust/snippets/save" | "/rust/snippets/delete" | "/rust/ajax/proxy"
+        | "/rust/runbooks/run" | "/rust/runbooks/cancel" | "/rust/jobs/run" => Role::Operator,
+        p if p.starts_with("/rust/ssh/execute") || p.starts_with("/rust/files/") || p.starts_with("/rust/logs/") => Role::Operator,
+        _ => Role::Viewer,
+    };
+    Some(role)
+}
+
+// Кто выполняет запрос: middleware кладёт в extensions после проверки cookie или токена
+#[derive(Clone, Serialize)]
+pub struct AuthUser {
+    pub name: String,
+    pub role: Role,
+    // Имя API-токена, если вход по нему
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub token: Option<String>,
+}
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for AuthUser {
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        parts.extensions.get::<AuthUser>().cloned()
+            .ok_or_else(|| fail(StatusCode::UNAUTHORIZED, "not signed in (or authentication is disabled)"))
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+struct StoredUser {
+    name: String,
+    role: Role,
+    // PHC-строка Argon2id
+    password_hash: String,
+    created_at: DateTime<Utc>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+struct StoredToken {
+    id: String,
+    name: String,
+    user: String,
+    // Не выше роли владельца: при понижении пользователя понижается и токен
+    role: Role,
+    // Blake2b-512 от secret: токен случайный, медленный хеш на каждый запрос не нужен
+    hash: String,
+    created_at: DateTime<Utc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    expires_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct UsersFile {
+    version: u32,
+    users: Vec<StoredUser>,
+    tokens: Vec<StoredToken>,
+}
+
+impl Default for UsersFile {
+    fn default() -> Self {
+        UsersFile { version: FORMAT_VERSION, users: vec![], tokens: vec![] }
+    }
+}
+
+// Cookie-сессии только в памяти: после перезапуска нужно войти заново
+struct Session {
+    user: String,
+    expires: Instant,
+}
+
+pub struct Auth {
+    path: PathBuf,
+    config: AuthConfig,
+    audit: Arc<AuditLog>,
+    file: Mutex<UsersFile>,
+    sessions: Mutex<HashMap<String, Session>>,
+    // Неудачные входы по адресу клиента: сколько и с какого момента
+    failures: Mutex<HashMap<String, (u32, Instant)>>,
+}
+
+// Случайный пароль не идёт через tracing и stderr: логи уходят в сборщики. Файл 0600 создаётся один раз
+fn save_admin_password(path: &Path, password: &str) -> std::io::Result<()> {
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    options.open(path).and_then(|mut f| writeln!(f, "{}", password))
+}
+
+fn hash_password(password: &str) -> Result<String, String> {
+    let mut salt = [0u8; 16];
+    rand::thread_rng().fill_bytes(&mut salt);
+    let salt = SaltString::encode_b64(&salt).map_err(|e| e.to_string())?;
+    Argon2::default().hash_password(password.as_bytes(), &salt)
+        .map(|h| h.to_string())
+        .map_err(|e| format!("password hashing failed: {}", e))
+}
+
+fn verify_password(password: &str, hash: &str) -> bool {
+    PasswordHash::new(hash)
+        .is_ok_and(|h| Argon2::default().verify_password(password.as_bytes(), &h).is_ok())
+}
+
+// Argon2 — десятки миллисекунд CPU, не в потоке рантайма
+async fn blocking<T: Send + 'static>(f: impl FnOnce() -> T + Send + 'static) -> Result<T, ApiError> {
+    tokio::task::spawn_blocking(f).await.map_err(|e| fail(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))
+}
+
+fn token_hash(secret: &str) -> String {
+    Blake2b512::digest(secret.as_bytes()).iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn random_secret() -> String {
+    let mut bytes = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    B64URL.encode(bytes)
+}
+
+fn valid_name(name: &str) -> bool {
+    !name.is_empty() && name.len() <= 64 && name.chars().all(|c| c.is_ascii_alphanumeric() || "._-@".contains(c))
+}
+
+fn cookie<'a>(headers: &'a HeaderMap, name: &str) -> Option<&'a str> {
+    headers.get_all(header::COOKIE).iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(';'))
+        .filter_map(|pair| pair.trim().split_once('='))
+        .find(|(k, _)| *k == name)
+        .map(|(_, v)| v)
+}
+
+impl Auth {
+    pub fn open(path: PathBuf, config: AuthConfig, audit: Arc<AuditLog>) -> Auth {
+        let (file, loaded) = match Self::read(&path) {
+            Ok(file) => (file, true),
+            Err(e) => {
+                error!("auth: {}: nobody can sign in until the file is fixed", e);
+                (UsersFile::default(), false)
+            }
+        };
+        let auth = Auth {
+            path,
+            config,
+            audit,
+            file: Mutex::new(file),
+            sessions: Mutex::new(HashMap::new()),
+            failures: Mutex::new(HashMap::new()),
+        };
+        if !auth.config.enabled {
+            warn!("authentication is disabled: every route is open");
+        } else if loaded {
+            auth.bootstrap();
+        }
+        auth
+    }
+
+    fn read(path: &PathBuf) -> Result<UsersFile, String> {
+        if !path.exists() {
+            return Ok(UsersFile::default());
+        }
+        let text = fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        serde_json::from_str(&text).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    fn write(&self, file: &UsersFile) -> Result<(), ApiError> {
+        let json = serde_json::to_vec_pretty(file).map_err(|e| fail(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+        if let Some(dir) = self.path.parent() {
+            let _ = fs::create_dir_all(dir);
+        }
+        write_atomic(&self.path, &json).map_err(|e| {
+            error!("auth: cannot write {}: {}", self.path.display(), e);
+            fail(StatusCode::INTERNAL_SERVER_ERROR, format!("cannot write users file: {}", e))
+        })
+    }
+
+    // Первый запуск: admin с паролем из RUST_SERVER_ADMIN_PASSWORD или случайным (в файл рядом с users.json)
+    fn bootstrap(&self) {
+        let Ok(mut file) = self.file.lock() else { return };
+        if !file.users.is_empty() {
+            info!("auth: {} users, {} API tokens", file.users.len(), file.tokens.len());
+            return;
+        }
+        let (password, generated) = match std::env::var("RUST_SERVER_ADMIN_PASSWORD").ok().filter(|p| !p.is_empty()) {
+            Some(p) => (p, false),
+            None => (rand::thread_rng().sample_iter(&Alphanumeric).take(20).map(char::from).collect(), true),
+        };
+        let password_hash = match hash_password(&password) {
+            Ok(h) => h,
+            Err(e) => return error!("auth: cannot create the admin user: {}", e),
+        };
+        // Случайный пароль сначала в файл: если его некуда записать, admin без известного пароля не нужен
+        let password_file = self.path.with_file_name(ADMIN_PASSWORD_FILE);
+        if generated {
+            if let Err(e) = save_admin_password(&password_file, &password) {
+                return error!("auth: cannot write {} ({}), the admin user is not created; \
+                    set RUST_SERVER_ADMIN_PASSWORD or fix the permissions of the data directory", password_file.display(), e);
+            }
+        }
+        file.users.push(StoredUser { name: "admin".into(), role: Role::Admin, password_hash, created_at: Utc::now() });
+        if self.write(&file).is_err() {
+            file.users.clear();
+            if generated {
+                let _ = fs::remove_file(&password_file);
+            }
+            return;
+        }
+        if generated {
+            warn!("auth: created user \"admin\", the password is in {} — change it via /rust/auth/password and delete the file", password_file.display());
+        } else {
+            info!("auth: created user \"admin\" from RUST_SERVER_ADMIN_PASSWORD");
+        }
+    }
+
+    // Адрес для ограничения входов: X-Forwarded-For только от доверенного прокси, иначе его подделает кто угодно.
+    // Из цепочки берётся самый правый адрес, который не является доверенным прокси
+    fn client_key(&self, client: &ClientAddr) -> String {
+        let peer = client.ip.clone().unwrap_or_default();
+        if !is_trusted_proxy(&peer) {
+            return peer;
+        }
+        client.forwarded_for.as_deref()
+            .and_then(|xff| xff.rsplit(',').map(str::trim).find(|ip| !is_trusted_proxy(ip)))
+            .filter(|ip| !ip.is_empty())
+            .map(|ip| ip.to_string())
+            .unwrap_or(peer)
+    }
+
+    // Bearer-токен или cookie сессии; роль берётся у пользователя сейчас, а не на момент входа
+    fn identify(&self, headers: &HeaderMap) -> Option<AuthUser> {
+        let bearer = headers.get(header::AUTHORIZATION)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.strip_prefix("Bearer "));
+        if let Some(token) = bearer {
+            return self.by_token(token.trim());
+        }
+        let sid = cookie(headers, SESSION_COOKIE)?;
+        let user = {
+            let mut sessions = self.sessions.lock().ok()?;
+            match sessions.get(sid) {
+                Some(s) if s.expires > Instant::now() => s.user.clone(),
+                Some(_) => {
+                    sessions.remove(sid);
+                    return None;
+                }
+                None => return None,
+            }
+        };
+        let file = self.file.lock().ok()?;
+        let u = file.users.iter().find(|u| u.name == user)?;
+        Some(AuthUser { name: u.name.clone(), role: u.role, token: None })
+    }
+
+    fn by_token(&self, token: &str) -> Option<AuthUser> {
+        let (id, secret) = token.strip_prefix(TOKEN_PREFIX)?.split_once('_')?;
+        let file = self.file.lock().ok()?;
+        let t = file.tokens.iter().find(|t| t.id == id)?;
+        if t.hash != token_hash(secret) || t.expires_at.is_some_and(|e| e < Utc::now()) {
+            return None;
+        }
+        let owner = file.users.iter().find(|u| u.name == t.user)?;
+        Some(AuthUser { name: owner.name.clone(), role: t.role.min(owner.role), token: Some(t.name.clone()) })
+    }
+
+    fn set_cookie(&self, value: &str, max_age: u64) -> HeaderValue {
+        let secure = if self.config.secure_cookie { "; Secure" } else { "" };
+        let cookie = format!("{}={}; Path=/; HttpOnly; SameSite=Strict; Max-Age={}{}", SESSION_COOKIE, value, max_age, secure);
+        HeaderValue::from_str(&cookie).unwrap_or(HeaderValue::from_static(""))
+    }
+
+    fn throttled(&self, client: &str) -> bool {
+        let max = self.config.max_failed_logins;
+        let Ok(mut failures) = self.failures.lock() else { return false };
+        failures.retain(|_, (_, since)| since.elapsed() < FAILED_LOGIN_WINDOW);
+        max > 0 && failures.get(client).is_some_and(|(n, _)| *n >= max)
+    }
+
+    fn login_failed(&self, client: &str) {
+        if let Ok(mut failures) = self.failures.lock() {
+            failures.entry(client.to_string()).or_insert((0, Instant::now())).0 += 1;
+        }
+    }
+
+    fn drop_sessions(&self, user: &str) {
+        if let Ok(mut sessions) = self.sessions.lock() {
+            sessions.retain(|_, s| s.user != user);
+        }
+    }
+
+    fn audit(&self, action: &str, client: &ClientAddr, details: serde_json::Value, result: Result<(), &str>) {
+        let mut entry = AuditEntry::new(action, client);
+        // Вход ещё без пользователя в запросе: кто вошёл, видно по имени
+        if action == "login" && result.is_ok() {
+            entry.actor = details["name"].as_str().map(|s| s.to_string());
+        }
+        entry.details = Some(details);
+        self.audit.record(match result {
+            Ok(()) => entry.ok(0),
+            Err(e) => entry.failed(AuditOutcome::Error, e),
+        });
+    }
+}
+
+// Middleware для всех маршрутов create_task_routes
+pub async fn require(State(auth): State<Arc<Auth>>, mut req: Request, next: Next) -> Response {
+    if !auth.config.enabled {
+        return next.run(req).await;
+    }
+    let user = auth.identify(req.headers());
+    if let Some(user) = &user {
+        req.extensions_mut().insert(user.clone());
+    }
+    let Some(need) = required_role(req.uri().path()) else { return next.run(req).await };
+    match user {
+        Some(user) if user.role >= need => next.run(req).await,
+        Some(user) => {
+            debug!("auth: {} ({}) needs {} for {}", user.name, user.role, need, req.uri().path());
+            (StatusCode::FORBIDDEN, Json(json!({"error": format!("{} role required", need), "role": user.role, "required": need}))).into_response()
+        }
+        // Страницы открываются в браузере — на вход, API и /metrics отвечают 401
+        None if req.method() == Method::GET && !req.uri().path().starts_with("/rust/") && req.uri().path() != "/metrics" => {
+            Redirect::to(&format!("/signin?next={}", req.uri().path())).into_response()
+        }
+        None => (StatusCode::UNAUTHORIZED, Json(json!({"error": "authentication required"}))).into_response(),
+    }
+}
+
+pub fn create_routes(auth: Arc<Auth>) -> Router {
+    Router::new()
+        .route("/rust/auth/login", post(login))
+        .route("/rust/auth/logout", post(logout))
+        .route("/rust/auth/me", get(me))
+        .route("/rust/auth/password", post(change_password))
+        .route("/rust/auth/users", get(list_users))
+        .route("/rust/auth/users/save", post(save_user))
+        .route("/rust/auth/users/delete", post(delete_user))
+        .route("/rust/auth/tokens", get(list_tokens))
+        .route("/rust/auth/tokens/create", post(create_token))
+        .route("/rust/auth/tokens/revoke", post(revoke_token))
+        .with_state(auth)
+}
+
+#[derive(Deserialize)]
+struct LoginReq { name: String, password: String }
+
+async fn login(State(auth): State<Arc<Auth>>, client: ClientAddr, Json(body): Json<LoginReq>) -> Response {
+    info!("login: name={}", body.name);
+    if !auth.config.enabled {
+        return fail(StatusCode::BAD_REQUEST, "authentication is disabled").into_response();
+    }
+    let key = auth.client_key(&client);
+    let details = json!({"name": body.name});
+    if auth.throttled(&key) {
+        auth.audit("login", &client, details, Err("too many failed logins"));
+        return fail(StatusCode::TOO_MANY_REQUESTS, "too many failed logins, try again later").into_response();
+    }
+    let user = auth.file.lock().ok().and_then(|f| f.users.iter().find(|u| u.name == body.name).cloned());
+    let ok = match user.clone() {
+        Some(u) => blocking(move || verify_password(&body.password, &u.password_hash)).await.unwrap_or(false),
+        None => false,
+    };
+    let Some(user) = user.filter(|_| ok) else {
+        auth.login_failed(&key);
+        auth.audit("login", &client, details, Err("invalid name or password"));
+        return fail(StatusCode::UNAUTHORIZED, "invalid name or password").into_response();
+    };
+    if let Ok(mut failures) = auth.failures.lock() {
+        failures.remove(&key);
+    }
+    let sid = random_secret();
+    let ttl = Duration::from_secs(auth.config.session_ttl_hours.max(1) * 3600);
+    if let Ok(mut sessions) = auth.sessions.lock() {
+        sessions.retain(|_, s| s.expires > Instant::now());
+        sessions.insert(sid.clone(), Session { user: user.name.clone(), expires: Instant::now() + ttl });
+    }
+    auth.audit("login", &client, details, Ok(()));
+    let mut response = Json(json!({"ok": true, "user": {"name": user.name, "role": user.role}})).into_response();
+    response.headers_mut().insert(header::SET_COOKIE, auth.set_cookie(&sid, ttl.as_secs()));
+    response
+}
+
+async fn logout(State(auth): State<Arc<Auth>>, headers: HeaderMap) -> Response {
+    if let Some(sid) = cookie(&headers, SESSION_COOKIE) {
+        if let Ok(mut sessions) = auth.sessions.lock() {
+            sessions.remove(sid);
+        }
+    }
+    let mut response = Json(json!({"ok": true})).into_response();
+    response.headers_mut().insert(header::SET_COOKIE, auth.set_cookie("", 0));
+    response
+}
+
+async fn me(State(auth): State<Arc<Auth>>, user: Option<AuthUser>) -> impl IntoResponse {
+    Json(json!({"enabled": auth.config.enabled, "user": user}))
+}
+
+#[derive(Deserialize)]
+struct PasswordReq { current: String, new: String }
+
+async fn change_password(State(auth): State<Arc<Auth>>, client: ClientAddr, user: AuthUser, Json(body): Json<PasswordReq>) -> ApiResult {
+    info!("change_password: name={}", user.name);
+    if user.token.is_some() {
+        return Err(fail(StatusCode::FORBIDDEN, "sign in with a password to change it"));
+    }
+    if body.new.len() < MIN_PASSWORD_LEN {
+        return Err(fail(StatusCode::BAD_REQUEST, format!("password must be at least {} characters", MIN_PASSWORD_LEN)));
+    }
+    let current = auth.file.lock().ok()
+        .and_then(|f| f.users.iter().find(|u| u.name == user.name).map(|u| u.password_hash.clone()))
+        .ok_or_else(|| fail(StatusCode::NOT_FOUND, "user not found"))?;
+    let (ok, hash) = blocking(move || (verify_password(&body.current, &current), hash_password(&body.new))).await?;
+    if !ok {
+        auth.audit("user_password", &client, json!({"name": user.name}), Err("wrong current password"));
+        return Err(fail(StatusCode::UNAUTHORIZED, "wrong current password"));
+    }
+    let hash = hash.map_err(|e| fail(StatusCode::INTERNAL_SERVER_ERROR, e))?;
+    {
+        let mut file = auth.file.lock().map_err(|_| fail(StatusCode::INTERNAL_SERVER_ERROR, "lock poisoned"))?;
+        let Some(u) = file.users.iter_mut().find(|u| u.name == user.name) else {
+            return Err(fail(StatusCode::NOT_FOUND, "user not found"));
+        };
+        u.password_hash = hash;
+        auth.write(&file)?;
+    }
+    auth.audit("user_password", &client, json!({"name": user.name}), Ok(()));
+    Ok(Json(json!({"ok": true})))
+}
+
+async fn list_users(State(auth): State<Arc<Auth>>) -> ApiResult {
+    debug!("list_users");
+    let file = auth.file.lock().map_err(|_| fail(StatusCode::INTERNAL_SERVER_ERROR, "lock poisoned"))?;
+    let users: Vec<_> = file.users.iter().map(|u| json!({
+        "name": u.name,
+        "role": u.role,
+        "created_at": u.created_at,
+        "tokens": file.tokens.iter().filter(|t| t.user == u.name).count(),
+    })).collect();
+    Ok(Json(json!({"users": users})))
+}
+
+#[derive(Deserialize)]
+struct SaveUserReq {
+    name: String,
+    role: Role,
+    // Обязателен для нового пользователя; у существующего без поля пароль не меняется
+    password: Option<String>,
+}
+
+async fn save_user(State(auth): State<Arc<Auth>>, client: ClientAddr, Json(body): Json<SaveUserReq>) -> ApiResult {
+    info!("save_user: name={} role={}", body.name, body.role);
+    if !valid_name(&body.name) {
+        return Err(fail(StatusCode::BAD_REQUEST, "name: 1-64 characters of a-z, 0-9, . _ - @"));
+    }
+    let hash = match body.password {
+        Some(p) if p.len() < MIN_PASSWORD_LEN => {
+            return Err(fail(StatusCode::BAD_REQUEST, format!("password must be at least {} characters", MIN_PASSWORD_LEN)));
+        }
+        Some(p) => Some(blocking(move || hash_password(&p)).await?.map_err(|e| fail(StatusCode::INTERNAL_SERVER_ERROR, e))?),
+        None => None,
+    };
+    let password_changed = hash.is_some();
+    let details = json!({"name": body.name, "role": body.role, "password_changed": password_changed});
+    let result = (|| {
+        let mut file = auth.file.lock().map_err(|_| fail(StatusCode::INTERNAL_SERVER_ERROR, "lock poisoned"))?;
+        let admins = file.users.iter().filter(|u| u.role == Role::Admin && u.name != body.name).count();
+        match file.users.iter_mut().find(|u| u.name == body.name) {
+            Some(u) => {
+                if u.role == Role::Admin && body.role != Role::Admin && admins == 0 {
+                    return Err(fail(StatusCode::CONFLICT, "cannot demote the last admin"));
+                }
+                u.role = body.role;
+                if let Some(h) = hash {
+                    u.password_hash = h;
+                }
+            }
+            None => {
+                let Some(password_hash) = hash else {
+                    return Err(fail(StatusCode::BAD_REQUEST, "password is required for a new user"));
+                };
+                file.users.push(StoredUser { name: body.name.clone(), role: body.role, password_hash, created_at: Utc::now() });
+            }
+        }
+        // Токены не могут быть выше новой роли
+        for t in file.tokens.iter_mut().filter(|t| t.user == body.name) {
+            t.role = t.role.min(body.role);
+        }
+        auth.write(&file)
+    })();
+    auth.audit("user_save", &client, details, outcome(&result));
+    result?;
+    // Новый пароль — старые входы больше не действуют
+    if password_changed {
+        auth.drop_sessions(&body.name);
+    }
+    Ok(Json(json!({"ok": true})))
+}
+
+#[derive(Deserialize)]
+struct DeleteUserReq { name: String }
+
+async fn delete_user(State(auth): State<Arc<Auth>>, client: ClientAddr, Json(body): Json<DeleteUserReq>) -> ApiResult {
+    info!("delete_user: name={}", body.name);
+    let result = (|| {
+        let mut file = auth.file.lock().map_err(|_| fail(StatusCode::INTERNAL_SERVER_ERROR, "lock poisoned"))?;
+        let Some(user) = file.users.iter().find(|u| u.name == body.name) else {
+            return Err(fail(StatusCode::NOT_FOUND, format!("user not found: {}", body.name)));
+        };
+        if user.role == Role::Admin && file.users.iter().filter(|u| u.role == Role::Admin).count() == 1 {
+            return Err(fail(StatusCode::CONFLICT, "cannot delete the last admin"));
+        }
+        file.users.retain(|u| u.name != body.name);
+        file.tokens.retain(|t| t.user != body.name);
+        auth.write(&file)
+    })();
+    auth.audit("user_delete", &client, json!({"name": body.name}), outcome(&result));
+    result?;
+    auth.drop_sessions(&body.name);
+    Ok(Json(json!({"ok": true})))
+}
+
+fn token_view(t: &StoredToken) -> serde_json::Value {
+    json!({"id": t.id, "name": t.name, "user": t.user, "role": t.role, "created_at": t.created_at, "expires_at": t.expires_at})
+}
+
+// Свои токены; admin видит все
+async fn list_tokens(State(auth): State<Arc<Auth>>, user: AuthUser) -> ApiResult {
+    debug!("list_tokens: user={}", user.name);
+    let file = auth.file.lock().map_err(|_| fail(StatusCode::INTERNAL_SERVER_ERROR, "lock poisoned"))?;
+    let tokens: Vec<_> = file.tokens.iter()
+        .filter(|t| user.role == Role::Admin || t.user == user.name)
+        .map(token_view)
+        .collect();
+    Ok(Json(json!({"tokens": tokens})))
+}
+
+#[derive(Deserialize)]
+struct CreateTokenReq {
+    name: String,
+    // По умолчанию — роль создателя; выше неё нельзя
+    role: Option<Role>,
+    ttl_days: Option<u64>,
+}
+
+async fn create_token(State(auth): State<Arc<Auth>>, client: ClientAddr, user: AuthUser, Json(body): Json<CreateTokenReq>) -> ApiResult {
+    info!("create_token: user={} name={} role={:?}", user.name, body.name, body.role);
+    if body.name.trim().is_empty() || body.name.len() > 64 {
+        return Err(fail(StatusCode::BAD_REQUEST, "token name: 1-64 characters"));
+    }
+    let role = body.role.unwrap_or(user.role);
+    if role > user.role {
+        return Err(fail(StatusCode::FORBIDDEN, format!("role {} cannot create a {} token", user.role, role)));
+    }
+    let mut id = [0u8; 6];
+    rand::thread_rng().fill_bytes(&mut id);
+    let id: String = id.iter().map(|b| format!("{:02x}", b)).collect();
+    let secret = random_secret();
+    let token = StoredToken {
+        id: id.clone(),
+        name: body.name.trim().to_string(),
+        user: user.name.clone(),
+        role,
+        hash: token_hash(&secret),
+        created_at: Utc::now(),
+        expires_at: body.ttl_days.filter(|d| *d > 0).map(|d| Utc::now() + chrono::Duration::days(d as i64)),
+    };
+    let mut view = token_view(&token);
+    {
+        let mut file = auth.file.lock().map_err(|_| fail(StatusCode::INTERNAL_SERVER_ERROR, "lock poisoned"))?;
+        file.tokens.push(token);
+        if let Err(e) = auth.write(&file) {
+            file.tokens.retain(|t| t.id != id);
+            return Err(e);
+        }
+    }
+    auth.audit("token_create", &client, view.clone(), Ok(()));
+    // Показывается один раз, на сервере остаётся только хеш
+    view["token"] = json!(format!("{}{}_{}", TOKEN_PREFIX, id, secret));
+    Ok(Json(view))
+}
+
+#[derive(Deserialize)]
+struct RevokeTokenReq { id: String }
+
+async fn revoke_token(State(auth): State<Arc<Auth>>, client: ClientAddr, user: AuthUser, Json(body): Json<RevokeTokenReq>) -> ApiResult {
+    info!("revoke_token: user={} id={}", user.name, body.id);
+    let result = (|| {
+        let mut file = auth.file.lock().map_err(|_| fail(StatusCode::INTERNAL_SERVER_ERROR, "lock poisoned"))?;
+        let visible = |t: &StoredToken| t.id == body.id && (user.role == Role::Admin || t.user == user.name);
+        if !file.tokens.iter().any(visible) {
+            return Err(fail(StatusCode::NOT_FOUND, format!("token not found: {}", body.id)));
+        }
+        file.tokens.retain(|t| !visible(t));
+        auth.write(&file)
+    })();
+    auth.audit("token_revoke", &client, json!({"id": body.id}), outcome(&result));
+    result?;
+    Ok(Json(json!({"ok": true})))
+}
@@ -1,45 +1,120 @@
-use axum::{routing::{get, post}, Router, response::IntoResponse, Json, extract::State};
+use axum::{body::Body, routing::{get, post}, Router, response::{IntoResponse, Response}, Json, extract::{Query, State, WebSocketUpgrade}, http::{HeaderMap, StatusCode}};
+use chrono::Utc;
 use serde_json::json;
-use serde::{Deserialize, Serialize};
-use std::sync::{Arc, Mutex};
-use std::fs;
-use std::path::Path;
+use serde::Deserialize;
+use std::sync::Arc;
+use std::time::Duration;
 use reqwest::Client;
 use tracing::{info, error, debug};
 
-const GO_SERVER_BASE: &str = "http://127.0.0.1:3002/go";
+use crate::config::{CommandPolicy, Config, FilesConfig, LogsConfig, RelayKind};
+use super::relay::{self, ListRelaysQuery, Relay, RelayError, RelayOnly, RelayRegistry, RelayState, RelayTarget};
+use super::relay_models::*;
+use super::history::{self, HistoryQuery, MetricStore};
+use super::metrics;
+use super::session_store::{SessionData, SessionStore};
+use super::servers::{Credentials, ServerStore, VaultError, MAX_JUMPS};
+use super::ssh_auth::{self, AuthError, AuthReq};
+use super::ssh_sessions::{self, SshSessionInfo};
+use super::terminal::{self, TerminalQuery};
+use super::auth::AuthUser;
+use super::exec_stream::{self, Executions};
+use super::policy::{self, Effective};
+use super::audit::{AuditEntry, AuditLog, AuditOutcome, AuditQuery, ClientAddr};
+use super::runbooks::{self, RunRequest, Runbook, RunbookError, RunbookStore, Runner, Runs};
+use super::scheduler::{Job, Scheduler};
+use super::alerts::{Alerts, EventsQuery, TestReq};
+use super::live::{self, LiveFeeds};
+use super::export::{self, ExportQuery};
+use super::inventory::{self, Facts, Inventory, InventoryQuery, InventoryUpdate};
+use super::files::{self, FilesQuery};
+use super::logtail::{self, LogTails, TailQuery};
+
+type RelayResult<T> = Result<Json<T>, RelayError>;
 
 #[derive(Clone)]
 struct AppState { 
-    http: Client,
-    session_data: Arc<Mutex<SessionData>>
+    relays: Arc<RelayRegistry>,
+    history: Arc<MetricStore>,
+    sessions: Arc<SessionStore>,
+    servers: Arc<ServerStore>,
+    executions: Arc<Executions>,
+    policy: CommandPolicy,
+    audit: Arc<AuditLog>,
+    runbooks: Arc<RunbookStore>,
+    runs: Arc<Runs>,
+    runner: Arc<Runner>,
+    scheduler: Arc<Scheduler>,
+    alerts: Arc<Alerts>,
+    live: Arc<LiveFeeds>,
+    files: FilesConfig,
+    logs: LogsConfig,
+    tails: Arc<LogTails>,
 }
 
-#[derive(Default, Serialize, Deserialize)]
-struct SessionData {
-    network_history: Vec<NetworkPoint>,
-    cpu_history: Vec<f64>,
-    gpu_history: Vec<f64>,
-    current_host: String,
+impl RelayState for Arc<AppState> {
+    fn relays(&self) -> &RelayRegistry { &self.relays }
 }
 
-#[derive(Serialize, Deserialize)]
-struct NetworkPoint {
-    rx: f64,
-    tx: f64,
-    timestamp: u64,
+impl AppState {
+    fn policy_for(&self, target: &RelayTarget) -> Effective {
+        policy::for_target(&self.relays, &self.servers, &self.policy, target)
+    }
 }
 
-pub fn create_routes() -> Router {
+pub fn create_routes(config: &Config, audit: Arc<AuditLog>) -> Router {
+    let http = Client::builder()
+        .connect_timeout(Duration::from_millis(config.relay_client.connect_timeout_ms))
+        .build()
+        .unwrap_or_default();
+    let relays = Arc::new(RelayRegistry::from_config(config, http.clone()));
+    relays.spawn_health_checks(Duration::from_secs(config.relay_health_interval_secs));
+    ssh_sessions::spawn_reaper(relays.clone(), &config.ssh_sessions);
+    let history = Arc::new(MetricStore::new(config.poller.capacity));
+    history::spawn_poller(relays.clone(), history.clone(), &config.poller);
+    // Метрики берутся у поллера: без него работают только правила "service"
+    let poll_secs = if config.poller.enabled { config.poller.interval_secs } else { 0 };
+    let alerts = Arc::new(Alerts::open(config.data_path("alerts.jsonl"), config.alerts.clone(), poll_secs, relays.clone(), history.clone(), http));
+    alerts.spawn();
+    ssh_auth::set_key_dir(config.ssh_key_dir.clone());
+    let servers = Arc::new(ServerStore::open(config.servers_path.clone(), std::env::var("RUST_SERVER_MASTER_KEY").ok()));
+    let runner = Arc::new(Runner {
+        relays: relays.clone(),
+        servers: servers.clone(),
+        policy: config.policy.clone(),
+        audit: audit.clone(),
+        config: config.runbooks.clone(),
+    });
+    let runbooks = Arc::new(RunbookStore::open(config.data_path("runbooks.json")));
+    let runs = Arc::new(Runs::new(config.runbooks.keep_runs));
+    let scheduler = Arc::new(Scheduler::open(config.data_path("jobs"), config.scheduler.clone(), runner.clone(), runs.clone(), runbooks.clone()));
+    scheduler.spawn();
     let state = AppState { 
-        http: Client::new(),
-        session_data: Arc::new(Mutex::new(SessionData::default()))
+        relays,
+        history,
+        sessions: Arc::new(SessionStore::open(config.data_path("sessions"), config.sessions.clone())),
+        servers,
+        executions: Arc::new(Executions::default()),
+        policy: config.policy.clone(),
+        audit,
+        runbooks,
+        runs,
+        runner,
+        scheduler,
+        alerts,
+        live: Arc::new(LiveFeeds::default()),
+        files: config.files.clone(),
+        logs: config.logs.clone(),
+        tails: Arc::new(LogTails::default()),
     };
-    info!("Initializing dashboard routes");
+    info!("Initializing dashboard routes ({} relays)", config.relays.len());
     Router::new()
         .route("/rust/ssh/connect", post(ssh_connect))
         .route("/rust/ssh/disconnect", post(ssh_disconnect))
         .route("/rust/ssh/execute", post(ssh_execute))
+        .route("/rust/ssh/execute/stream", post(ssh_execute_stream))
+        .route("/rust/ssh/execute/cancel", post(ssh_execute_cancel))
+        .route("/rust/ssh/execute/running", get(ssh_execute_running))
         .route("/rust/data/processes", get(get_processes))
         .route("/rust/data/ports", get(get_ports))
         .route("/rust/data/connections", get(get_connections))
@@ -50,34 +125,159 @@ pub fn create_routes() -> Router {
         .route("/rust/session/save", post(save_session))
         .route("/rust/session/restore", get(restore_session))
         .route("/rust/ssh/status", get(get_ssh_status))
+        .route("/rust/ssh/sessions", get(list_ssh_sessions))
+        .route("/rust/ssh/terminal", get(ssh_terminal))
+        .route("/rust/files/list", get(files_list))
+        .route("/rust/files/read", get(files_read))
+        .route("/rust/files/download", get(files_download))
+        .route("/rust/files/upload", post(files_upload))
+        .route("/rust/logs/tail", get(logs_tail))
+        .route("/rust/logs/tails", get(logs_tails))
+        .route("/rust/dashboard/ws", get(dashboard_ws))
+        .route("/rust/dashboard/feeds", get(dashboard_feeds))
+        .route("/rust/ssh/policy", get(get_ssh_policy))
         .route("/rust/process/action", post(process_action))
         .route("/rust/servers/save", post(save_server))
         .route("/rust/servers/list", get(list_servers))
         .route("/rust/servers/delete", post(delete_server))
+        .route("/rust/servers/policy", post(set_server_policy))
+        .route("/rust/servers/unlock", post(unlock_servers))
+        .route("/rust/servers/lock", post(lock_servers))
+        .route("/rust/inventory", get(list_inventory))
+        .route("/rust/inventory/update", post(update_inventory))
+        .route("/rust/inventory/export", get(export_inventory))
+        .route("/rust/inventory/import", post(import_inventory))
         .route("/rust/data/services", get(get_services))
         .route("/rust/data/disk-usage", get(get_disk_usage))
         .route("/rust/data/users", get(get_users))
         .route("/rust/data/system-info", get(get_system_info))
+        .route("/rust/relays", get(list_relays))
+        .route("/rust/relay/health", get(relay_health))
+        .route("/rust/metrics/history", get(metrics_history))
+        .route("/metrics", get(prometheus_metrics))
+        .route("/rust/metrics/export", get(metrics_export))
+        .route("/rust/audit", get(audit_log))
+        .route("/rust/runbooks/list", get(list_runbooks))
+        .route("/rust/runbooks/save", post(save_runbook))
+        .route("/rust/runbooks/delete", post(delete_runbook))
+        .route("/rust/runbooks/run", post(run_runbook))
+        .route("/rust/runbooks/runs", get(list_runbook_runs))
+        .route("/rust/runbooks/report", get(runbook_report))
+        .route("/rust/runbooks/cancel", post(cancel_runbook_run))
+        .route("/rust/jobs/list", get(list_jobs))
+        .route("/rust/jobs/save", post(save_job))
+        .route("/rust/jobs/delete", post(delete_job))
+        .route("/rust/jobs/run", post(run_job))
+        .route("/rust/jobs/history", get(job_history))
+        .route("/rust/alerts", get(list_alerts))
+        .route("/rust/alerts/events", get(alert_events))
+        .route("/rust/alerts/test", post(test_alert_webhooks))
         .with_state(Arc::new(state))
 }
 
+async fn list_relays(State(state): State<Arc<AppState>>, Query(q): Query<ListRelaysQuery>) -> impl IntoResponse {
+    debug!("list_relays");
+    relay::list_relays(&state.relays, q.check.unwrap_or(false)).await
+}
+
+async fn relay_health(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    debug!("relay_health");
+    Json(state.relays.breakers())
+}
+
+async fn audit_log(State(state): State<Arc<AppState>>, Query(q): Query<AuditQuery>) -> impl IntoResponse {
+    debug!("audit_log: host={:?} action={:?} actor={:?} since={:?} until={:?}", q.host, q.action, q.actor, q.since, q.until);
+    // Чтение ротированных файлов — синхронный I/O
+    let audit = state.audit.clone();
+    match tokio::task::spawn_blocking(move || audit.query(&q)).await {
+        Ok(result) => result.into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, Json(json!({"error": e.to_string()}))).into_response(),
+    }
+}
+
+async fn metrics_history(State(state): State<Arc<AppState>>, Query(q): Query<HistoryQuery>) -> impl IntoResponse {
+    debug!("metrics_history: metric={:?} host={:?} since={:?}", q.metric, q.host, q.since);
+    history::history(&state.history, &q)
+}
+
+async fn metrics_export(State(state): State<Arc<AppState>>, headers: HeaderMap, Query(q): Query<ExportQuery>) -> Response {
+    debug!("metrics_export: metric={:?} host={:?} since={:?} until={:?} step={:?} agg={:?} format={:?} source={:?}", q.metric, q.host, q.since, q.until, q.step, q.agg, q.format, q.source);
+    let series = match q.source.as_deref().unwrap_or("poller") {
+        "poller" => export::store_series(&state.history),
+        // Ключ сессии — как у /rust/session/restore
+        "session" => {
+            let key = session_key(&headers, &SessionKeyQuery { session: q.session.clone(), host: q.host.clone() });
+            match state.sessions.load(&key) {
+                Ok(Some(restored)) => export::session_series(&restored.data),
+                Ok(None) => return (StatusCode::NOT_FOUND, Json(json!({"error": format!("no saved session {}", key)}))).into_response(),
+                Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, Json(json!({"error": e}))).into_response(),
+            }
+        }
+        other => return (StatusCode::BAD_REQUEST, Json(json!({"error": format!("unknown source {:?}, expected poller or session", other)}))).into_response(),
+    };
+    export::export(series, &q)
+}
+
+async fn prometheus_metrics(State(state): State<Arc<AppState>>) -> Response {
+    debug!("prometheus_metrics");
+    metrics::response(&state.history)
+}
+
+// Либо явные host/user и способ входа, либо имя сохранённого сервера: тогда секреты расшифровываются здесь и не попадают в браузер
+#[derive(Deserialize)]
+struct SSHConnectReq {
+    server: Option<String>,
+    host: Option<String>,
+    user: Option<String>,
+    port: Option<u16>,
+    #[serde(flatten)]
+    auth: AuthReq,
+    // Без поля у сохранённого сервера — его цепочка из инвентаря; [] — напрямую
+    jumps: Option<Vec<JumpReq>>,
+}
+
+// Jump host: сохранённый сервер (со своей цепочкой) или явные host/user и способ входа
 #[derive(Deserialize)]
-struct SSHConnectReq { host: String, user: String, port: Option<u16>, password: Option<String> }
+struct JumpReq {
+    server: Option<String>,
+    host: Option<String>,
+    user: Option<String>,
+    port: Option<u16>,
+    #[serde(flatten)]
+    auth: AuthReq,
+}
+
+fn resolve_jumps(servers: &ServerStore, jumps: Vec<JumpReq>) -> Result<Vec<Credentials>, VaultError> {
+    let mut chain = vec![];
+    for (i, jump) in jumps.into_iter().enumerate() {
+        let bad = |msg: String| VaultError::Invalid(format!("jumps[{}]: {}", i, msg));
+        match (jump.server, jump.host, jump.user) {
+            (Some(name), _, _) => {
+                let mut creds = servers.credentials(&name)?;
+                chain.append(&mut creds.jumps);
+                chain.push(creds);
+            }
+            (None, Some(host), Some(user)) => {
+                let auth = jump.auth.validate().map_err(|e| bad(e.message()))?;
+                chain.push(Credentials { host, user, port: jump.port.unwrap_or(22), auth: Some(auth), server: None, jumps: vec![] });
+            }
+            _ => return Err(bad("either server or host and user are required".into())),
+        }
+    }
+    if chain.len() > MAX_JUMPS {
+        return Err(VaultError::Invalid(format!("more than {} jump hosts", MAX_JUMPS)));
+    }
+    Ok(chain)
+}
 
 #[derive(Deserialize)]
 struct SSHExecuteReq { command: String }
 
 #[derive(Deserialize)]
-struct ProcessActionReq { pid: String, action: String }
+struct ExecCancelReq { id: String }
 
-#[derive(Serialize, Deserialize, Clone)]
-struct SavedServer {
-    name: String,
-    host: String,
-    user: String,
-    port: u16,
-    password: String,
-}
+#[derive(Deserialize)]
+struct ProcessActionReq { pid: String, action: String }
 
 #[derive(Deserialize)]
 struct SaveServerReq {
@@ -85,166 +285,342 @@ struct SaveServerReq {
     host: String,
     user: String,
     port: u16,
-    password: String,
-}
-
-async fn ssh_connect(State(state): State<Arc<AppState>>, Json(body): Json<SSHConnectReq>) -> impl IntoResponse {
-    info!("ssh_connect: host={} user={} port={}", body.host, body.user, body.port.unwrap_or(22));
-    let go_url = format!("{}/ssh/connect", GO_SERVER_BASE);
-    let resp = state.http.post(&go_url).json(&json!({
-        "host": body.host,
-        "user": body.user,
-        "port": body.port.unwrap_or(22),
-        "password": body.password
-    })).send().await;
-    match resp {
-        Ok(r) => {
-            let status = r.status();
-            let text = r.text().await.unwrap_or_else(|_| "".into());
-            info!("ssh_connect result: status={} bytes={}", status.as_u16(), text.len());
-            (status, text)
+    #[serde(flatten)]
+    auth: AuthReq,
+    // Без поля — политика прежней записи сохраняется
+    policy: Option<CommandPolicy>,
+}
+
+// Неудачный connect тоже попадает в аудит
+fn connect_failed(state: &AppState, entry: AuditEntry, message: String, response: Response) -> Response {
+    state.audit.record(entry.failed(AuditOutcome::Error, &message));
+    response
+}
+
+async fn ssh_connect(State(state): State<Arc<AppState>>, client: ClientAddr, RelayOnly(relay): RelayOnly, Json(body): Json<SSHConnectReq>) -> Response {
+    let mut entry = AuditEntry::new("ssh_connect", &client);
+    entry.relay = Some(relay.backend.name.clone());
+    entry.server = body.server.clone();
+    entry.host = body.host.clone();
+    entry.user = body.user.clone();
+    let mut target = match &body.server {
+        Some(name) => match state.servers.credentials(name) {
+            Ok(c) => Credentials { port: body.port.unwrap_or(c.port), ..c },
+            Err(e) => return connect_failed(&state, entry, e.message(), e.into_response()),
+        },
+        None => match (body.host, body.user) {
+            (Some(host), Some(user)) => match body.auth.validate() {
+                Ok(auth) => Credentials { host, user, port: body.port.unwrap_or(22), auth: Some(auth), server: None, jumps: vec![] },
+                Err(e) => return connect_failed(&state, entry, e.message(), e.into_response()),
+            },
+            _ => return (StatusCode::BAD_REQUEST, Json(json!({"error": "either server or host and user are required"}))).into_response(),
+        },
+    };
+    if let Some(jumps) = body.jumps {
+        match resolve_jumps(&state.servers, jumps) {
+            Ok(chain) => target.jumps = chain,
+            Err(e) => return connect_failed(&state, entry, e.message(), e.into_response()),
         }
-        Err(e) => {
-            error!("ssh_connect error: {}", e);
-            (axum::http::StatusCode::BAD_GATEWAY, format!("connection error: {}", e))
+    }
+    entry.host = Some(target.host.clone());
+    entry.user = Some(target.user.clone());
+    let jumps: Vec<String> = target.jumps.iter().map(Credentials::label).collect();
+    if !jumps.is_empty() {
+        entry.details = Some(json!({"jumps": jumps}));
+    }
+    let Some(auth) = target.auth.clone() else {
+        let e = AuthError::one("password", "saved server has no credentials");
+        return connect_failed(&state, entry, e.message(), e.into_response());
+    };
+    info!("ssh_connect: host={} user={} port={} auth={:?} saved={:?} jumps={:?}", target.host, target.user, target.port, auth.kind(), body.server, jumps);
+    let payload = match target.relay_payload() {
+        Ok(payload) => payload,
+        Err(e) => return connect_failed(&state, entry, e.clone(), (StatusCode::BAD_REQUEST, Json(json!({"ok": false, "error": e}))).into_response()),
+    };
+    // Каждый connect — новая сессия, если не переподключаем уже существующую по X-SSH-Session
+    let id = match (relay.explicit, relay.session) {
+        (true, Some(id)) => id,
+        _ => ssh_sessions::new_id(),
+    };
+    let relay = RelayTarget { session: Some(id.clone()), explicit: true, ..relay };
+    entry.session = Some(id.clone());
+    // Go relay держит одно подключение: пока его занимает другая сессия, новый connect отклоняется
+    let _claim = match relay.backend.kind {
+        RelayKind::Http => match state.relays.sessions.claim(&relay.backend.name, &id) {
+            Ok(claim) => Some(claim),
+            Err(other) => {
+                let message = format!("relay {} holds a single SSH connection, already used by session {}; disconnect it or use a native relay", relay.backend.name, other);
+                return connect_failed(&state, entry, message.clone(), (StatusCode::CONFLICT, Json(json!({"ok": false, "error": message, "session_id": other}))).into_response());
+            }
+        },
+        RelayKind::Native => None,
+    };
+    let result: Result<SshConnectResult, RelayError> = state.relays.post_json(&relay, "/ssh/connect", Some(&payload)).await;
+    let result = match result {
+        Ok(result) => result,
+        Err(e) => return connect_failed(&state, entry, e.message(), e.into_response()),
+    };
+    state.audit.record(match result.connected {
+        true => entry.ok(0),
+        false => entry.failed(AuditOutcome::Error, &result.message),
+    });
+    info!("ssh_connect result: session={} connected={} message=\"{}\"", id, result.connected, result.message);
+    let mut response = json!(result);
+    // Relay знает только индекс hop'а, имя сохранённого сервера — здесь
+    if let Some(hop) = result.failed_hop.and_then(|i| target.hop(i)) {
+        response["failed_hop"] = hop;
+    }
+    if result.connected {
+        let now = Utc::now();
+        let info = SshSessionInfo {
+            id: id.clone(),
+            relay: relay.backend.name.clone(),
+            host: target.host,
+            user: target.user,
+            port: target.port,
+            auth: auth.kind(),
+            server: body.server.clone(),
+            jumps,
+            created_at: now,
+            last_used: now,
+        };
+        state.relays.sessions.insert(info);
+        response["session_id"] = json!(id);
+        if let Some(name) = body.server {
+            tokio::spawn(collect_facts(state.clone(), relay, name));
         }
     }
+    Json(response).into_response()
 }
 
-async fn ssh_disconnect(State(state): State<Arc<AppState>>) -> impl IntoResponse {
-    info!("ssh_disconnect");
-    let go_url = format!("{}/ssh/disconnect", GO_SERVER_BASE);
-    let resp = state.http.post(&go_url).send().await;
-    match resp {
-        Ok(r) => {
-            let status = r.status();
-            let text = r.text().await.unwrap_or_else(|_| "".into());
-            info!("ssh_disconnect result: status={} bytes={}", status.as_u16(), text.len());
-            (status, text)
-        }
-        Err(e) => {
-            error!("ssh_disconnect error: {}", e);
-            (axum::http::StatusCode::BAD_GATEWAY, format!("connection error: {}", e))
+async fn ssh_disconnect(State(state): State<Arc<AppState>>, Relay(relay): Relay) -> RelayResult<SshDisconnectResult> {
+    info!("ssh_disconnect: relay={} session={:?}", relay.backend.name, relay.session);
+    let result = state.relays.post_json(&relay, "/ssh/disconnect", None::<&()>).await?;
+    if let Some(id) = &relay.session {
+        state.relays.sessions.remove(id);
+    }
+    Ok(Json(result))
+}
+
+async fn ssh_terminal(State(state): State<Arc<AppState>>, Relay(relay): Relay, Query(q): Query<TerminalQuery>, ws: WebSocketUpgrade) -> Response {
+    info!("ssh_terminal: relay={} session={:?} size={:?}x{:?}", relay.backend.name, relay.session, q.cols, q.rows);
+    if let Err(denied) = state.policy_for(&relay).check_terminal() {
+        return denied.into_response();
+    }
+    terminal::open(state.relays.clone(), relay, q, ws).await
+}
+
+async fn files_list(State(state): State<Arc<AppState>>, Relay(relay): Relay, Query(q): Query<FilesQuery>) -> Response {
+    debug!("files_list: session={:?} path={:?}", relay.session, q.path);
+    let policy = state.policy_for(&relay);
+    files::list(relay, policy, &state.files, q).await
+}
+
+async fn files_read(State(state): State<Arc<AppState>>, Relay(relay): Relay, Query(q): Query<FilesQuery>) -> Response {
+    debug!("files_read: session={:?} path={:?} offset={:?} length={:?}", relay.session, q.path, q.offset, q.length);
+    let policy = state.policy_for(&relay);
+    files::read(relay, policy, &state.files, q).await
+}
+
+async fn files_download(State(state): State<Arc<AppState>>, Relay(relay): Relay, Query(q): Query<FilesQuery>) -> Response {
+    debug!("files_download: session={:?} path={:?}", relay.session, q.path);
+    let policy = state.policy_for(&relay);
+    files::download(relay, policy, q).await
+}
+
+// Тело — содержимое файла как есть, путь и overwrite в query
+async fn files_upload(State(state): State<Arc<AppState>>, client: ClientAddr, Relay(relay): Relay, Query(q): Query<FilesQuery>, body: Body) -> Response {
+    info!("files_upload: session={:?} path={:?} overwrite={}", relay.session, q.path, q.overwrite);
+    let entry = AuditEntry::new("file_upload", &client).on(&state.relays, &relay);
+    let policy = state.policy_for(&relay);
+    files::upload(relay, policy, &state.files, q, body, (state.audit.clone(), entry)).await
+}
+
+// Last-Event-ID присылает EventSource при переподключении
+async fn logs_tail(State(state): State<Arc<AppState>>, client: ClientAddr, Relay(relay): Relay, headers: HeaderMap, Query(q): Query<TailQuery>) -> Response {
+    info!("logs_tail: session={:?} file={:?} unit={:?}", relay.session, q.file, q.unit);
+    let requested = match q.target() {
+        Ok(t) => t,
+        Err(e) => return RelayError::InvalidQuery(e).into_response(),
+    };
+    let entry = AuditEntry::new("log_tail", &client).on(&state.relays, &relay).command(&requested.source(&state.logs).command);
+    let policy = state.policy_for(&relay);
+    let source = match requested.authorize(&state.relays, &relay, &policy).await {
+        Ok(t) => t.source(&state.logs),
+        Err(refusal) => {
+            info!("logs_tail: refused: {}", refusal.message());
+            state.audit.record(entry.failed(refusal.outcome(), &refusal.message()));
+            return refusal.into_response();
         }
+    };
+    let entry = entry.command(&source.command);
+    if let Err(denied) = policy.check_command(&source.command) {
+        info!("logs_tail: denied by policy ({}): {}", denied.rule, source.command);
+        state.audit.record(entry.failed(AuditOutcome::Denied, &denied.error));
+        return denied.into_response();
     }
+    let last_event_id = headers.get("last-event-id").and_then(|v| v.to_str().ok()).map(str::to_string);
+    let result = logtail::open(state.tails.clone(), state.relays.clone(), relay, state.logs.clone(), q, source, last_event_id).await;
+    state.audit.record(match &result {
+        Ok(_) => entry.ok(0),
+        Err(e) => entry.failed(AuditOutcome::Error, &e.message()),
+    });
+    result.into_response()
 }
 
-async fn ssh_execute(State(state): State<Arc<AppState>>, Json(body): Json<SSHExecuteReq>) -> impl IntoResponse {
+async fn logs_tails(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    debug!("logs_tails");
+    Json(state.tails.describe())
+}
+
+async fn dashboard_ws(State(state): State<Arc<AppState>>, Relay(relay): Relay, ws: WebSocketUpgrade) -> Response {
+    info!("dashboard_ws: relay={} session={:?}", relay.backend.name, relay.session);
+    live::open(state.live.clone(), state.relays.clone(), relay, ws)
+}
+
+async fn dashboard_feeds(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    debug!("dashboard_feeds");
+    Json(state.live.describe())
+}
+
+// Политика, действующая для выбранной сессии
+async fn get_ssh_policy(State(state): State<Arc<AppState>>, Relay(relay): Relay) -> impl IntoResponse {
+    debug!("get_ssh_policy: session={:?}", relay.session);
+    let effective = state.policy_for(&relay);
+    Json(json!({"server": effective.server, "session": relay.session, "policy": effective.policy, "error": effective.unavailable}))
+}
+
+async fn list_ssh_sessions(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    debug!("list_ssh_sessions");
+    Json(state.relays.sessions.describe())
+}
+
+async fn ssh_execute(State(state): State<Arc<AppState>>, client: ClientAddr, Relay(relay): Relay, Json(body): Json<SSHExecuteReq>) -> Response {
     let preview = body.command.chars().take(60).collect::<String>();
     debug!("ssh_execute: cmd_preview=\"{}\"", preview);
-    let go_url = format!("{}/ssh/execute", GO_SERVER_BASE);
-    let resp = state.http.post(&go_url).json(&json!({
+    let entry = AuditEntry::new("ssh_execute", &client).on(&state.relays, &relay).command(&body.command);
+    if let Err(denied) = state.policy_for(&relay).check_command(&body.command) {
+        info!("ssh_execute: denied by policy ({}): {}", denied.rule, preview);
+        state.audit.record(entry.failed(AuditOutcome::Denied, &denied.error));
+        return denied.into_response();
+    }
+    let result = state.relays.post_text(&relay, "/ssh/execute", &json!({
         "command": body.command
-    })).send().await;
-    match resp {
-        Ok(r) => {
-            let status = r.status();
-            let text = r.text().await.unwrap_or_else(|_| "".into());
-            info!("ssh_execute result: status={} bytes={}", status.as_u16(), text.len());
-            (status, text)
-        }
-        Err(e) => {
-            error!("ssh_execute error: {}", e);
-            (axum::http::StatusCode::BAD_GATEWAY, format!("connection error: {}", e))
-        }
+    })).await;
+    state.audit.record(match &result {
+        Ok(output) => entry.ok(output.len()).exit(Some(0)),
+        Err(e) => entry.failed(AuditOutcome::Error, &e.message()),
+    });
+    result.into_response()
+}
+
+async fn ssh_execute_stream(State(state): State<Arc<AppState>>, client: ClientAddr, Relay(relay): Relay, Json(body): Json<SSHExecuteReq>) -> Response {
+    let preview = body.command.chars().take(60).collect::<String>();
+    debug!("ssh_execute_stream: cmd_preview=\"{}\"", preview);
+    let entry = AuditEntry::new("ssh_execute_stream", &client).on(&state.relays, &relay).command(&body.command);
+    if let Err(denied) = state.policy_for(&relay).check_command(&body.command) {
+        info!("ssh_execute_stream: denied by policy ({}): {}", denied.rule, preview);
+        state.audit.record(entry.failed(AuditOutcome::Denied, &denied.error));
+        return denied.into_response();
     }
+    exec_stream::start(state.executions.clone(), state.relays.clone(), relay, body.command, (state.audit.clone(), entry)).await
 }
 
-async fn get_connections(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+async fn ssh_execute_cancel(State(state): State<Arc<AppState>>, user: Option<AuthUser>, Json(body): Json<ExecCancelReq>) -> Response {
+    debug!("ssh_execute_cancel: id={}", body.id);
+    exec_stream::cancel(&state.executions, &body.id, user.as_ref())
+}
+
+async fn ssh_execute_running(State(state): State<Arc<AppState>>, user: Option<AuthUser>) -> impl IntoResponse {
+    debug!("ssh_execute_running");
+    Json(state.executions.describe(user.as_ref()))
+}
+
+async fn get_connections(State(state): State<Arc<AppState>>, Relay(relay): Relay, Query(q): Query<ListQuery>) -> RelayResult<Vec<ConnectionRow>> {
     debug!("get_connections");
-    let go_url = format!("{}/data/connections", GO_SERVER_BASE);
-    let resp = state.http.get(&go_url).send().await;
-    match resp { 
-        Ok(r) => { 
-            let s=r.status(); 
-            let t=r.text().await.unwrap_or_default(); 
-            info!("get_connections: status={} bytes={}", s.as_u16(), t.len());
-            (s,t) 
-        }, 
-        Err(e)=> {
-            error!("get_connections error: {}", e);
-            (axum::http::StatusCode::BAD_GATEWAY, format!("connection error: {}", e)) 
-        }
-    }
+    let rows: Vec<ConnectionRow> = state.relays.get_json(&relay, "/data/connections").await?;
+    // Go отдаёт здесь голый массив, поэтому total не добавляем
+    Ok(Json(q.apply(rows)?.rows))
 }
 
-async fn get_client_ips(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+async fn get_client_ips(State(state): State<Arc<AppState>>, Relay(relay): Relay, Query(q): Query<ListQuery>) -> RelayResult<Vec<String>> {
     debug!("get_client_ips");
-    let go_url = format!("{}/data/client-ips", GO_SERVER_BASE);
-    let resp = state.http.get(&go_url).send().await;
-    match resp { 
-        Ok(r) => { let s=r.status(); let t=r.text().await.unwrap_or_default(); info!("get_client_ips: status={} bytes={}", s.as_u16(), t.len()); (s,t) }, 
-        Err(e)=> { error!("get_client_ips error: {}", e); (axum::http::StatusCode::BAD_GATEWAY, format!("connection error: {}", e)) } }
+    let ips: Vec<String> = state.relays.get_json(&relay, "/data/client-ips").await?;
+    Ok(Json(q.apply(ips)?.rows))
 }
 
-async fn get_process_logs(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+async fn get_process_logs(State(state): State<Arc<AppState>>, Relay(relay): Relay) -> RelayResult<ProcessLogs> {
     debug!("get_process_logs");
-    let go_url = format!("{}/data/process-logs", GO_SERVER_BASE);
-    let resp = state.http.get(&go_url).send().await;
-    match resp { 
-        Ok(r) => { let s=r.status(); let t=r.text().await.unwrap_or_default(); info!("get_process_logs: status={} bytes={}", s.as_u16(), t.len()); (s,t) }, 
-        Err(e)=> { error!("get_process_logs error: {}", e); (axum::http::StatusCode::BAD_GATEWAY, format!("connection error: {}", e)) } }
+    Ok(Json(state.relays.get_json(&relay, "/data/process-logs").await?))
 }
 
-async fn get_resources(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+async fn get_resources(State(state): State<Arc<AppState>>, Relay(relay): Relay) -> RelayResult<Resources> {
     debug!("get_resources");
-    let go_url = format!("{}/data/resources", GO_SERVER_BASE);
-    let resp = state.http.get(&go_url).send().await;
-    match resp { 
-        Ok(r) => { let s=r.status(); let t=r.text().await.unwrap_or_default(); info!("get_resources: status={} bytes={}", s.as_u16(), t.len()); (s,t) }, 
-        Err(e)=> { error!("get_resources error: {}", e); (axum::http::StatusCode::BAD_GATEWAY, format!("connection error: {}", e)) } }
+    Ok(Json(state.relays.get_json(&relay, "/data/resources").await?))
 }
 
-async fn get_network(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+async fn get_network(State(state): State<Arc<AppState>>, Relay(relay): Relay) -> RelayResult<Vec<NetworkSample>> {
     debug!("get_network");
-    let go_url = format!("{}/data/network", GO_SERVER_BASE);
-    let resp = state.http.get(&go_url).send().await;
-    match resp { 
-        Ok(r) => { let s=r.status(); let t=r.text().await.unwrap_or_default(); info!("get_network: status={} bytes={}", s.as_u16(), t.len()); (s,t) }, 
-        Err(e)=> { error!("get_network error: {}", e); (axum::http::StatusCode::BAD_GATEWAY, format!("connection error: {}", e)) } }
+    Ok(Json(state.relays.get_json(&relay, "/data/network").await?))
 }
 
-async fn get_processes(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+async fn get_processes(State(state): State<Arc<AppState>>, Relay(relay): Relay, Query(q): Query<ListQuery>) -> RelayResult<serde_json::Value> {
     debug!("get_processes");
-    let go_url = format!("{}/data/processes", GO_SERVER_BASE);
-    let resp = state.http.get(&go_url).send().await;
-    match resp { 
-        Ok(r) => { let s=r.status(); let t=r.text().await.unwrap_or_default(); info!("get_processes: status={} bytes={}", s.as_u16(), t.len()); (s,t) }, 
-        Err(e)=> { error!("get_processes error: {}", e); (axum::http::StatusCode::BAD_GATEWAY, format!("connection error: {}", e)) } }
+    let list: ProcessList = state.relays.get_json(&relay, "/data/processes").await?;
+    let page = q.apply(list.rows)?;
+    Ok(Json(json!({"rows": page.rows, "total": page.total})))
 }
 
-async fn get_ports(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+async fn get_ports(State(state): State<Arc<AppState>>, Relay(relay): Relay, Query(q): Query<ListQuery>) -> RelayResult<serde_json::Value> {
     debug!("get_ports");
-    let go_url = format!("{}/data/ports", GO_SERVER_BASE);
-    let resp = state.http.get(&go_url).send().await;
-    match resp { 
-        Ok(r) => { let s=r.status(); let t=r.text().await.unwrap_or_default(); info!("get_ports: status={} bytes={}", s.as_u16(), t.len()); (s,t) }, 
-        Err(e)=> { error!("get_ports error: {}", e); (axum::http::StatusCode::BAD_GATEWAY, format!("connection error: {}", e)) } }
+    let list: PortList = state.relays.get_json(&relay, "/data/ports").await?;
+    let page = q.apply(list.rows)?;
+    Ok(Json(json!({"rows": page.rows, "total": page.total})))
 }
 
-async fn save_session(State(state): State<Arc<AppState>>, Json(body): Json<SessionData>) -> impl IntoResponse {
+// Ключ сессии: X-Session-Id / ?session=, затем ?host=, иначе общая сессия "default"
+#[derive(Deserialize)]
+struct SessionKeyQuery { session: Option<String>, host: Option<String> }
+
+fn session_key(headers: &HeaderMap, q: &SessionKeyQuery) -> String {
+    let header = headers.get("x-session-id").and_then(|v| v.to_str().ok()).filter(|s| !s.is_empty());
+    if let Some(id) = header.or(q.session.as_deref().filter(|s| !s.is_empty())) {
+        return format!("session-{}", id);
+    }
+    match q.host.as_deref().filter(|s| !s.is_empty()) {
+        Some(host) => format!("host-{}", host),
+        None => "default".into(),
+    }
+}
+
+async fn save_session(State(state): State<Arc<AppState>>, headers: HeaderMap, Query(q): Query<SessionKeyQuery>, Json(body): Json<SessionData>) -> impl IntoResponse {
+    let key = session_key(&headers, &q);
     info!(
-        "save_session: net={} cpu={} gpu={} host=\"{}\"",
+        "save_session: key={} net={} cpu={} gpu={} host=\"{}\"",
+        key,
         body.network_history.len(),
         body.cpu_history.len(),
         body.gpu_history.len(),
         body.current_host
     );
-    match state.session_data.lock() {
-        Ok(mut data) => {
-            *data = body;
-            Json(json!({"success": true}))
+    match state.sessions.save(&key, body) {
+        Ok(_) => Json(json!({"success": true})),
+        Err(e) => {
+            error!("save_session error: {}", e);
+            Json(json!({"success": false, "error": e}))
         }
-        Err(_) => Json(json!({"success": false, "error": "Failed to lock session data"}))
     }
 }
 
-async fn restore_session(State(state): State<Arc<AppState>>) -> impl IntoResponse {
-    debug!("restore_session");
-    match state.session_data.lock() {
-        Ok(data) => { 
+async fn restore_session(State(state): State<Arc<AppState>>, headers: HeaderMap, Query(q): Query<SessionKeyQuery>) -> impl IntoResponse {
+    let key = session_key(&headers, &q);
+    debug!("restore_session: key={}", key);
+    match state.sessions.load(&key) {
+        Ok(restored) => {
+            let (data, updated_at) = restored.map(|r| (r.data, r.updated_at)).unwrap_or_default();
             info!(
-                "restore_session: net={} cpu={} gpu={} host=\"{}\"",
+                "restore_session: key={} net={} cpu={} gpu={} host=\"{}\"",
+                key,
                 data.network_history.len(),
                 data.cpu_history.len(),
                 data.gpu_history.len(),
@@ -256,150 +632,361 @@ async fn restore_session(State(state): State<Arc<AppState>>) -> impl IntoRespons
                 "networkHistory": data.network_history,
                 "cpuHistory": data.cpu_history,
                 "gpuHistory": data.gpu_history,
-                "currentHost": data.current_host
+                "currentHost": data.current_host,
+                "updatedAt": updated_at
             }
         }))
         },
-        Err(_) => { error!("restore_session lock error"); Json(json!({"success": false, "error": "Failed to lock session data"})) }
+        Err(e) => { error!("restore_session error: {}", e); Json(json!({"success": false, "error": e})) }
     }
 }
 
-async fn get_ssh_status(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+async fn get_ssh_status(State(state): State<Arc<AppState>>, Relay(relay): Relay) -> RelayResult<SshStatus> {
     debug!("get_ssh_status");
-    let go_url = format!("{}/ssh/status", GO_SERVER_BASE);
-    let resp = state.http.get(&go_url).send().await;
-    match resp { 
-        Ok(r) => { let s=r.status(); let t=r.text().await.unwrap_or_default(); info!("get_ssh_status: status={} bytes={}", s.as_u16(), t.len()); (s,t) }, 
-        Err(e)=> { error!("get_ssh_status error: {}", e); (axum::http::StatusCode::BAD_GATEWAY, format!("connection error: {}", e)) } }
+    Ok(Json(state.relays.get_json(&relay, "/ssh/status").await?))
 }
 
-async fn process_action(State(state): State<Arc<AppState>>, Json(body): Json<ProcessActionReq>) -> impl IntoResponse {
+async fn process_action(State(state): State<Arc<AppState>>, client: ClientAddr, Relay(relay): Relay, Json(body): Json<ProcessActionReq>) -> Response {
     info!("process_action: pid={} action={}", body.pid, body.action);
-    let go_url = format!("{}/process/action", GO_SERVER_BASE);
-    let resp = state.http.post(&go_url).json(&json!({
+    let entry = AuditEntry::new("process_action", &client).on(&state.relays, &relay)
+        .command(&format!("{} {}", body.action, body.pid));
+    if let Err(denied) = state.policy_for(&relay).check_action(&body.pid, &body.action) {
+        info!("process_action: denied by policy ({})", denied.rule);
+        state.audit.record(entry.failed(AuditOutcome::Denied, &denied.error));
+        return denied.into_response();
+    }
+    let result: Result<ProcessActionResult, _> = state.relays.post_json(&relay, "/process/action", Some(&json!({
         "pid": body.pid,
         "action": body.action
-    })).send().await;
-    match resp {
-        Ok(r) => {
-            let status = r.status();
-            let text = r.text().await.unwrap_or_else(|_| "".into());
-            info!("process_action result: status={} bytes={}", status.as_u16(), text.len());
-            (status, text)
+    }))).await;
+    match result {
+        Ok(result) => {
+            info!("process_action result: success={} bytes={}", result.success, result.output.len());
+            state.audit.record(entry.ok(result.output.len()));
+            Json(result).into_response()
         }
         Err(e) => {
-            error!("process_action error: {}", e);
-            (axum::http::StatusCode::BAD_GATEWAY, format!("connection error: {}", e))
+            state.audit.record(entry.failed(AuditOutcome::Error, &e.message()));
+            e.into_response()
         }
     }
 }
 
-const SERVERS_FILE: &str = "saved_servers.json";
-
-fn load_servers() -> Vec<SavedServer> {
-    if !Path::new(SERVERS_FILE).exists() {
-        return vec![];
-    }
-    match fs::read_to_string(SERVERS_FILE) {
-        Ok(content) => serde_json::from_str(&content).unwrap_or_default(),
-        Err(_) => vec![]
-    }
-}
-
-fn save_servers_to_file(servers: &Vec<SavedServer>) -> Result<(), String> {
-    let json = serde_json::to_string_pretty(servers).map_err(|e| e.to_string())?;
-    fs::write(SERVERS_FILE, json).map_err(|e| e.to_string())
+// Изменения сохранённых серверов в аудите
+fn audit_server(state: &AppState, client: &ClientAddr, action: &str, name: &str, details: serde_json::Value, result: &Result<(), VaultError>) {
+    let mut entry = AuditEntry::new(action, client);
+    entry.server = Some(name.to_string());
+    entry.details = Some(details);
+    state.audit.record(match result {
+        Ok(()) => entry.ok(0),
+        Err(e) => entry.failed(AuditOutcome::Error, &e.message()),
+    });
 }
 
-async fn save_server(_state: State<Arc<AppState>>, Json(body): Json<SaveServerReq>) -> impl IntoResponse {
+async fn save_server(State(state): State<Arc<AppState>>, client: ClientAddr, Json(body): Json<SaveServerReq>) -> Response {
     info!("save_server: name={} host={}", body.name, body.host);
-    
-    let mut servers = load_servers();
-    
-    // Remove existing server with same name
-    servers.retain(|s| s.name != body.name);
-    
-    // Add new server
-    servers.push(SavedServer {
-        name: body.name,
-        host: body.host,
-        user: body.user,
-        port: body.port,
-        password: body.password,
-    });
-    
-    match save_servers_to_file(&servers) {
-        Ok(_) => Json(json!({"ok": true})),
+    let auth = match body.auth.validate() {
+        Ok(auth) => auth,
+        Err(e) => return e.into_response(),
+    };
+    let details = json!({"host": body.host, "user": body.user, "port": body.port, "auth": auth.kind(), "policy": body.policy});
+    let result = state.servers.save(&body.name, &body.host, &body.user, body.port, &auth, body.policy);
+    audit_server(&state, &client, "server_save", &body.name, details, &result);
+    match result {
+        Ok(_) => Json(json!({"ok": true})).into_response(),
         Err(e) => {
-            error!("Failed to save servers: {}", e);
-            Json(json!({"ok": false, "error": e}))
+            error!("Failed to save server {}: {}", body.name, e.message());
+            e.into_response()
         }
     }
 }
 
-async fn list_servers(_state: State<Arc<AppState>>) -> impl IntoResponse {
+async fn list_servers(State(state): State<Arc<AppState>>) -> Result<Json<serde_json::Value>, VaultError> {
     debug!("list_servers");
-    let servers = load_servers();
+    let servers = state.servers.list()?;
     info!("list_servers: found {} servers", servers.len());
-    Json(json!({"servers": servers}))
+    Ok(Json(json!({"servers": servers, "locked": state.servers.is_locked()})))
+}
+
+// Факты инвентаря обновляются при каждом подключении к сохранённому серверу
+async fn collect_facts(state: Arc<AppState>, relay: RelayTarget, name: String) {
+    match state.relays.get_json::<SystemInfo>(&relay, "/data/system-info").await {
+        Ok(info) => {
+            if let Err(e) = state.servers.set_facts(&name, Facts::from_system_info(info)) {
+                error!("inventory: cannot store facts of {}: {}", name, e.message());
+            }
+        }
+        Err(e) => debug!("inventory: no system info for {}: {}", name, e.message()),
+    }
+}
+
+async fn list_inventory(State(state): State<Arc<AppState>>, Query(q): Query<InventoryQuery>) -> Result<Json<serde_json::Value>, VaultError> {
+    debug!("list_inventory: tag={:?} group={:?} env={:?} q={:?}", q.tag, q.group, q.env, q.q);
+    Ok(inventory::list(state.servers.list()?, &q, state.servers.is_locked()))
+}
+
+async fn update_inventory(State(state): State<Arc<AppState>>, client: ClientAddr, Json(body): Json<InventoryUpdate>) -> Result<Json<serde_json::Value>, VaultError> {
+    info!("update_inventory: name={} tags={:?} groups={:?} env={:?}", body.name, body.tags, body.groups, body.env);
+    let update = Inventory { tags: body.tags, groups: body.groups, env: body.env, notes: body.notes, jump_host: body.jump_host, facts: None };
+    let details = json!(update);
+    let result = state.servers.set_inventory(&body.name, update);
+    audit_server(&state, &client, "inventory_update", &body.name, details, &result);
+    result?;
+    Ok(Json(json!({"ok": true})))
 }
 
 #[derive(Deserialize)]
-struct DeleteServerReq { name: String }
+struct InventoryExportQuery { format: Option<String> }
 
-async fn delete_server(_state: State<Arc<AppState>>, Json(body): Json<DeleteServerReq>) -> impl IntoResponse {
-    info!("delete_server: name={}", body.name);
-    
-    let mut servers = load_servers();
-    let initial_count = servers.len();
-    servers.retain(|s| s.name != body.name);
-    
-    if servers.len() < initial_count {
-        match save_servers_to_file(&servers) {
-            Ok(_) => Json(json!({"ok": true})),
-            Err(e) => {
-                error!("Failed to save servers: {}", e);
-                Json(json!({"ok": false, "error": e}))
-            }
+async fn export_inventory(State(state): State<Arc<AppState>>, Query(q): Query<InventoryExportQuery>) -> Response {
+    debug!("export_inventory: format={:?}", q.format);
+    match state.servers.list() {
+        Ok(servers) => inventory::export(servers, q.format.as_deref()),
+        Err(e) => e.into_response(),
+    }
+}
+
+#[derive(Deserialize)]
+struct InventoryImportQuery { prune: Option<bool> }
+
+// Тело — YAML (или JSON) в формате /rust/inventory/export
+async fn import_inventory(State(state): State<Arc<AppState>>, client: ClientAddr, Query(q): Query<InventoryImportQuery>, body: String) -> Response {
+    let prune = q.prune.unwrap_or(false);
+    let mut entry = AuditEntry::new("inventory_import", &client);
+    let file = match inventory::parse(&body) {
+        Ok(file) => file,
+        Err(e) => {
+            state.audit.record(entry.failed(AuditOutcome::Error, &e));
+            return (StatusCode::BAD_REQUEST, Json(json!({"ok": false, "error": e}))).into_response();
+        }
+    };
+    info!("import_inventory: {} hosts prune={}", file.hosts.len(), prune);
+    match state.servers.import(file.hosts, prune) {
+        Ok(report) => {
+            entry.details = Some(json!(report));
+            state.audit.record(entry.ok(0));
+            Json(json!({"ok": true, "report": report})).into_response()
+        }
+        Err(e) => {
+            state.audit.record(entry.failed(AuditOutcome::Error, &e.message()));
+            e.into_response()
         }
-    } else {
-        Json(json!({"ok": false, "error": "Server not found"}))
     }
 }
 
-async fn get_services(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+#[derive(Deserialize)]
+struct DeleteServerReq { name: String }
+
+async fn delete_server(State(state): State<Arc<AppState>>, client: ClientAddr, Json(body): Json<DeleteServerReq>) -> Result<Json<serde_json::Value>, VaultError> {
+    info!("delete_server: name={}", body.name);
+    let result = state.servers.delete(&body.name);
+    audit_server(&state, &client, "server_delete", &body.name, json!({}), &result);
+    result?;
+    Ok(Json(json!({"ok": true})))
+}
+
+#[derive(Deserialize)]
+struct ServerPolicyReq {
+    name: String,
+    // null — снять политику сервера, дальше действует [policy]
+    policy: Option<CommandPolicy>,
+}
+
+async fn set_server_policy(State(state): State<Arc<AppState>>, client: ClientAddr, Json(body): Json<ServerPolicyReq>) -> Result<Json<serde_json::Value>, VaultError> {
+    info!("set_server_policy: name={} policy={:?}", body.name, body.policy);
+    let details = json!({"policy": body.policy});
+    let result = state.servers.set_policy(&body.name, body.policy);
+    audit_server(&state, &client, "server_policy", &body.name, details, &result);
+    result?;
+    Ok(Json(json!({"ok": true})))
+}
+
+async fn list_runbooks(State(state): State<Arc<AppState>>) -> Result<Json<serde_json::Value>, RunbookError> {
+    debug!("list_runbooks");
+    Ok(Json(json!({"runbooks": state.runbooks.list()?})))
+}
+
+// Изменения runbook'ов и заданий в аудите, как и у сохранённых серверов
+fn audit_change(state: &AppState, client: &ClientAddr, action: &str, details: serde_json::Value, result: &Result<(), RunbookError>) {
+    let mut entry = AuditEntry::new(action, client);
+    entry.details = Some(details);
+    state.audit.record(match result {
+        Ok(()) => entry.ok(0),
+        Err(e) => entry.failed(AuditOutcome::Error, &e.message()),
+    });
+}
+
+async fn save_runbook(State(state): State<Arc<AppState>>, client: ClientAddr, Json(body): Json<Runbook>) -> Result<Json<serde_json::Value>, RunbookError> {
+    info!("save_runbook: name={} steps={}", body.name, body.steps.len());
+    let details = json!({"name": body.name, "steps": body.steps});
+    let result = state.runbooks.save(body);
+    audit_change(&state, &client, "runbook_save", details, &result);
+    result?;
+    Ok(Json(json!({"ok": true})))
+}
+
+#[derive(Deserialize)]
+struct DeleteRunbookReq { name: String }
+
+async fn delete_runbook(State(state): State<Arc<AppState>>, client: ClientAddr, Json(body): Json<DeleteRunbookReq>) -> Result<Json<serde_json::Value>, RunbookError> {
+    info!("delete_runbook: name={}", body.name);
+    let result = state.runbooks.delete(&body.name);
+    audit_change(&state, &client, "runbook_delete", json!({"name": body.name}), &result);
+    result?;
+    Ok(Json(json!({"ok": true})))
+}
+
+#[derive(Deserialize)]
+struct RunRunbookReq {
+    name: String,
+    // Имена сохранённых серверов
+    servers: Vec<String>,
+    concurrency: Option<usize>,
+    // Ошибка на одном хосте останавливает остальные (по умолчанию да)
+    stop_on_failure: Option<bool>,
+}
+
+// Запускается в фоне: 202 с id, отчёт — /rust/runbooks/report?id=
+async fn run_runbook(State(state): State<Arc<AppState>>, client: ClientAddr, RelayOnly(relay): RelayOnly, Json(body): Json<RunRunbookReq>) -> Result<Response, RunbookError> {
+    info!("run_runbook: name={} servers={:?} relay={}", body.name, body.servers, relay.backend.name);
+    let runbook = state.runbooks.get(&body.name)?;
+    let (report, _) = runbooks::start(state.runner.clone(), &state.runs, RunRequest {
+        runbook,
+        servers: body.servers,
+        backend: relay.backend,
+        concurrency: body.concurrency,
+        stop_on_failure: body.stop_on_failure.unwrap_or(true),
+        client,
+    })?;
+    Ok((StatusCode::ACCEPTED, Json(json!({"id": report.id, "report": report}))).into_response())
+}
+
+async fn list_runbook_runs(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    debug!("list_runbook_runs");
+    Json(state.runs.describe())
+}
+
+#[derive(Deserialize)]
+struct RunIdQuery { id: String }
+
+async fn runbook_report(State(state): State<Arc<AppState>>, Query(q): Query<RunIdQuery>) -> Result<Json<serde_json::Value>, RunbookError> {
+    debug!("runbook_report: id={}", q.id);
+    Ok(Json(state.runs.report(&q.id)?))
+}
+
+async fn cancel_runbook_run(State(state): State<Arc<AppState>>, Json(body): Json<RunIdQuery>) -> Result<Json<serde_json::Value>, RunbookError> {
+    info!("cancel_runbook_run: id={}", body.id);
+    state.runs.cancel(&body.id)?;
+    Ok(Json(json!({"id": body.id, "cancelled": true})))
+}
+
+async fn list_jobs(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    debug!("list_jobs");
+    Json(state.scheduler.describe())
+}
+
+async fn save_job(State(state): State<Arc<AppState>>, client: ClientAddr, Json(body): Json<Job>) -> Result<Json<serde_json::Value>, RunbookError> {
+    info!("save_job: name={} schedule={:?}", body.name, body.schedule);
+    let details = json!(body);
+    let result = state.scheduler.save(body);
+    audit_change(&state, &client, "job_save", details, &result);
+    result?;
+    Ok(Json(json!({"ok": true})))
+}
+
+#[derive(Deserialize)]
+struct JobNameReq { name: String }
+
+async fn delete_job(State(state): State<Arc<AppState>>, client: ClientAddr, Json(body): Json<JobNameReq>) -> Result<Json<serde_json::Value>, RunbookError> {
+    info!("delete_job: name={}", body.name);
+    let result = state.scheduler.delete(&body.name);
+    audit_change(&state, &client, "job_delete", json!({"name": body.name}), &result);
+    result?;
+    Ok(Json(json!({"ok": true})))
+}
+
+// Запуск вне расписания; 409, если прошлый запуск ещё идёт
+async fn run_job(State(state): State<Arc<AppState>>, client: ClientAddr, Json(body): Json<JobNameReq>) -> Result<Response, RunbookError> {
+    info!("run_job: name={}", body.name);
+    let job = state.scheduler.get(&body.name)?;
+    let id = state.scheduler.start(job, "manual", client)?;
+    Ok((StatusCode::ACCEPTED, Json(json!({"name": body.name, "run_id": id}))).into_response())
+}
+
+#[derive(Deserialize)]
+struct JobHistoryQuery { name: String, limit: Option<usize> }
+
+async fn job_history(State(state): State<Arc<AppState>>, Query(q): Query<JobHistoryQuery>) -> Result<Json<serde_json::Value>, RunbookError> {
+    debug!("job_history: name={}", q.name);
+    state.scheduler.get(&q.name)?;
+    let runs = state.scheduler.history(&q.name, q.limit.unwrap_or(20))?;
+    Ok(Json(json!({"name": q.name, "runs": runs})))
+}
+
+async fn list_alerts(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    debug!("list_alerts");
+    Json(state.alerts.describe())
+}
+
+async fn alert_events(State(state): State<Arc<AppState>>, Query(q): Query<EventsQuery>) -> impl IntoResponse {
+    debug!("alert_events: rule={:?} host={:?}", q.rule, q.host);
+    Json(json!({"events": state.alerts.events(&q)}))
+}
+
+async fn test_alert_webhooks(State(state): State<Arc<AppState>>, client: ClientAddr, Json(body): Json<TestReq>) -> impl IntoResponse {
+    info!("test_alert_webhooks: sink={:?}", body.sink);
+    let result = state.alerts.test(&body).await;
+    let mut entry = AuditEntry::new("alert_webhook_test", &client);
+    entry.details = Some(json!({"sink": body.sink}));
+    state.audit.record(match &result {
+        Ok(_) => entry.ok(0),
+        Err((_, Json(err))) => entry.failed(AuditOutcome::Error, err["error"].as_str().unwrap_or_default()),
+    });
+    result
+}
+
+#[derive(Deserialize)]
+struct UnlockReq { master_key: String }
+
+async fn unlock_servers(State(state): State<Arc<AppState>>, Json(body): Json<UnlockReq>) -> Result<Json<serde_json::Value>, VaultError> {
+    info!("unlock_servers");
+    // Argon2 — десятки миллисекунд CPU, не в потоке рантайма
+    let servers = state.servers.clone();
+    tokio::task::spawn_blocking(move || servers.unlock(&body.master_key))
+        .await
+        .map_err(|e| VaultError::Storage(e.to_string()))?
+        .inspect_err(|e| error!("unlock_servers failed: {}", e.message()))?;
+    Ok(Json(json!({"ok": true, "locked": false})))
+}
+
+async fn lock_servers(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    info!("lock_servers");
+    state.servers.lock();
+    Json(json!({"ok": true, "locked": true}))
+}
+
+async fn get_services(State(state): State<Arc<AppState>>, Relay(relay): Relay, Query(q): Query<ListQuery>) -> RelayResult<serde_json::Value> {
     debug!("get_services");
-    let go_url = format!("{}/data/services", GO_SERVER_BASE);
-    let resp = state.http.get(&go_url).send().await;
-    match resp { 
-        Ok(r) => { let s=r.status(); let t=r.text().await.unwrap_or_default(); info!("get_services: status={} bytes={}", s.as_u16(), t.len()); (s,t) }, 
-        Err(e)=> { error!("get_services error: {}", e); (axum::http::StatusCode::BAD_GATEWAY, format!("connection error: {}", e)) } }
+    let list: ServiceList = state.relays.get_json(&relay, "/data/services").await?;
+    let page = q.apply(list.services)?;
+    Ok(Json(json!({"services": page.rows, "total": page.total})))
 }
 
-async fn get_disk_usage(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+async fn get_disk_usage(State(state): State<Arc<AppState>>, Relay(relay): Relay, Query(q): Query<ListQuery>) -> RelayResult<serde_json::Value> {
     debug!("get_disk_usage");
-    let go_url = format!("{}/data/disk-usage", GO_SERVER_BASE);
-    let resp = state.http.get(&go_url).send().await;
-    match resp { 
-        Ok(r) => { let s=r.status(); let t=r.text().await.unwrap_or_default(); info!("get_disk_usage: status={} bytes={}", s.as_u16(), t.len()); (s,t) }, 
-        Err(e)=> { error!("get_disk_usage error: {}", e); (axum::http::StatusCode::BAD_GATEWAY, format!("connection error: {}", e)) } }
+    let usage: DiskUsage = state.relays.get_json(&relay, "/data/disk-usage").await?;
+    let page = q.apply(usage.directories)?;
+    Ok(Json(json!({"directories": page.rows, "total": page.total})))
 }
 
-async fn get_users(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+async fn get_users(State(state): State<Arc<AppState>>, Relay(relay): Relay, Query(q): Query<ListQuery>) -> RelayResult<serde_json::Value> {
     debug!("get_users");
-    let go_url = format!("{}/data/users", GO_SERVER_BASE);
-    let resp = state.http.get(&go_url).send().await;
-    match resp { 
-        Ok(r) => { let s=r.status(); let t=r.text().await.unwrap_or_default(); info!("get_users: status={} bytes={}", s.as_u16(), t.len()); (s,t) }, 
-        Err(e)=> { error!("get_users error: {}", e); (axum::http::StatusCode::BAD_GATEWAY, format!("connection error: {}", e)) } }
+    let list: UserList = state.relays.get_json(&relay, "/data/users").await?;
+    let page = q.apply(list.users)?;
+    Ok(Json(json!({"users": page.rows, "total": page.total})))
 }
 
-async fn get_system_info(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+async fn get_system_info(State(state): State<Arc<AppState>>, Relay(relay): Relay) -> RelayResult<SystemInfo> {
     debug!("get_system_info");
-    let go_url = format!("{}/data/system-info", GO_SERVER_BASE);
-    let resp = state.http.get(&go_url).send().await;
-    match resp { 
-        Ok(r) => { let s=r.status(); let t=r.text().await.unwrap_or_default(); info!("get_system_info: status={} bytes={}", s.as_u16(), t.len()); (s,t) }, 
-        Err(e)=> { error!("get_system_info error: {}", e); (axum::http::StatusCode::BAD_GATEWAY, format!("connection error: {}", e)) } }
+    Ok(Json(state.relays.get_json(&relay, "/data/system-info").await?))
 }
-
@@ -0,0 +1,409 @@
+// Потоковый execute: POST /rust/ssh/execute/stream отдаёт вывод команды событиями SSE по мере появления
+// (start, stdout, stderr, exit, error), POST /rust/ssh/execute/cancel {"id"} или закрытие соединения её останавливает
+use axum::{
+    http::{HeaderValue, StatusCode},
+    response::{sse::{Event, KeepAlive, Sse}, IntoResponse, Response},
+    Json,
+};
+use chrono::{DateTime, Utc};
+use futures_util::{stream, StreamExt};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use tokio::sync::{mpsc, oneshot};
+use tracing::{debug, info};
+
+use super::audit::{AuditEntry, AuditLog, AuditOutcome};
+use super::auth::{AuthUser, Role};
+use super::relay::{RelayError, RelayRegistry, RelayTarget};
+use super::ssh_sessions::new_id;
+
+pub const EXEC_ID_HEADER: &str = "x-exec-id";
+
+struct Running {
+    // Кто запустил; None — вход выключен
+    user: Option<String>,
+    relay: String,
+    session: Option<String>,
+    command: String,
+    started_at: DateTime<Utc>,
+    cancel: oneshot::Sender<()>,
+}
+
+#[derive(Default)]
+pub struct Executions {
+    running: Mutex<HashMap<String, Running>>,
+}
+
+// Свои команды; admin и режим без входа видят все
+fn visible(run: &Running, user: Option<&AuthUser>) -> bool {
+    match user {
+        None => true,
+        Some(user) => user.role == Role::Admin || run.user.as_deref() == Some(user.name.as_str()),
+    }
+}
+
+impl Executions {
+    // false — команды с таким id нет (уже завершилась) или она чужая
+    pub fn cancel(&self, id: &str, user: Option<&AuthUser>) -> bool {
+        let run = self.running.lock().ok().and_then(|mut r| {
+            r.get(id).filter(|run| visible(run, user))?;
+            r.remove(id)
+        });
+        let Some(run) = run else { return false };
+        info!("exec {}: cancel requested by {} ({})", id, user.map_or("-", |u| u.name.as_str()), run.command);
+        let _ = run.cancel.send(());
+        true
+    }
+
+    // GET /rust/ssh/execute/running
+    pub fn describe(&self, user: Option<&AuthUser>) -> Value {
+        let running = self.running.lock().map(|r| {
+            r.iter().filter(|(_, run)| visible(run, user)).map(|(id, run)| json!({
+                "id": id,
+                "user": run.user,
+                "relay": run.relay,
+                "session": run.session,
+                "command": run.command,
+                "started_at": run.started_at,
+            })).collect::<Vec<_>>()
+        }).unwrap_or_default();
+        json!({"running": running})
+    }
+
+    fn insert(&self, id: &str, run: Running) {
+        if let Ok(mut r) = self.running.lock() {
+            r.insert(id.to_string(), run);
+        }
+    }
+
+    fn remove(&self, id: &str) {
+        if let Ok(mut r) = self.running.lock() {
+            r.remove(id);
+        }
+    }
+}
+
+pub enum ExecEvent {
+    Output { stream: &'static str, data: String },
+    Exit { code: Option<i64>, signal: Option<String>, cancelled: bool, elapsed_ms: u64 },
+    Error(String),
+}
+
+impl ExecEvent {
+    fn into_sse(self) -> Event {
+        let (name, data) = match self {
+            ExecEvent::Output { stream, data } => (stream, json!({"data": data})),
+            ExecEvent::Exit { code, signal, cancelled, elapsed_ms } => {
+                ("exit", json!({"code": code, "signal": signal, "cancelled": cancelled, "elapsed_ms": elapsed_ms}))
+            }
+            ExecEvent::Error(message) => ("error", json!({"message": message})),
+        };
+        Event::default().event(name).data(data.to_string())
+    }
+}
+
+enum Outcome {
+    Exited { code: Option<i64>, signal: Option<String> },
+    Cancelled,
+    Failed(String),
+}
+
+// Откуда читается вывод: NDJSON-поток Go relay или канал встроенного SSH-клиента
+enum Source {
+    Http(reqwest::Response),
+    #[cfg(feature = "native-ssh")]
+    Native(super::native_ssh::ShellChannel),
+}
+
+// Команда запускается до ответа, чтобы "ssh not connected" и прочие ошибки ушли обычным JSON
+async fn start_source(registry: &RelayRegistry, target: &RelayTarget, command: &str) -> Result<Source, RelayError> {
+    #[cfg(feature = "native-ssh")]
+    if let Some(native) = target.backend.native() {
+        return native.start(target.session.as_deref(), command).await.map(Source::Native);
+    }
+    let resp = registry.open_stream(target, "/ssh/execute/stream", &json!({"command": command})).await?;
+    Ok(Source::Http(resp))
+}
+
+pub async fn start(executions: Arc<Executions>, registry: Arc<RelayRegistry>, target: RelayTarget, command: String, audit: (Arc<AuditLog>, AuditEntry)) -> Response {
+    let (audit, entry) = audit;
+    let source = match start_source(&registry, &target, &command).await {
+        Ok(source) => source,
+        Err(e) => {
+            audit.record(entry.failed(AuditOutcome::Error, &e.message()));
+            return e.into_response();
+        }
+    };
+    let id = new_id();
+    let preview = command.chars().take(200).collect::<String>();
+    info!("exec {}: started on relay {} ({})", id, target.backend.name, preview);
+    let (cancel_tx, cancel_rx) = oneshot::channel();
+    executions.insert(&id, Running {
+        user: entry.actor.clone(),
+        relay: target.backend.name.clone(),
+        session: target.session.clone(),
+        command: preview,
+        started_at: Utc::now(),
+        cancel: cancel_tx,
+    });
+
+    let (tx, rx) = mpsc::channel::<ExecEvent>(64);
+    let exec_id = id.clone();
+    tokio::spawn(async move {
+        let started = Instant::now();
+        let mut sink = Sink { tx, bytes: 0 };
+        let outcome = pump(source, &mut sink, cancel_rx).await;
+        executions.remove(&exec_id);
+        let elapsed_ms = started.elapsed().as_millis() as u64;
+        let mut entry = entry.ok(sink.bytes as usize);
+        let last = match outcome {
+            Outcome::Exited { code, signal } => {
+                info!("exec {}: exited with {:?} in {} ms", exec_id, code, elapsed_ms);
+                entry.exit_status = code;
+                if code != Some(0) {
+                    entry.outcome = AuditOutcome::Error;
+                    entry.error = signal.as_ref().map(|s| format!("killed by signal {}", s));
+                }
+                ExecEvent::Exit { code, signal, cancelled: false, elapsed_ms }
+            }
+            Outcome::Cancelled => {
+                info!("exec {}: cancelled after {} ms", exec_id, elapsed_ms);
+                entry.outcome = AuditOutcome::Cancelled;
+                ExecEvent::Exit { code: None, signal: None, cancelled: true, elapsed_ms }
+            }
+            Outcome::Failed(e) => {
+                info!("exec {}: failed: {}", exec_id, e);
+                entry = entry.failed(AuditOutcome::Error, &e);
+                ExecEvent::Error(e)
+            }
+        };
+        audit.record(entry);
+        let _ = sink.tx.send(last).await;
+    });
+
+    let first = Event::default().event("start").data(json!({"id": id}).to_string());
+    let events = stream::unfold(rx, |mut rx| async move { rx.recv().await.map(|e| (e.into_sse(), rx)) });
+    let body = stream::once(async move { first }).chain(events).map(Ok::<_, Infallible>);
+    let mut resp = Sse::new(body).keep_alive(KeepAlive::default()).into_response();
+    if let Ok(value) = HeaderValue::from_str(&id) {
+        resp.headers_mut().insert(EXEC_ID_HEADER, value);
+    }
+    resp
+}
+
+// Вывод команды без SSE и аудита — для слежения за логами; последнее событие — Exit или Error
+pub async fn spawn(registry: &RelayRegistry, target: &RelayTarget, command: &str, cancel: oneshot::Receiver<()>) -> Result<mpsc::Receiver<ExecEvent>, RelayError> {
+    let source = start_source(registry, target, command).await?;
+    let (tx, rx) = mpsc::channel::<ExecEvent>(64);
+    tokio::spawn(async move {
+        let started = Instant::now();
+        let mut sink = Sink { tx, bytes: 0 };
+        let elapsed_ms = || started.elapsed().as_millis() as u64;
+        let last = match pump(source, &mut sink, cancel).await {
+            Outcome::Exited { code, signal } => ExecEvent::Exit { code, signal, cancelled: false, elapsed_ms: elapsed_ms() },
+            Outcome::Cancelled => ExecEvent::Exit { code: None, signal: None, cancelled: true, elapsed_ms: elapsed_ms() },
+            Outcome::Failed(e) => ExecEvent::Error(e),
+        };
+        let _ = sink.tx.send(last).await;
+    });
+    Ok(rx)
+}
+
+async fn pump(source: Source, sink: &mut Sink, cancel: oneshot::Receiver<()>) -> Outcome {
+    match source {
+        Source::Http(resp) => pump_http(resp, sink, cancel).await,
+        #[cfg(feature = "native-ssh")]
+        Source::Native(channel) => native::pump(channel, sink, cancel).await,
+    }
+}
+
+pub fn cancel(executions: &Executions, id: &str, user: Option<&AuthUser>) -> Response {
+    if executions.cancel(id, user) {
+        Json(json!({"id": id, "cancelled": true})).into_response()
+    } else {
+        let msg = format!("unknown execution: {}", id);
+        (StatusCode::NOT_FOUND, Json(json!({"error": msg}))).into_response()
+    }
+}
+
+// Канал событий в SSE-ответ; считает байты вывода для аудита
+struct Sink {
+    tx: mpsc::Sender<ExecEvent>,
+    bytes: u64,
+}
+
+impl Sink {
+    // false — браузер ушёл, команду пора остановить
+    async fn emit(&mut self, stream: &'static str, data: Option<String>) -> bool {
+        let Some(data) = data else { return true };
+        self.bytes += data.len() as u64;
+        self.tx.send(ExecEvent::Output { stream, data }).await.is_ok()
+    }
+}
+
+// Строки Go relay: {"stream","data"}, в конце {"exit","signal"} и/или {"error"}.
+// Отмена — закрытие соединения, relay сам убивает команду
+async fn pump_http(resp: reqwest::Response, sink: &mut Sink, mut cancel: oneshot::Receiver<()>) -> Outcome {
+    let mut body = resp.bytes_stream();
+    let mut pending = Vec::new();
+    loop {
+        let chunk = tokio::select! {
+            _ = &mut cancel => return Outcome::Cancelled,
+            _ = sink.tx.closed() => return Outcome::Cancelled,
+            chunk = body.next() => chunk,
+        };
+        let bytes = match chunk {
+            Some(Ok(bytes)) => bytes,
+            Some(Err(e)) => return Outcome::Failed(format!("relay stream error: {}", e)),
+            None => return Outcome::Failed("relay closed the stream without an exit status".into()),
+        };
+        pending.extend_from_slice(&bytes);
+        while let Some(pos) = pending.iter().position(|&b| b == b'\n') {
+            let line: Vec<u8> = pending.drain(..=pos).collect();
+            let Ok(msg) = serde_json::from_slice::<Value>(&line) else {
+                debug!("exec: skipping invalid relay line");
+                continue;
+            };
+            if let Some(stream) = msg["stream"].as_str() {
+                let stream = if stream == "stderr" { "stderr" } else { "stdout" };
+                let data = msg["data"].as_str().map(|s| s.to_string());
+                if !sink.emit(stream, data).await {
+                    return Outcome::Cancelled;
+                }
+                continue;
+            }
+            let error = msg["error"].as_str().map(|s| s.to_string());
+            if msg.get("exit").is_none() {
+                return Outcome::Failed(error.unwrap_or_else(|| "relay sent an unknown event".into()));
+            }
+            if let Some(e) = error {
+                let _ = sink.tx.send(ExecEvent::Error(e)).await;
+            }
+            let signal = msg["signal"].as_str().filter(|s| !s.is_empty()).map(|s| s.to_string());
+            return Outcome::Exited { code: msg["exit"].as_i64(), signal };
+        }
+    }
+}
+
+#[cfg(feature = "native-ssh")]
+mod native {
+    use russh::{ChannelMsg, Sig};
+    use tokio::sync::oneshot;
+
+    use super::{Outcome, Sink};
+    use crate::routes::native_ssh::ShellChannel;
+
+    // Вывод приходит кусками: неполный UTF-8 символ в конце куска ждёт следующего
+    #[derive(Default)]
+    struct Utf8Buf(Vec<u8>);
+
+    impl Utf8Buf {
+        fn push(&mut self, chunk: &[u8]) -> Option<String> {
+            self.0.extend_from_slice(chunk);
+            let cut = match std::str::from_utf8(&self.0) {
+                Err(e) if e.error_len().is_none() => e.valid_up_to(),
+                _ => self.0.len(),
+            };
+            let rest = self.0.split_off(cut);
+            let text = String::from_utf8_lossy(&std::mem::replace(&mut self.0, rest)).into_owned();
+            (!text.is_empty()).then_some(text)
+        }
+
+        fn finish(&mut self) -> Option<String> {
+            let text = String::from_utf8_lossy(&std::mem::take(&mut self.0)).into_owned();
+            (!text.is_empty()).then_some(text)
+        }
+    }
+
+    fn signal_name(sig: Sig) -> String {
+        match sig {
+            Sig::Custom(name) => name,
+            sig => format!("{:?}", sig),
+        }
+    }
+
+    pub async fn pump(mut channel: ShellChannel, sink: &mut Sink, mut cancel: oneshot::Receiver<()>) -> Outcome {
+        let (mut stdout, mut stderr) = (Utf8Buf::default(), Utf8Buf::default());
+        let (mut code, mut signal) = (None, None);
+        loop {
+            let msg = tokio::select! {
+                _ = &mut cancel => None,
+                _ = sink.tx.closed() => None,
+                msg = channel.wait() => Some(msg),
+            };
+            let Some(msg) = msg else {
+                let _ = channel.signal(Sig::KILL).await;
+                let _ = channel.close().await;
+                return Outcome::Cancelled;
+            };
+            let alive = match msg {
+                Some(ChannelMsg::Data { data }) => sink.emit("stdout", stdout.push(&data)).await,
+                // ext = 1 — stderr
+                Some(ChannelMsg::ExtendedData { data, ext: 1 }) => sink.emit("stderr", stderr.push(&data)).await,
+                Some(ChannelMsg::ExitStatus { exit_status }) => {
+                    code = Some(exit_status as i64);
+                    true
+                }
+                Some(ChannelMsg::ExitSignal { signal_name: sig, .. }) => {
+                    signal = Some(signal_name(sig));
+                    true
+                }
+                Some(ChannelMsg::Close) | None => break,
+                Some(_) => true,
+            };
+            if !alive {
+                let _ = channel.close().await;
+                return Outcome::Cancelled;
+            }
+        }
+        sink.emit("stdout", stdout.finish()).await;
+        sink.emit("stderr", stderr.finish()).await;
+        Outcome::Exited { code, signal }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn user(name: &str, role: Role) -> AuthUser {
+        AuthUser { name: name.into(), role, token: None }
+    }
+
+    fn run(executions: &Executions, id: &str, owner: &str) -> oneshot::Receiver<()> {
+        let (cancel, rx) = oneshot::channel();
+        executions.insert(id, Running {
+            user: Some(owner.into()),
+            relay: "default".into(),
+            session: None,
+            command: "sleep 60".into(),
+            started_at: Utc::now(),
+            cancel,
+        });
+        rx
+    }
+
+    #[test]
+    fn only_owner_or_admin_sees_and_cancels() {
+        let executions = Executions::default();
+        let mut alice_rx = run(&executions, "a", "alice");
+        let _bob_rx = run(&executions, "b", "bob");
+        let (alice, bob) = (user("alice", Role::Operator), user("bob", Role::Operator));
+
+        let listed = executions.describe(Some(&alice));
+        assert_eq!(listed["running"].as_array().unwrap().len(), 1);
+        assert_eq!(listed["running"][0]["id"], "a");
+        assert_eq!(executions.describe(None)["running"].as_array().unwrap().len(), 2);
+
+        assert!(!executions.cancel("a", Some(&bob)));
+        assert!(alice_rx.try_recv().is_err());
+        assert!(executions.cancel("a", Some(&alice)));
+        assert!(alice_rx.try_recv().is_ok());
+
+        assert!(executions.cancel("b", Some(&user("root", Role::Admin))));
+        assert!(executions.describe(None)["running"].as_array().unwrap().is_empty());
+    }
+}
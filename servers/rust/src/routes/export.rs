@@ -0,0 +1,340 @@
+// Выгрузка истории метрик для отчётов об инцидентах: CSV, NDJSON или колоночный JSON,
+// сырые точки или агрегаты по интервалам step (min, max, avg, p95)
+use axum::{
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use serde_json::json;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+use super::history::{parse_since, MetricStore, Sample, METRICS};
+use super::session_store::SessionData;
+
+const AGGS: &[&str] = &["min", "max", "avg", "p95"];
+const FORMATS: &[&str] = &["csv", "ndjson", "json"];
+
+#[derive(Deserialize)]
+pub struct ExportQuery {
+    // Через запятую, по умолчанию все
+    pub metric: Option<String>,
+    pub host: Option<String>,
+    pub since: Option<String>,
+    pub until: Option<String>,
+    // 30s, 5m, 1h, 1d; без step — сырые точки
+    pub step: Option<String>,
+    // Через запятую, по умолчанию avg
+    pub agg: Option<String>,
+    pub format: Option<String>,
+    // poller (по умолчанию) или session — сохранённая сессия дашборда
+    pub source: Option<String>,
+    pub session: Option<String>,
+}
+
+pub struct Series {
+    pub host: String,
+    pub metric: String,
+    // По возрастанию ts
+    pub samples: Vec<Sample>,
+}
+
+// Строка выгрузки: count и агрегаты только при step, иначе одно значение
+struct Row<'a> {
+    host: &'a str,
+    metric: &'a str,
+    ts: i64,
+    count: Option<usize>,
+    values: Vec<f64>,
+}
+
+pub fn store_series(store: &MetricStore) -> Vec<Series> {
+    store.hosts().into_iter()
+        .flat_map(|h| METRICS.iter().map(move |m| (h.host.clone(), *m)))
+        .map(|(host, metric)| Series { samples: store.range(&host, metric, 0), host, metric: metric.to_string() })
+        .collect()
+}
+
+// Точки сессии без времени (сохранены до версии 2) пропускаются
+pub fn session_series(data: &SessionData) -> Vec<Series> {
+    let mut by_key: BTreeMap<(String, &str), Vec<Sample>> = BTreeMap::new();
+    let host = |h: &str| if h.is_empty() { data.current_host.clone() } else { h.to_string() };
+    for (metric, points) in [("cpu", &data.cpu_history), ("gpu", &data.gpu_history)] {
+        for p in points.iter().filter(|p| p.timestamp > 0) {
+            by_key.entry((host(&p.host), metric)).or_default().push(Sample { ts: p.timestamp as i64, value: p.value });
+        }
+    }
+    for p in data.network_history.iter().filter(|p| p.timestamp > 0) {
+        by_key.entry((host(&p.host), "net_rx")).or_default().push(Sample { ts: p.timestamp as i64, value: p.rx });
+        by_key.entry((host(&p.host), "net_tx")).or_default().push(Sample { ts: p.timestamp as i64, value: p.tx });
+    }
+    by_key.into_iter().map(|((host, metric), mut samples)| {
+        samples.sort_by_key(|s| s.ts);
+        Series { host, metric: metric.to_string(), samples }
+    }).collect()
+}
+
+fn parse_list(value: Option<&str>, allowed: &[&'static str], default: &[&'static str], what: &str) -> Result<Vec<&'static str>, String> {
+    let Some(value) = value.map(str::trim).filter(|s| !s.is_empty()) else { return Ok(default.to_vec()) };
+    let mut list = vec![];
+    for item in value.split(',').map(str::trim).filter(|s| !s.is_empty()) {
+        let Some(found) = allowed.iter().find(|a| **a == item) else { return Err(format!("unknown {} {:?}", what, item)) };
+        if !list.contains(found) {
+            list.push(*found);
+        }
+    }
+    Ok(list)
+}
+
+// 30s, 5m, 1h, 1d или просто секунды -> мс
+fn parse_step(step: Option<&str>) -> Result<Option<i64>, String> {
+    let Some(s) = step.map(str::trim).filter(|s| !s.is_empty()) else { return Ok(None) };
+    let (num, unit) = s.split_at(s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len()));
+    let secs = match unit {
+        "" | "s" => 1,
+        "m" => 60,
+        "h" => 3600,
+        "d" => 86400,
+        _ => return Err(format!("invalid step {}, expected e.g. 30s, 5m, 1h", s)),
+    };
+    match num.parse::<i64>().ok().filter(|n| *n > 0).and_then(|n| n.checked_mul(secs * 1000)) {
+        Some(ms) => Ok(Some(ms)),
+        None => Err(format!("invalid step {}, expected e.g. 30s, 5m, 1h", s)),
+    }
+}
+
+// Ближайший ранг: наименьшее значение, не меньше которого 95% точек
+fn percentile(sorted: &[f64], p: f64) -> f64 {
+    let rank = ((p * sorted.len() as f64).ceil() as usize).clamp(1, sorted.len());
+    sorted[rank - 1]
+}
+
+fn aggregate(values: &mut [f64], agg: &str) -> f64 {
+    match agg {
+        "min" => values.iter().copied().fold(f64::INFINITY, f64::min),
+        "max" => values.iter().copied().fold(f64::NEG_INFINITY, f64::max),
+        "p95" => {
+            values.sort_by(f64::total_cmp);
+            percentile(values, 0.95)
+        }
+        _ => values.iter().sum::<f64>() / values.len() as f64,
+    }
+}
+
+// Корзины выровнены по кратным step от эпохи: выгрузки разных хостов совпадают по времени
+fn downsample(samples: &[Sample], step: i64, aggs: &[&str]) -> Vec<(i64, usize, Vec<f64>)> {
+    let mut buckets: BTreeMap<i64, Vec<f64>> = BTreeMap::new();
+    for s in samples {
+        buckets.entry(s.ts.div_euclid(step) * step).or_default().push(s.value);
+    }
+    buckets.into_iter().map(|(ts, mut values)| {
+        let out = aggs.iter().map(|agg| aggregate(&mut values, agg)).collect();
+        (ts, values.len(), out)
+    }).collect()
+}
+
+fn rfc3339(ts: i64) -> String {
+    DateTime::<Utc>::from_timestamp_millis(ts).map(|d| d.to_rfc3339()).unwrap_or_default()
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+pub fn export(series: Vec<Series>, q: &ExportQuery) -> Response {
+    let bad = |msg: String| (StatusCode::BAD_REQUEST, Json(json!({"error": msg, "metrics": METRICS, "aggs": AGGS, "formats": FORMATS}))).into_response();
+    let metrics = match parse_list(q.metric.as_deref(), METRICS, METRICS, "metric") {
+        Ok(m) => m,
+        Err(e) => return bad(e),
+    };
+    let aggs = match parse_list(q.agg.as_deref(), AGGS, &["avg"], "agg") {
+        Ok(a) => a,
+        Err(e) => return bad(e),
+    };
+    let format = q.format.as_deref().unwrap_or("csv");
+    if !FORMATS.contains(&format) {
+        return bad(format!("unknown format {:?}", format));
+    }
+    let since = match parse_since(q.since.as_deref()) {
+        Ok(s) => s,
+        Err(e) => return bad(e),
+    };
+    let until = match q.until.as_deref().map(str::trim).filter(|s| !s.is_empty()) {
+        None => i64::MAX,
+        Some(u) => match parse_since(Some(u)) {
+            Ok(u) => u,
+            Err(_) => return bad(format!("invalid until {}, expected unix ms or RFC3339", u)),
+        },
+    };
+    let step = match parse_step(q.step.as_deref()) {
+        Ok(s) => s,
+        Err(e) => return bad(e),
+    };
+
+    let series: Vec<_> = series.into_iter()
+        .filter(|s| metrics.contains(&s.metric.as_str()))
+        .filter(|s| q.host.as_deref().is_none_or(|want| want == s.host))
+        .map(|mut s| {
+            s.samples.retain(|p| p.ts >= since && p.ts <= until);
+            s
+        })
+        .collect();
+    let mut rows = vec![];
+    for s in &series {
+        match step {
+            Some(step) => rows.extend(downsample(&s.samples, step, &aggs).into_iter().map(|(ts, count, values)| Row {
+                host: &s.host, metric: &s.metric, ts, count: Some(count), values,
+            })),
+            None => rows.extend(s.samples.iter().map(|p| Row {
+                host: &s.host, metric: &s.metric, ts: p.ts, count: None, values: vec![p.value],
+            })),
+        }
+    }
+    let value_columns: Vec<&str> = if step.is_some() { aggs.clone() } else { vec!["value"] };
+
+    let body = match format {
+        // ts — RFC3339: файл сразу читается в таблицах
+        "csv" => {
+            let mut out = String::new();
+            let mut columns = vec!["host", "metric", "ts"];
+            if step.is_some() {
+                columns.push("count");
+            }
+            columns.extend(&value_columns);
+            let _ = writeln!(out, "{}", columns.join(","));
+            for r in &rows {
+                let _ = write!(out, "{},{},{}", csv_field(r.host), r.metric, rfc3339(r.ts));
+                if let Some(count) = r.count {
+                    let _ = write!(out, ",{}", count);
+                }
+                for v in &r.values {
+                    let _ = write!(out, ",{}", v);
+                }
+                out.push('\n');
+            }
+            out
+        }
+        "ndjson" => {
+            let mut out = String::new();
+            for r in &rows {
+                let mut obj = json!({"host": r.host, "metric": r.metric, "ts": r.ts});
+                if let Some(count) = r.count {
+                    obj["count"] = json!(count);
+                }
+                for (name, v) in value_columns.iter().zip(&r.values) {
+                    obj[*name] = json!(v);
+                }
+                let _ = writeln!(out, "{}", obj);
+            }
+            out
+        }
+        // Колоночный вид: по массиву на столбец, как в Parquet/Arrow — удобно грузить в pandas и т.п.
+        _ => {
+            let mut columns = serde_json::Map::new();
+            columns.insert("host".into(), json!(rows.iter().map(|r| r.host).collect::<Vec<_>>()));
+            columns.insert("metric".into(), json!(rows.iter().map(|r| r.metric).collect::<Vec<_>>()));
+            columns.insert("ts".into(), json!(rows.iter().map(|r| r.ts).collect::<Vec<_>>()));
+            if step.is_some() {
+                columns.insert("count".into(), json!(rows.iter().map(|r| r.count).collect::<Vec<_>>()));
+            }
+            for (i, name) in value_columns.iter().enumerate() {
+                columns.insert(name.to_string(), json!(rows.iter().map(|r| r.values[i]).collect::<Vec<_>>()));
+            }
+            json!({
+                "rows": rows.len(),
+                "step_ms": step,
+                "since": since,
+                "until": (until != i64::MAX).then_some(until),
+                "columns": columns,
+            }).to_string()
+        }
+    };
+    let content_type = match format {
+        "csv" => "text/csv; charset=utf-8",
+        "ndjson" => "application/x-ndjson",
+        _ => "application/json",
+    };
+    let filename = format!("attachment; filename=\"metrics-{}.{}\"", Utc::now().format("%Y%m%dT%H%M%SZ"), format);
+    ([(header::CONTENT_TYPE, content_type.to_string()), (header::CONTENT_DISPOSITION, filename)], body).into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn samples(points: &[(i64, f64)]) -> Vec<Sample> {
+        points.iter().map(|&(ts, value)| Sample { ts, value }).collect()
+    }
+
+    #[test]
+    fn step_parsing_rejects_overflow() {
+        assert_eq!(parse_step(Some("5m")), Ok(Some(300_000)));
+        assert_eq!(parse_step(Some("90")), Ok(Some(90_000)));
+        assert_eq!(parse_step(None), Ok(None));
+        for bad in ["0s", "5w", "-1m", "9999999999999999d", "99999999999999999999"] {
+            assert!(parse_step(Some(bad)).is_err(), "{:?} should be rejected", bad);
+        }
+    }
+
+    #[test]
+    fn p95_is_nearest_rank() {
+        let mut values: Vec<f64> = (1..=20).rev().map(f64::from).collect();
+        // ceil(0.95 * 20) = 19-е значение
+        assert_eq!(aggregate(&mut values, "p95"), 19.0);
+        assert_eq!(aggregate(&mut [42.0], "p95"), 42.0);
+        let mut values: Vec<f64> = (1..=10).map(f64::from).collect();
+        assert_eq!(aggregate(&mut values, "p95"), 10.0);
+        assert_eq!(aggregate(&mut values, "min"), 1.0);
+        assert_eq!(aggregate(&mut values, "max"), 10.0);
+        assert_eq!(aggregate(&mut values, "avg"), 5.5);
+    }
+
+    #[test]
+    fn buckets_align_to_step_and_count_points() {
+        let points = samples(&[(59_000, 1.0), (60_000, 2.0), (61_500, 4.0), (119_999, 6.0), (185_000, 10.0)]);
+        let buckets = downsample(&points, 60_000, &["min", "max", "avg"]);
+        assert_eq!(buckets, [
+            (0, 1, vec![1.0, 1.0, 1.0]),
+            (60_000, 3, vec![2.0, 6.0, 4.0]),
+            (180_000, 1, vec![10.0, 10.0, 10.0]),
+        ]);
+    }
+
+    #[tokio::test]
+    async fn csv_has_count_column_only_with_step() {
+        let series = || vec![Series { host: "web-1".into(), metric: "cpu".into(), samples: samples(&[(0, 10.0), (30_000, 20.0), (60_000, 30.0)]) }];
+        let query = |step: Option<&str>| ExportQuery {
+            metric: Some("cpu".into()),
+            host: None,
+            since: Some("0".into()),
+            until: None,
+            step: step.map(str::to_string),
+            agg: Some("avg,max".into()),
+            format: None,
+            source: None,
+            session: None,
+        };
+        let body = |resp: Response| async move {
+            String::from_utf8(axum::body::to_bytes(resp.into_body(), usize::MAX).await.unwrap().to_vec()).unwrap()
+        };
+
+        let csv = body(export(series(), &query(Some("1m")))).await;
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines[0], "host,metric,ts,count,avg,max");
+        assert_eq!(lines[1], "web-1,cpu,1970-01-01T00:00:00+00:00,2,15,20");
+        assert_eq!(lines[2], "web-1,cpu,1970-01-01T00:01:00+00:00,1,30,30");
+
+        let raw = body(export(series(), &query(None))).await;
+        assert_eq!(raw.lines().next(), Some("host,metric,ts,value"));
+        assert_eq!(raw.lines().count(), 4);
+
+        let resp = export(series(), &query(Some("9999999999999999d")));
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    }
+}
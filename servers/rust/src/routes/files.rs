@@ -0,0 +1,474 @@
+// Файлы на хосте по SFTP встроенного SSH-клиента: /rust/files/list, /rust/files/read (кусками не больше
+// [files].max_read_kb), /rust/files/download (потоком) и /rust/files/upload (тело запроса как есть).
+// Доступ только внутри каталогов policy.files сессии; пути сравниваются после realpath на хосте
+use serde::Deserialize;
+
+use super::relay::RelayTarget;
+
+#[cfg_attr(not(feature = "native-ssh"), allow(dead_code))]
+#[derive(Deserialize)]
+pub struct FilesQuery {
+    // Пусто — первый разрешённый каталог, относительный путь — от него
+    pub path: Option<String>,
+    pub offset: Option<u64>,
+    pub length: Option<u64>,
+    // upload: без него существующий файл не перезаписывается
+    #[serde(default)]
+    pub overwrite: bool,
+}
+
+fn unsupported_message(target: &RelayTarget) -> String {
+    format!("relay {} has no file transfer support, use a relay with kind = \"native\"", target.backend.name)
+}
+
+#[cfg(not(feature = "native-ssh"))]
+pub use unsupported::{download, list, read, upload};
+
+#[cfg(not(feature = "native-ssh"))]
+mod unsupported {
+    use axum::{
+        body::Body,
+        http::StatusCode,
+        response::{IntoResponse, Response},
+        Json,
+    };
+    use serde_json::json;
+    use std::sync::Arc;
+
+    use super::{unsupported_message, FilesQuery};
+    use crate::config::FilesConfig;
+    use crate::routes::audit::{AuditEntry, AuditLog, AuditOutcome};
+    use crate::routes::policy::Effective;
+    use crate::routes::relay::RelayTarget;
+
+    fn unsupported(target: &RelayTarget) -> Response {
+        (StatusCode::NOT_IMPLEMENTED, Json(json!({"error": unsupported_message(target)}))).into_response()
+    }
+
+    pub async fn list(target: RelayTarget, _policy: Effective, _config: &FilesConfig, _q: FilesQuery) -> Response {
+        unsupported(&target)
+    }
+
+    pub async fn read(target: RelayTarget, _policy: Effective, _config: &FilesConfig, _q: FilesQuery) -> Response {
+        unsupported(&target)
+    }
+
+    pub async fn download(target: RelayTarget, _policy: Effective, _q: FilesQuery) -> Response {
+        unsupported(&target)
+    }
+
+    pub async fn upload(target: RelayTarget, _policy: Effective, _config: &FilesConfig, _q: FilesQuery, _body: Body, audit: (Arc<AuditLog>, AuditEntry)) -> Response {
+        let (audit, entry) = audit;
+        audit.record(entry.failed(AuditOutcome::Error, &unsupported_message(&target)));
+        unsupported(&target)
+    }
+}
+
+#[cfg(feature = "native-ssh")]
+pub use sftp::{download, list, read, upload};
+
+#[cfg(feature = "native-ssh")]
+mod sftp {
+    use axum::{
+        body::{Body, Bytes},
+        http::{header, StatusCode},
+        response::{IntoResponse, Response},
+        Json,
+    };
+    use base64::Engine;
+    use chrono::{DateTime, Utc};
+    use futures_util::{stream, StreamExt};
+    use russh_sftp::client::{error::Error as SftpError, fs::Metadata, SftpSession};
+    use russh_sftp::protocol::{OpenFlags, StatusCode as SftpStatus};
+    use serde_json::json;
+    use std::io::SeekFrom;
+    use std::sync::Arc;
+    use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt};
+    use tracing::{debug, info};
+
+    use super::{unsupported_message, FilesQuery};
+    use crate::config::FilesConfig;
+    use crate::routes::audit::{AuditEntry, AuditLog, AuditOutcome};
+    use crate::routes::policy::{Effective, PolicyDenied};
+    use crate::routes::relay::{RelayError, RelayTarget};
+    use crate::routes::ssh_sessions::new_id;
+
+    const CHUNK: usize = 64 * 1024;
+
+    enum FilesError {
+        Denied(PolicyDenied),
+        Relay(RelayError),
+        Failed(StatusCode, String),
+    }
+
+    impl FilesError {
+        fn message(&self) -> String {
+            match self {
+                FilesError::Denied(d) => d.error.clone(),
+                FilesError::Relay(e) => e.message(),
+                FilesError::Failed(_, msg) => msg.clone(),
+            }
+        }
+
+        fn outcome(&self) -> AuditOutcome {
+            match self {
+                FilesError::Denied(_) => AuditOutcome::Denied,
+                FilesError::Relay(_) | FilesError::Failed(..) => AuditOutcome::Error,
+            }
+        }
+    }
+
+    impl IntoResponse for FilesError {
+        fn into_response(self) -> Response {
+            match self {
+                FilesError::Denied(d) => d.into_response(),
+                FilesError::Relay(e) => e.into_response(),
+                FilesError::Failed(status, msg) => (status, Json(json!({"error": msg}))).into_response(),
+            }
+        }
+    }
+
+    fn bad(msg: impl Into<String>) -> FilesError {
+        FilesError::Failed(StatusCode::BAD_REQUEST, msg.into())
+    }
+
+    // Коды SFTP в HTTP: нет файла — 404, нет прав у пользователя SSH — 403
+    fn sftp_error(path: &str, e: SftpError) -> FilesError {
+        let status = match &e {
+            SftpError::Status(s) if s.status_code == SftpStatus::NoSuchFile => StatusCode::NOT_FOUND,
+            SftpError::Status(s) if s.status_code == SftpStatus::PermissionDenied => StatusCode::FORBIDDEN,
+            SftpError::Timeout => StatusCode::GATEWAY_TIMEOUT,
+            _ => StatusCode::BAD_GATEWAY,
+        };
+        // У Status текст сервера обычно повторяет код
+        let msg = match &e {
+            SftpError::Status(s) => s.status_code.to_string(),
+            e => e.to_string(),
+        };
+        FilesError::Failed(status, format!("{}: {}", path, msg))
+    }
+
+    fn io_error(path: &str, e: std::io::Error) -> FilesError {
+        FilesError::Failed(StatusCode::BAD_GATEWAY, format!("{}: {}", path, e))
+    }
+
+    fn kind(meta: &Metadata) -> &'static str {
+        let t = meta.file_type();
+        if t.is_dir() {
+            "dir"
+        } else if t.is_symlink() {
+            "symlink"
+        } else if t.is_file() {
+            "file"
+        } else {
+            "other"
+        }
+    }
+
+    fn describe(name: &str, path: &str, meta: &Metadata) -> serde_json::Value {
+        json!({
+            "name": name,
+            "path": path,
+            "type": kind(meta),
+            "size": meta.size,
+            "mode": meta.permissions.map(|p| format!("{:04o}", p & 0o7777)),
+            "modified": meta.mtime.and_then(|t| DateTime::<Utc>::from_timestamp(t as i64, 0)),
+        })
+    }
+
+    fn join(dir: &str, name: &str) -> String {
+        if dir.ends_with('/') { format!("{}{}", dir, name) } else { format!("{}/{}", dir, name) }
+    }
+
+    // SFTP-подсистема сессии и разрешённые каталоги в каноническом виде
+    struct Sandbox {
+        sftp: SftpSession,
+        roots: Vec<String>,
+        policy: Effective,
+    }
+
+    impl Sandbox {
+        async fn open(target: &RelayTarget, policy: Effective) -> Result<Sandbox, FilesError> {
+            let Some(native) = target.backend.native() else {
+                return Err(FilesError::Failed(StatusCode::NOT_IMPLEMENTED, unsupported_message(target)));
+            };
+            let sftp = native.sftp(target.session.as_deref()).await.map_err(FilesError::Relay)?;
+            // "." у SFTP — домашний каталог
+            let wanted = if policy.policy.files.is_empty() { vec![".".to_string()] } else { policy.policy.files.clone() };
+            let mut roots = vec![];
+            for root in &wanted {
+                match sftp.canonicalize(root.as_str()).await {
+                    Ok(real) => roots.push(real),
+                    Err(e) => debug!("files: skipping root {}: {}", root, e),
+                }
+            }
+            if roots.is_empty() {
+                let msg = format!("none of the allowed directories exist on the host: {}", wanted.join(", "));
+                return Err(FilesError::Failed(StatusCode::NOT_FOUND, msg));
+            }
+            Ok(Sandbox { sftp, roots, policy })
+        }
+
+        fn absolute(&self, path: Option<&str>) -> String {
+            match path.map(str::trim).filter(|p| !p.is_empty()) {
+                None => self.roots[0].clone(),
+                Some(p) if p.starts_with('/') => p.to_string(),
+                Some(p) => join(&self.roots[0], p),
+            }
+        }
+
+        // Существующий путь: realpath на хосте и проверка по roots
+        async fn resolve(&self, path: Option<&str>) -> Result<String, FilesError> {
+            let path = self.absolute(path);
+            let real = self.sftp.canonicalize(path.as_str()).await.map_err(|e| sftp_error(&path, e))?;
+            self.policy.check_path(&real, &self.roots).map_err(FilesError::Denied)?;
+            Ok(real)
+        }
+
+        // Файл, которого может ещё не быть: проверяется каталог, а существующий путь — целиком (симлинк наружу не пройдёт)
+        async fn resolve_new(&self, path: Option<&str>) -> Result<String, FilesError> {
+            let path = self.absolute(path);
+            let (dir, name) = path.trim_end_matches('/').rsplit_once('/').unwrap_or(("", ""));
+            if name.is_empty() || name == "." || name == ".." {
+                return Err(bad(format!("invalid file path {}", path)));
+            }
+            let dir = self.resolve(Some(if dir.is_empty() { "/" } else { dir })).await?;
+            let full = join(&dir, name);
+            match self.sftp.symlink_metadata(full.as_str()).await {
+                Ok(_) => self.resolve(Some(&full)).await,
+                Err(_) => Ok(full),
+            }
+        }
+
+        // temp -> path. SFTP rename обычно не перезаписывает существующий файл: тогда прежний отодвигается
+        // в сторону и удаляется только после переименования, при ошибке возвращается на место
+        async fn replace(&self, temp: &str, path: &str, existing: Option<Metadata>) -> Result<(), FilesError> {
+            if let Some(mode) = existing.as_ref().and_then(|m| m.permissions) {
+                let _ = self.sftp.set_metadata(temp, Metadata { permissions: Some(mode), ..Metadata::default() }).await;
+            }
+            let Err(e) = self.sftp.rename(temp, path).await else { return Ok(()) };
+            if existing.is_none() {
+                return Err(sftp_error(path, e));
+            }
+            let old = temp_name(path, "old");
+            self.sftp.rename(path, old.as_str()).await.map_err(|e| sftp_error(path, e))?;
+            if let Err(e) = self.sftp.rename(temp, path).await {
+                let _ = self.sftp.rename(old.as_str(), path).await;
+                return Err(sftp_error(path, e));
+            }
+            let _ = self.sftp.remove_file(old.as_str()).await;
+            Ok(())
+        }
+    }
+
+    // .name.upload-<id> в том же каталоге: rename не пересекает файловые системы
+    fn temp_name(path: &str, kind: &str) -> String {
+        let (dir, name) = path.rsplit_once('/').unwrap_or(("", path));
+        format!("{}/.{}.{}-{}", dir, name, kind, new_id())
+    }
+
+    pub async fn list(target: RelayTarget, policy: Effective, config: &FilesConfig, q: FilesQuery) -> Response {
+        let sandbox = match Sandbox::open(&target, policy).await {
+            Ok(s) => s,
+            Err(e) => return e.into_response(),
+        };
+        let result = async {
+            let path = sandbox.resolve(q.path.as_deref()).await?;
+            let dir = sandbox.sftp.read_dir(path.as_str()).await.map_err(|e| sftp_error(&path, e))?;
+            let mut entries: Vec<_> = dir.filter(|e| e.file_name() != "." && e.file_name() != "..").collect();
+            // Сначала каталоги, затем по имени
+            entries.sort_by_key(|e| (!e.file_type().is_dir(), e.file_name()));
+            let total = entries.len();
+            let items: Vec<_> = entries.iter().take(config.max_entries)
+                .map(|e| describe(&e.file_name(), &join(&path, &e.file_name()), &e.metadata()))
+                .collect();
+            // Родитель — только если он тоже внутри разрешённых каталогов
+            let parent = path.rsplit_once('/').map(|(p, _)| if p.is_empty() { "/".to_string() } else { p.to_string() })
+                .filter(|p| *p != path && sandbox.policy.check_path(p, &sandbox.roots).is_ok());
+            Ok::<_, FilesError>(json!({
+                "path": path,
+                "parent": parent,
+                "roots": sandbox.roots,
+                "entries": items,
+                "total": total,
+                "truncated": total > config.max_entries,
+            }))
+        }.await;
+        match result {
+            Ok(body) => Json(body).into_response(),
+            Err(e) => e.into_response(),
+        }
+    }
+
+    // Кусок файла: текст, если это UTF-8, иначе base64; незаконченный символ на конце уходит в следующий кусок
+    pub async fn read(target: RelayTarget, policy: Effective, config: &FilesConfig, q: FilesQuery) -> Response {
+        let sandbox = match Sandbox::open(&target, policy).await {
+            Ok(s) => s,
+            Err(e) => return e.into_response(),
+        };
+        let limit = config.max_read_kb * 1024;
+        let result = async {
+            let path = sandbox.resolve(q.path.as_deref()).await?;
+            let meta = sandbox.sftp.metadata(path.as_str()).await.map_err(|e| sftp_error(&path, e))?;
+            if meta.file_type().is_dir() {
+                return Err(bad(format!("{} is a directory", path)));
+            }
+            let size = meta.size.unwrap_or(0);
+            let offset = q.offset.unwrap_or(0);
+            let length = q.length.unwrap_or(limit).min(limit);
+            let mut file = sandbox.sftp.open(path.as_str()).await.map_err(|e| sftp_error(&path, e))?;
+            if offset > 0 {
+                file.seek(SeekFrom::Start(offset)).await.map_err(|e| io_error(&path, e))?;
+            }
+            let mut data = Vec::new();
+            (&mut file).take(length).read_to_end(&mut data).await.map_err(|e| io_error(&path, e))?;
+            let _ = file.shutdown().await;
+            let mut eof = offset + (data.len() as u64) >= size;
+            let (encoding, content) = match std::str::from_utf8(&data) {
+                Ok(text) => ("utf8", text.to_string()),
+                Err(e) if e.error_len().is_none() && !eof && e.valid_up_to() > 0 => {
+                    data.truncate(e.valid_up_to());
+                    eof = false;
+                    ("utf8", String::from_utf8_lossy(&data).into_owned())
+                }
+                Err(_) => ("base64", base64::engine::general_purpose::STANDARD.encode(&data)),
+            };
+            Ok::<_, FilesError>(json!({
+                "path": path,
+                "size": size,
+                "offset": offset,
+                "length": data.len(),
+                "eof": eof,
+                "limit": limit,
+                "encoding": encoding,
+                "data": content,
+            }))
+        }.await;
+        match result {
+            Ok(body) => Json(body).into_response(),
+            Err(e) => e.into_response(),
+        }
+    }
+
+    // Весь файл потоком; SFTP-сессию держит открытый файл, пока тело не дочитано
+    pub async fn download(target: RelayTarget, policy: Effective, q: FilesQuery) -> Response {
+        let sandbox = match Sandbox::open(&target, policy).await {
+            Ok(s) => s,
+            Err(e) => return e.into_response(),
+        };
+        let result = async {
+            let path = sandbox.resolve(q.path.as_deref()).await?;
+            let meta = sandbox.sftp.metadata(path.as_str()).await.map_err(|e| sftp_error(&path, e))?;
+            if meta.file_type().is_dir() {
+                return Err(bad(format!("{} is a directory", path)));
+            }
+            let file = sandbox.sftp.open(path.as_str()).await.map_err(|e| sftp_error(&path, e))?;
+            Ok::<_, FilesError>((path, meta.size, file))
+        }.await;
+        let (path, size, file) = match result {
+            Ok(r) => r,
+            Err(e) => return e.into_response(),
+        };
+        info!("files: download {} ({:?} bytes) on relay {}", path, size, target.backend.name);
+        let body = stream::unfold(Some(file), |file| async move {
+            let mut file = file?;
+            let mut buf = vec![0u8; CHUNK];
+            match file.read(&mut buf).await {
+                Ok(0) => None,
+                Ok(n) => {
+                    buf.truncate(n);
+                    Some((Ok(Bytes::from(buf)), Some(file)))
+                }
+                Err(e) => Some((Err(e), None)),
+            }
+        });
+        let name = path.rsplit('/').next().unwrap_or("file").replace(['"', '\\'], "_");
+        let mut resp = Body::from_stream(body).into_response();
+        let headers = resp.headers_mut();
+        headers.insert(header::CONTENT_TYPE, header::HeaderValue::from_static("application/octet-stream"));
+        if let Ok(v) = header::HeaderValue::from_str(&format!("attachment; filename=\"{}\"", name)) {
+            headers.insert(header::CONTENT_DISPOSITION, v);
+        }
+        if let Some(size) = size {
+            headers.insert(header::CONTENT_LENGTH, size.into());
+        }
+        resp
+    }
+
+    // Тело пишется в файл по мере прихода; при ошибке или превышении [files].max_upload_mb файл удаляется
+    pub async fn upload(target: RelayTarget, policy: Effective, config: &FilesConfig, q: FilesQuery, body: Body, audit: (Arc<AuditLog>, AuditEntry)) -> Response {
+        let (audit, mut entry) = audit;
+        entry.details = Some(json!({"path": q.path, "overwrite": q.overwrite}));
+        if let Err(denied) = policy.check_upload(q.path.as_deref().unwrap_or("")) {
+            audit.record(entry.failed(AuditOutcome::Denied, &denied.error));
+            return denied.into_response();
+        }
+        if q.path.as_deref().is_none_or(|p| p.trim().is_empty()) {
+            audit.record(entry.failed(AuditOutcome::Error, "path is required"));
+            return bad("path is required").into_response();
+        }
+        let sandbox = match Sandbox::open(&target, policy).await {
+            Ok(s) => s,
+            Err(e) => {
+                audit.record(entry.failed(e.outcome(), &e.message()));
+                return e.into_response();
+            }
+        };
+        let limit = config.max_upload_mb * 1024 * 1024;
+        let mut written = 0u64;
+        let mut resolved = None;
+        let result = async {
+            let path = sandbox.resolve_new(q.path.as_deref()).await?;
+            resolved = Some(path.clone());
+            let existing = match sandbox.sftp.metadata(path.as_str()).await {
+                Ok(meta) if meta.file_type().is_dir() => return Err(bad(format!("{} is a directory", path))),
+                Ok(meta) => Some(meta),
+                Err(_) => None,
+            };
+            let exists = existing.is_some();
+            if exists && !q.overwrite {
+                return Err(FilesError::Failed(StatusCode::CONFLICT, format!("{} already exists, pass overwrite=true to replace it", path)));
+            }
+            // Пишется во временный файл рядом: прежний файл заменяется только целиком загруженным
+            let temp = temp_name(&path, "upload");
+            let flags = OpenFlags::WRITE | OpenFlags::CREATE | OpenFlags::EXCLUDE;
+            let mut file = sandbox.sftp.open_with_flags(temp.as_str(), flags).await.map_err(|e| sftp_error(&path, e))?;
+            let mut chunks = body.into_data_stream();
+            let copied = async {
+                while let Some(chunk) = chunks.next().await {
+                    let chunk = chunk.map_err(|e| FilesError::Failed(StatusCode::BAD_REQUEST, format!("upload interrupted: {}", e)))?;
+                    written += chunk.len() as u64;
+                    if written > limit {
+                        let msg = format!("upload exceeds [files].max_upload_mb = {}", config.max_upload_mb);
+                        return Err(FilesError::Failed(StatusCode::PAYLOAD_TOO_LARGE, msg));
+                    }
+                    file.write_all(&chunk).await.map_err(|e| io_error(&path, e))?;
+                }
+                file.shutdown().await.map_err(|e| io_error(&path, e))
+            }.await;
+            drop(file);
+            let placed = match copied {
+                Ok(()) => sandbox.replace(&temp, &path, existing).await,
+                Err(e) => Err(e),
+            };
+            if let Err(e) = placed {
+                let _ = sandbox.sftp.remove_file(temp.as_str()).await;
+                return Err(e);
+            }
+            Ok(json!({"path": path, "size": written, "replaced": exists}))
+        }.await;
+        entry.bytes_in = written;
+        entry.details = Some(json!({"path": resolved.or(q.path), "overwrite": q.overwrite}));
+        match result {
+            Ok(body) => {
+                info!("files: uploaded {} bytes to {} on relay {}", written, body["path"], target.backend.name);
+                audit.record(entry.ok(0));
+                Json(body).into_response()
+            }
+            Err(e) => {
+                audit.record(entry.failed(e.outcome(), &e.message()));
+                e.into_response()
+            }
+        }
+    }
+}
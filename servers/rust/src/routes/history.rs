@@ -0,0 +1,289 @@
+// История метрик хостов: фоновый опрос relay и кольцевые буферы в памяти
+use axum::{http::StatusCode, Json};
+use chrono::{DateTime, Utc};
+use futures_util::future::join_all;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::time::MissedTickBehavior;
+use tracing::{debug, info};
+
+use crate::config::PollerConfig;
+use super::relay::{RelayRegistry, RelayTarget};
+use super::relay_models::{Resources, SshStatus};
+
+pub const METRICS: &[&str] = &["cpu", "load", "gpu", "ram_used", "ram_pct", "disk", "net_rx", "net_tx"];
+
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub struct Sample {
+    // unix ms
+    pub ts: i64,
+    pub value: f64,
+}
+
+struct RingBuffer {
+    cap: usize,
+    buf: VecDeque<Sample>,
+}
+
+impl RingBuffer {
+    fn new(cap: usize) -> RingBuffer {
+        RingBuffer { cap: cap.max(1), buf: VecDeque::with_capacity(cap.max(1)) }
+    }
+
+    fn push(&mut self, sample: Sample) {
+        if self.buf.len() == self.cap {
+            self.buf.pop_front();
+        }
+        self.buf.push_back(sample);
+    }
+
+    fn since(&self, since: i64) -> Vec<Sample> {
+        // Буфер упорядочен по времени
+        let start = self.buf.partition_point(|s| s.ts < since);
+        self.buf.range(start..).copied().collect()
+    }
+
+    fn last(&self) -> Option<Sample> {
+        self.buf.back().copied()
+    }
+}
+
+#[derive(Clone, Serialize)]
+pub struct HostInfo {
+    pub host: String,
+    pub relay: String,
+    pub last_seen: DateTime<Utc>,
+}
+
+// Байты rx, tx и время показания, мс
+type NetCounters = (i64, i64, i64);
+
+pub struct MetricStore {
+    capacity: usize,
+    series: Mutex<HashMap<(String, String), RingBuffer>>,
+    hosts: Mutex<HashMap<String, HostInfo>>,
+    // Прошлое показание счётчиков /proc/net/dev по (relay, host)
+    counters: Mutex<HashMap<(String, String), NetCounters>>,
+}
+
+impl MetricStore {
+    pub fn new(capacity: usize) -> MetricStore {
+        MetricStore { capacity, series: Mutex::new(HashMap::new()), hosts: Mutex::new(HashMap::new()), counters: Mutex::new(HashMap::new()) }
+    }
+
+    // Скорость, Мбит/с, между этим и прошлым показанием счётчиков; None — первое показание или счётчик сбросился
+    fn net_rate(&self, relay: &str, host: &str, ts: i64, rx: i64, tx: i64) -> Option<(f64, f64)> {
+        let mut counters = self.counters.lock().ok()?;
+        let prev = counters.insert((relay.to_string(), host.to_string()), (rx, tx, ts))?;
+        let (prev_rx, prev_tx, prev_ts) = prev;
+        if ts <= prev_ts || rx < prev_rx || tx < prev_tx {
+            return None;
+        }
+        let secs = (ts - prev_ts) as f64 / 1000.0;
+        let mbps = |bytes: i64| bytes as f64 / secs / (1024.0 * 1024.0) * 8.0;
+        Some((mbps(rx - prev_rx), mbps(tx - prev_tx)))
+    }
+
+    pub fn record(&self, relay: &str, host: &str, ts: i64, values: &[(&str, f64)]) {
+        if let Ok(mut series) = self.series.lock() {
+            for (metric, value) in values {
+                series.entry((host.to_string(), metric.to_string()))
+                    .or_insert_with(|| RingBuffer::new(self.capacity))
+                    .push(Sample { ts, value: *value });
+            }
+        }
+        if let Ok(mut hosts) = self.hosts.lock() {
+            let last_seen = DateTime::from_timestamp_millis(ts).unwrap_or_else(Utc::now);
+            hosts.insert(host.to_string(), HostInfo { host: host.to_string(), relay: relay.to_string(), last_seen });
+        }
+    }
+
+    pub fn range(&self, host: &str, metric: &str, since: i64) -> Vec<Sample> {
+        self.series.lock().ok()
+            .and_then(|s| s.get(&(host.to_string(), metric.to_string())).map(|b| b.since(since)))
+            .unwrap_or_default()
+    }
+
+    pub fn latest(&self, host: &str, metric: &str) -> Option<Sample> {
+        self.series.lock().ok()
+            .and_then(|s| s.get(&(host.to_string(), metric.to_string())).and_then(RingBuffer::last))
+    }
+
+    pub fn hosts(&self) -> Vec<HostInfo> {
+        let mut hosts: Vec<_> = self.hosts.lock().map(|h| h.values().cloned().collect()).unwrap_or_default();
+        hosts.sort_by(|a, b| a.host.cmp(&b.host));
+        hosts
+    }
+}
+
+fn resource_values(r: &Resources) -> Vec<(&'static str, f64)> {
+    let ram_pct = if r.ram.total > 0.0 { r.ram.used / r.ram.total * 100.0 } else { 0.0 };
+    vec![
+        ("cpu", r.cpu.usage),
+        ("load", r.cpu.load),
+        ("gpu", r.gpu.usage),
+        ("ram_used", r.ram.used),
+        ("ram_pct", ram_pct),
+        ("disk", r.ssd.util),
+    ]
+}
+
+// По одной SSH-сессии на хост; relay без сессий опрашивается как раньше
+pub fn poll_targets(registry: &RelayRegistry) -> Vec<RelayTarget> {
+    let sessions = registry.sessions.list();
+    let mut targets = vec![];
+    for backend in registry.backends() {
+        let mut hosts = HashSet::new();
+        let ids: Vec<_> = sessions.iter()
+            .filter(|s| s.relay == backend.name && hosts.insert(s.host.clone()))
+            .map(|s| s.id.clone())
+            .collect();
+        if ids.is_empty() {
+            targets.push(RelayTarget::relay(backend.clone()));
+        }
+        targets.extend(ids.into_iter().map(|id| RelayTarget { backend: backend.clone(), session: Some(id), explicit: true }));
+    }
+    targets
+}
+
+async fn poll_relay(registry: &RelayRegistry, store: &MetricStore, target: &RelayTarget) {
+    let backend = &target.backend;
+    let status: SshStatus = match registry.get_json(target, "/ssh/status").await {
+        Ok(status) => status,
+        Err(e) => {
+            debug!("poller: relay {} session {:?} status failed: {}", backend.name, target.session, e.message());
+            return;
+        }
+    };
+    if !status.connected || status.host.is_empty() {
+        return;
+    }
+    // /data/network не годится: это 60 точек для графика вокруг скорости с подмешанными колебаниями, и считается
+    // она от прошлого запроса любого клиента. Скорость — по счётчикам интерфейсов между опросами поллера
+    let read_counters = json!({"command": "cat /proc/net/dev"});
+    let (resources, counters) = tokio::join!(
+        registry.get_json::<Resources>(target, "/data/resources"),
+        registry.post_text(target, "/ssh/execute", &read_counters),
+    );
+    let ts = Utc::now().timestamp_millis();
+    let mut values = vec![];
+    if let Ok(r) = &resources {
+        values.extend(resource_values(r));
+    }
+    match counters.as_deref().map(parse_net_dev) {
+        Ok(Some((rx, tx))) => {
+            if let Some((rx, tx)) = store.net_rate(&backend.name, &status.host, ts, rx, tx) {
+                values.push(("net_rx", rx));
+                values.push(("net_tx", tx));
+            }
+        }
+        Ok(None) => debug!("poller: relay {} session {:?}: no interfaces in /proc/net/dev", backend.name, target.session),
+        Err(e) => debug!("poller: relay {} session {:?} network counters failed: {}", backend.name, target.session, e.message()),
+    }
+    if !values.is_empty() {
+        store.record(&backend.name, &status.host, ts, &values);
+    }
+}
+
+// "iface: rx ... tx" из /proc/net/dev без lo: суммарные байты (rx, tx); None — ни одного интерфейса
+pub fn parse_net_dev(out: &str) -> Option<(i64, i64)> {
+    let counters: Vec<(i64, i64)> = out.lines()
+        .filter_map(|ln| ln.split_once(':'))
+        .filter(|(iface, _)| iface.trim() != "lo")
+        .filter_map(|(_, rest)| {
+            let f: Vec<&str> = rest.split_whitespace().collect();
+            Some((f.first()?.parse::<i64>().ok()?, f.get(8)?.parse::<i64>().ok()?))
+        })
+        .collect();
+    if counters.is_empty() {
+        return None;
+    }
+    Some(counters.iter().fold((0, 0), |acc, (rx, tx)| (acc.0 + rx, acc.1 + tx)))
+}
+
+pub fn spawn_poller(registry: Arc<RelayRegistry>, store: Arc<MetricStore>, config: &PollerConfig) {
+    if !config.enabled || config.interval_secs == 0 {
+        info!("metric poller disabled");
+        return;
+    }
+    let every = Duration::from_secs(config.interval_secs);
+    info!("metric poller: every {:?}, {} samples per series", every, config.capacity);
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(every);
+        ticker.set_missed_tick_behavior(MissedTickBehavior::Skip);
+        loop {
+            ticker.tick().await;
+            let targets = poll_targets(&registry);
+            join_all(targets.iter().map(|t| poll_relay(&registry, &store, t))).await;
+        }
+    });
+}
+
+// since: unix ms или RFC3339
+pub fn parse_since(since: Option<&str>) -> Result<i64, String> {
+    match since.map(str::trim).filter(|s| !s.is_empty()) {
+        None => Ok(0),
+        Some(s) => s.parse::<i64>().or_else(|_| {
+            DateTime::parse_from_rfc3339(s)
+                .map(|d| d.timestamp_millis())
+                .map_err(|_| format!("invalid since {}, expected unix ms or RFC3339", s))
+        }),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct HistoryQuery {
+    pub metric: Option<String>,
+    pub since: Option<String>,
+    pub host: Option<String>,
+}
+
+pub fn history(store: &MetricStore, q: &HistoryQuery) -> Result<Json<serde_json::Value>, (StatusCode, Json<serde_json::Value>)> {
+    let bad = |msg: String| (StatusCode::BAD_REQUEST, Json(json!({"error": msg, "metrics": METRICS})));
+    let metric = q.metric.as_deref().unwrap_or("");
+    if !METRICS.contains(&metric) {
+        return Err(bad(format!("unknown metric {:?}", metric)));
+    }
+    let since = parse_since(q.since.as_deref()).map_err(bad)?;
+    let series: Vec<_> = store.hosts().into_iter()
+        .filter(|h| q.host.as_deref().is_none_or(|want| want == h.host))
+        .map(|h| {
+            let samples = store.range(&h.host, metric, since);
+            json!({"host": h.host, "relay": h.relay, "samples": samples})
+        })
+        .collect();
+    Ok(Json(json!({"metric": metric, "since": since, "series": series})))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NET_DEV: &str = "Inter-|   Receive                                                |  Transmit
+ face |bytes    packets errs drop fifo frame compressed multicast|bytes    packets errs drop fifo colls carrier compressed
+    lo: 5000      10    0    0    0     0          0         0     5000      10    0    0    0     0       0          0
+  eth0: 1048576   100    0    0    0     0          0         0   2097152     200    0    0    0     0       0          0
+  eth1: 1048576   100    0    0    0     0          0         0         0       0    0    0    0     0       0          0";
+
+    #[test]
+    fn parse_net_dev_sums_interfaces_without_loopback() {
+        assert_eq!(parse_net_dev(NET_DEV), Some((2 * 1048576, 2097152)));
+        assert_eq!(parse_net_dev("ssh: command not found"), None);
+    }
+
+    #[test]
+    fn net_rate_uses_two_real_readings() {
+        let store = MetricStore::new(10);
+        assert_eq!(store.net_rate("r", "h", 1_000, 0, 0), None);
+        // 1 MiB за секунду — 8 Мбит/с
+        assert_eq!(store.net_rate("r", "h", 2_000, 1048576, 2 * 1048576), Some((8.0, 16.0)));
+        // Счётчик сбросился (перезагрузка хоста): точку пропускаем, следующая считается от нового показания
+        assert_eq!(store.net_rate("r", "h", 3_000, 10, 10), None);
+        assert_eq!(store.net_rate("r", "h", 4_000, 10, 10), Some((0.0, 0.0)));
+        // Другой хост — свои счётчики
+        assert_eq!(store.net_rate("r", "other", 4_000, 10, 10), None);
+    }
+}
@@ -0,0 +1,249 @@
+// Инвентарь хостов поверх сохранённых серверов: теги, группы, окружение, заметки, jump host и факты
+// (ОС, ядро, uptime из /data/system-info). Импорт и экспорт в YAML вместо таблицы хостов
+use axum::{
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::{BTreeMap, HashMap};
+
+use super::relay_models::SystemInfo;
+use super::servers::ServerView;
+use super::ssh_auth::AuthKind;
+
+// Пустые поля не пишутся: записи без инвентаря выглядят в файле как раньше
+#[derive(Serialize, Deserialize, Clone, Default, PartialEq, Debug)]
+#[serde(default)]
+pub struct Inventory {
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub groups: Vec<String>,
+    // prod, staging, ...
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub env: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub notes: Option<String>,
+    // Имя другого сохранённого сервера
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub jump_host: Option<String>,
+    // Собираются при подключении, руками не задаются
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub facts: Option<Facts>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct Facts {
+    pub os: String,
+    pub kernel: String,
+    pub arch: String,
+    pub uptime: String,
+    pub last_seen: DateTime<Utc>,
+}
+
+impl Facts {
+    pub fn from_system_info(info: SystemInfo) -> Facts {
+        Facts { os: info.distro, kernel: info.kernel, arch: info.arch, uptime: info.uptime, last_seen: Utc::now() }
+    }
+}
+
+// Изменяемая часть: факты остаются прежними
+#[derive(Deserialize)]
+pub struct InventoryUpdate {
+    pub name: String,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub groups: Vec<String>,
+    pub env: Option<String>,
+    pub notes: Option<String>,
+    pub jump_host: Option<String>,
+}
+
+// Строка YAML-файла. Секретов нет: пароли и ключи задаются через /rust/servers/save
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct InventoryHost {
+    pub name: String,
+    pub host: String,
+    pub user: String,
+    #[serde(default = "default_port")]
+    pub port: u16,
+    // Только для новых записей: у существующих способ входа не меняется
+    #[serde(default)]
+    pub auth: AuthKind,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub key_path: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub groups: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub env: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub notes: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub jump_host: Option<String>,
+    // Выгружаются для справки, при импорте игнорируются
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub facts: Option<Facts>,
+}
+
+fn default_port() -> u16 {
+    22
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct InventoryFile {
+    pub hosts: Vec<InventoryHost>,
+}
+
+#[derive(Serialize, Default)]
+pub struct ImportReport {
+    pub created: Vec<String>,
+    pub updated: Vec<String>,
+    pub unchanged: Vec<String>,
+    pub deleted: Vec<String>,
+}
+
+impl InventoryHost {
+    pub fn inventory(&self) -> Inventory {
+        Inventory {
+            tags: self.tags.clone(),
+            groups: self.groups.clone(),
+            env: self.env.clone(),
+            notes: self.notes.clone(),
+            jump_host: self.jump_host.clone(),
+            facts: None,
+        }
+    }
+}
+
+impl From<ServerView> for InventoryHost {
+    fn from(s: ServerView) -> InventoryHost {
+        InventoryHost {
+            name: s.name, host: s.host, user: s.user, port: s.port, auth: s.auth, key_path: s.key_path,
+            tags: s.inventory.tags, groups: s.inventory.groups, env: s.inventory.env, notes: s.inventory.notes,
+            jump_host: s.inventory.jump_host, facts: s.inventory.facts,
+        }
+    }
+}
+
+// Теги, группы и окружение: строчные, без пробелов, без повторов
+fn label(value: &str, what: &str) -> Result<String, String> {
+    let value = value.trim().to_lowercase();
+    if value.is_empty() || !value.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.' | '/')) {
+        return Err(format!("invalid {} {:?}: use letters, digits, - _ . /", what, value));
+    }
+    Ok(value)
+}
+
+fn labels(values: &[String], what: &str) -> Result<Vec<String>, String> {
+    let mut out = values.iter().map(|v| label(v, what)).collect::<Result<Vec<_>, _>>()?;
+    out.sort();
+    out.dedup();
+    Ok(out)
+}
+
+// Нормализует поля, введённые человеком; facts не трогает
+pub fn normalize(inv: &mut Inventory) -> Result<(), String> {
+    inv.tags = labels(&inv.tags, "tag")?;
+    inv.groups = labels(&inv.groups, "group")?;
+    inv.env = inv.env.as_deref().map(str::trim).filter(|e| !e.is_empty()).map(|e| label(e, "env")).transpose()?;
+    inv.notes = inv.notes.as_deref().map(str::trim).filter(|n| !n.is_empty()).map(String::from);
+    inv.jump_host = inv.jump_host.as_deref().map(str::trim).filter(|j| !j.is_empty()).map(String::from);
+    Ok(())
+}
+
+// Каждый jump host существует, и цепочка не замыкается
+pub fn check_jumps(jumps: &HashMap<String, Option<String>>) -> Result<(), String> {
+    for start in jumps.keys() {
+        let mut seen = vec![start.as_str()];
+        let mut current = start.as_str();
+        while let Some(Some(next)) = jumps.get(current) {
+            if !jumps.contains_key(next) {
+                return Err(format!("{}: jump host {} is not a saved server", current, next));
+            }
+            if seen.contains(&next.as_str()) {
+                return Err(format!("jump host loop: {} -> {}", seen.join(" -> "), next));
+            }
+            seen.push(next);
+            current = next;
+        }
+    }
+    Ok(())
+}
+
+#[derive(Deserialize)]
+pub struct InventoryQuery {
+    // Через запятую: нужны все
+    pub tag: Option<String>,
+    pub group: Option<String>,
+    pub env: Option<String>,
+    // Подстрока имени, адреса или заметок
+    pub q: Option<String>,
+}
+
+impl InventoryQuery {
+    fn matches(&self, s: &ServerView) -> bool {
+        let all = |want: &Option<String>, have: &[String]| {
+            want.as_deref().unwrap_or("").split(',').map(|w| w.trim().to_lowercase()).filter(|w| !w.is_empty()).all(|w| have.contains(&w))
+        };
+        let env_ok = self.env.as_deref().filter(|e| !e.is_empty()).is_none_or(|e| s.inventory.env.as_deref() == Some(e.to_lowercase().as_str()));
+        let text_ok = self.q.as_deref().map(|q| q.trim().to_lowercase()).filter(|q| !q.is_empty()).is_none_or(|q| {
+            [Some(&s.name), Some(&s.host), s.inventory.notes.as_ref()].into_iter().flatten().any(|v| v.to_lowercase().contains(&q))
+        });
+        all(&self.tag, &s.inventory.tags) && all(&self.group, &s.inventory.groups) && env_ok && text_ok
+    }
+}
+
+// Отфильтрованный список и сводка по тегам, группам и окружениям всего инвентаря
+pub fn list(servers: Vec<ServerView>, q: &InventoryQuery, locked: bool) -> Json<serde_json::Value> {
+    let mut tags: BTreeMap<&str, usize> = BTreeMap::new();
+    let mut groups: BTreeMap<&str, usize> = BTreeMap::new();
+    let mut envs: BTreeMap<&str, usize> = BTreeMap::new();
+    for s in &servers {
+        s.inventory.tags.iter().for_each(|t| *tags.entry(t).or_default() += 1);
+        s.inventory.groups.iter().for_each(|g| *groups.entry(g).or_default() += 1);
+        if let Some(env) = &s.inventory.env {
+            *envs.entry(env).or_default() += 1;
+        }
+    }
+    let summary = json!({"tags": tags, "groups": groups, "envs": envs});
+    let total = servers.len();
+    let matched: Vec<_> = servers.iter().filter(|s| q.matches(s)).collect();
+    Json(json!({"servers": matched, "matched": matched.len(), "total": total, "summary": summary, "locked": locked}))
+}
+
+pub fn export(servers: Vec<ServerView>, format: Option<&str>) -> Response {
+    let file = InventoryFile { hosts: servers.into_iter().map(InventoryHost::from).collect() };
+    let (body, content_type, ext) = match format.unwrap_or("yaml") {
+        "yaml" | "yml" => match serde_yaml::to_string(&file) {
+            Ok(yaml) => (yaml, "application/yaml", "yaml"),
+            Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, Json(json!({"error": e.to_string()}))).into_response(),
+        },
+        "json" => (json!(file).to_string(), "application/json", "json"),
+        other => return (StatusCode::BAD_REQUEST, Json(json!({"error": format!("unknown format {:?}, expected yaml or json", other)}))).into_response(),
+    };
+    let disposition = format!("attachment; filename=\"inventory-{}.{}\"", Utc::now().format("%Y%m%d"), ext);
+    ([(header::CONTENT_TYPE, content_type.to_string()), (header::CONTENT_DISPOSITION, disposition)], body).into_response()
+}
+
+// JSON — частный случай YAML, так что принимается и он
+pub fn parse(text: &str) -> Result<InventoryFile, String> {
+    let file: InventoryFile = serde_yaml::from_str(text).map_err(|e| format!("invalid inventory: {}", e))?;
+    let mut names = std::collections::HashSet::new();
+    for h in &file.hosts {
+        if h.name.trim().is_empty() || h.host.trim().is_empty() || h.user.trim().is_empty() {
+            return Err(format!("host {:?}: name, host and user are required", h.name));
+        }
+        if !names.insert(h.name.as_str()) {
+            return Err(format!("duplicate host name {}", h.name));
+        }
+    }
+    Ok(file)
+}
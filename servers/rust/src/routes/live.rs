@@ -0,0 +1,228 @@
+// Живые данные дашборда /rust/dashboard/ws: клиент подписывается на темы, а сервер опрашивает relay один раз
+// на SSH-сессию и тему и рассылает результат всем подписчикам — N открытых вкладок не дают N× нагрузки на SSH.
+// Браузер -> сервер: {"type":"subscribe","topic","interval_secs"} и {"type":"unsubscribe","topic"}.
+// Сервер -> браузер: {"type":"ready"|"subscribed"|"unsubscribed"|"data"|"error", ...}
+use axum::{
+    extract::ws::{Message, WebSocket, WebSocketUpgrade},
+    response::Response,
+};
+use chrono::Utc;
+use serde::Deserialize;
+use serde_json::json;
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::{mpsc, Notify};
+use tokio::time::Instant;
+use tracing::{debug, info};
+
+use super::relay::{RelayError, RelayRegistry, RelayTarget};
+use super::relay_models::{ConnectionRow, NetworkSample, PortList, ProcessList, Resources};
+
+pub const TOPICS: &[&str] = &["processes", "ports", "connections", "resources", "network"];
+const DEFAULT_INTERVAL_SECS: u64 = 5;
+const MIN_INTERVAL_SECS: u64 = 1;
+const MAX_INTERVAL_SECS: u64 = 300;
+// Очередь одного клиента: медленный клиент пропускает обновления, а не тормозит остальных
+const QUEUE: usize = 32;
+
+// relay, SSH-сессия, тема
+type FeedKey = (String, Option<String>, &'static str);
+
+struct Subscriber {
+    id: u64,
+    every: Duration,
+    last: Option<Instant>,
+    tx: mpsc::Sender<Arc<str>>,
+}
+
+struct Feed {
+    subscribers: Vec<Subscriber>,
+    // Последние данные: новый подписчик получает их сразу, не дожидаясь опроса
+    last: Option<Arc<str>>,
+    wake: Arc<Notify>,
+}
+
+#[derive(Default)]
+pub struct LiveFeeds {
+    next_id: AtomicU64,
+    feeds: Mutex<HashMap<FeedKey, Feed>>,
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum ClientMsg {
+    Subscribe { topic: String, interval_secs: Option<u64> },
+    Unsubscribe { topic: String },
+}
+
+fn event(value: serde_json::Value) -> Message {
+    Message::Text(value.to_string())
+}
+
+async fn fetch(registry: &RelayRegistry, target: &RelayTarget, topic: &str) -> Result<serde_json::Value, RelayError> {
+    Ok(match topic {
+        "processes" => json!(registry.get_json::<ProcessList>(target, "/data/processes").await?),
+        "ports" => json!(registry.get_json::<PortList>(target, "/data/ports").await?),
+        "connections" => json!(registry.get_json::<Vec<ConnectionRow>>(target, "/data/connections").await?),
+        "resources" => json!(registry.get_json::<Resources>(target, "/data/resources").await?),
+        _ => json!(registry.get_json::<Vec<NetworkSample>>(target, "/data/network").await?),
+    })
+}
+
+impl LiveFeeds {
+    // Сколько клиентов подписано по каждой ленте — для /rust/dashboard/feeds
+    pub fn describe(&self) -> serde_json::Value {
+        let Ok(feeds) = self.feeds.lock() else { return json!({"feeds": []}) };
+        let list: Vec<_> = feeds.iter().map(|((relay, session, topic), feed)| json!({
+            "relay": relay,
+            "session": session,
+            "topic": topic,
+            "subscribers": feed.subscribers.len(),
+            "interval_secs": feed.subscribers.iter().map(|s| s.every.as_secs()).min(),
+        })).collect();
+        json!({"feeds": list})
+    }
+
+    fn subscribe(self: &Arc<Self>, registry: &Arc<RelayRegistry>, target: &RelayTarget, topic: &'static str, id: u64, every: Duration, tx: mpsc::Sender<Arc<str>>) {
+        let key = (target.backend.name.clone(), target.session.clone(), topic);
+        let Ok(mut feeds) = self.feeds.lock() else { return };
+        let start = !feeds.contains_key(&key);
+        let feed = feeds.entry(key.clone()).or_insert_with(|| Feed { subscribers: vec![], last: None, wake: Arc::new(Notify::new()) });
+        feed.subscribers.retain(|s| s.id != id);
+        if let Some(last) = &feed.last {
+            let _ = tx.try_send(last.clone());
+        }
+        feed.subscribers.push(Subscriber { id, every, last: None, tx });
+        // Опрос мог спать с большим интервалом прежнего подписчика
+        feed.wake.notify_one();
+        if start {
+            let wake = feed.wake.clone();
+            let target = RelayTarget { backend: target.backend.clone(), session: target.session.clone(), explicit: target.explicit };
+            tokio::spawn(self.clone().poll(registry.clone(), target, key, wake));
+        }
+    }
+
+    fn unsubscribe(&self, target: &RelayTarget, topic: &'static str, id: u64) {
+        let key = (target.backend.name.clone(), target.session.clone(), topic);
+        if let Ok(mut feeds) = self.feeds.lock() {
+            if let Some(feed) = feeds.get_mut(&key) {
+                feed.subscribers.retain(|s| s.id != id);
+                feed.wake.notify_one();
+            }
+        }
+    }
+
+    // Один опрос на ленту; интервал — наименьший среди подписчиков, каждому данные уходят с его интервалом
+    async fn poll(self: Arc<Self>, registry: Arc<RelayRegistry>, target: RelayTarget, key: FeedKey, wake: Arc<Notify>) {
+        let topic = key.2;
+        info!("live: feed {} {:?} {} started", key.0, key.1, topic);
+        let mut polled: Option<Instant> = None;
+        loop {
+            let every = {
+                let Ok(mut feeds) = self.feeds.lock() else { return };
+                let Some(feed) = feeds.get_mut(&key) else { return };
+                feed.subscribers.retain(|s| !s.tx.is_closed());
+                match feed.subscribers.iter().map(|s| s.every).min() {
+                    Some(every) => every,
+                    None => {
+                        feeds.remove(&key);
+                        info!("live: feed {} {:?} {} stopped", key.0, key.1, topic);
+                        return;
+                    }
+                }
+            };
+            if let Some(at) = polled {
+                tokio::select! {
+                    _ = tokio::time::sleep_until(at + every) => {}
+                    // Подписчики изменились: пересчитать интервал
+                    _ = wake.notified() => continue,
+                }
+            }
+            // Живая подписка — активность, как и запросы вкладки: сессию не закрывает reaper
+            if let Some(id) = &target.session {
+                registry.sessions.touch(id);
+            }
+            let now = Instant::now();
+            polled = Some(now);
+            let (msg, data) = match fetch(&registry, &target, topic).await {
+                Ok(data) => {
+                    let msg: Arc<str> = json!({"type": "data", "topic": topic, "ts": Utc::now().timestamp_millis(), "data": data}).to_string().into();
+                    (msg.clone(), Some(msg))
+                }
+                Err(e) => {
+                    debug!("live: feed {} {:?} {}: {}", key.0, key.1, topic, e.message());
+                    (json!({"type": "error", "topic": topic, "error": e.message()}).to_string().into(), None)
+                }
+            };
+            let Ok(mut feeds) = self.feeds.lock() else { return };
+            let Some(feed) = feeds.get_mut(&key) else { return };
+            for s in feed.subscribers.iter_mut() {
+                // Половина интервала опроса — допуск, чтобы подписчик на 10s не ждал до 15s при опросе раз в 5s
+                if s.last.is_none_or(|l| now.duration_since(l) + every / 2 >= s.every) {
+                    let _ = s.tx.try_send(msg.clone());
+                    s.last = Some(now);
+                }
+            }
+            if data.is_some() {
+                feed.last = data;
+            }
+        }
+    }
+}
+
+pub fn open(feeds: Arc<LiveFeeds>, registry: Arc<RelayRegistry>, target: RelayTarget, ws: WebSocketUpgrade) -> Response {
+    ws.on_upgrade(move |socket| run(socket, feeds, registry, target))
+}
+
+async fn run(mut socket: WebSocket, feeds: Arc<LiveFeeds>, registry: Arc<RelayRegistry>, target: RelayTarget) {
+    let id = feeds.next_id.fetch_add(1, Ordering::Relaxed);
+    let (tx, mut rx) = mpsc::channel::<Arc<str>>(QUEUE);
+    let mut topics: HashSet<&'static str> = HashSet::new();
+    info!("live: client {} on relay {} session {:?}", id, target.backend.name, target.session);
+    let _ = socket.send(event(json!({"type": "ready", "relay": target.backend.name, "session": target.session, "topics": TOPICS}))).await;
+    loop {
+        tokio::select! {
+            msg = socket.recv() => match msg {
+                Some(Ok(Message::Text(text))) => {
+                    let reply = match serde_json::from_str::<ClientMsg>(&text) {
+                        Ok(ClientMsg::Subscribe { topic, interval_secs }) => match TOPICS.iter().find(|t| **t == topic) {
+                            Some(topic) => {
+                                let secs = interval_secs.unwrap_or(DEFAULT_INTERVAL_SECS).clamp(MIN_INTERVAL_SECS, MAX_INTERVAL_SECS);
+                                feeds.subscribe(&registry, &target, topic, id, Duration::from_secs(secs), tx.clone());
+                                topics.insert(topic);
+                                json!({"type": "subscribed", "topic": topic, "interval_secs": secs})
+                            }
+                            None => json!({"type": "error", "message": format!("unknown topic {}", topic), "topics": TOPICS}),
+                        },
+                        Ok(ClientMsg::Unsubscribe { topic }) => match topics.iter().copied().find(|t| *t == topic) {
+                            Some(topic) => {
+                                feeds.unsubscribe(&target, topic, id);
+                                topics.remove(topic);
+                                json!({"type": "unsubscribed", "topic": topic})
+                            }
+                            None => json!({"type": "error", "message": format!("not subscribed to {}", topic)}),
+                        },
+                        Err(e) => json!({"type": "error", "message": format!("bad message: {}", e)}),
+                    };
+                    if socket.send(event(reply)).await.is_err() {
+                        break;
+                    }
+                }
+                Some(Ok(Message::Close(_))) | None | Some(Err(_)) => break,
+                // ping/pong axum отвечает сам
+                Some(Ok(_)) => {}
+            },
+            Some(msg) = rx.recv() => {
+                if socket.send(Message::Text(msg.to_string())).await.is_err() {
+                    break;
+                }
+            }
+        }
+    }
+    for topic in topics {
+        feeds.unsubscribe(&target, topic, id);
+    }
+    info!("live: client {} closed", id);
+}
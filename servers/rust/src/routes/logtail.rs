@@ -0,0 +1,511 @@
+// Слежение за логами GET /rust/logs/tail?file=... или ?unit=...: на хосте сессии запускается tail -F или
+// journalctl -f, новые строки уходят событиями SSE (ready, line, gap, end). Процесс один на SSH-сессию и источник,
+// сколько бы клиентов ни смотрело; последние строки держатся в памяти, и переподключившийся клиент
+// (Last-Event-ID) получает пропущенное. Фильтры include/exclude у каждого клиента свои
+use axum::response::{sse::{Event, KeepAlive, Sse}, IntoResponse, Response};
+use chrono::{DateTime, Utc};
+use futures_util::stream;
+use once_cell::sync::Lazy;
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::{HashMap, VecDeque};
+use std::convert::Infallible;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::{broadcast, mpsc, oneshot};
+use tokio::time::Instant;
+use tracing::{debug, info};
+
+use crate::config::LogsConfig;
+use super::audit::AuditOutcome;
+use super::exec_stream::{self, ExecEvent};
+use super::policy::{Effective, PolicyDenied};
+use super::relay::{RelayError, RelayRegistry, RelayTarget};
+
+const DEFAULT_BACKLOG: usize = 100;
+// Очередь рассылки: отставший клиент получает gap, а не тормозит остальных
+const QUEUE: usize = 1024;
+const REGEX_SIZE_LIMIT: usize = 1 << 20;
+const CHECK_EVERY: Duration = Duration::from_secs(5);
+
+// Подсказка для подсветки: первое совпадение сверху вниз
+static SEVERITY: Lazy<Vec<(&'static str, Regex)>> = Lazy::new(|| {
+    [
+        ("error", r"(?i)\b(emerg|alert|crit|critical|fatal|panic|err|error|failed|failure)\b"),
+        ("warning", r"(?i)\b(warn|warning)\b"),
+        ("info", r"(?i)\b(info|notice)\b"),
+        ("debug", r"(?i)\b(debug|trace)\b"),
+    ]
+    .into_iter()
+    .map(|(name, re)| (name, Regex::new(re).expect("severity regex")))
+    .collect()
+});
+
+#[derive(Deserialize)]
+pub struct TailQuery {
+    // Абсолютный путь к файлу или имя unit'а systemd — что-то одно
+    pub file: Option<String>,
+    pub unit: Option<String>,
+    // Строка проходит, если совпала с include и не совпала с exclude
+    pub include: Option<String>,
+    pub exclude: Option<String>,
+    // Сколько последних строк прислать при подключении, не больше [logs].backlog_lines
+    pub backlog: Option<usize>,
+}
+
+// Что читать: файл или unit
+pub enum TailTarget {
+    File(String),
+    Unit(String),
+}
+
+// Что и чем читать на хосте
+pub struct TailSource {
+    // file:/var/log/syslog или unit:nginx.service
+    pub name: String,
+    pub command: String,
+}
+
+// Отказ до запуска: политика или ошибка relay при проверке пути
+pub enum Refusal {
+    Denied(PolicyDenied),
+    Failed(RelayError),
+}
+
+impl Refusal {
+    pub fn message(&self) -> String {
+        match self {
+            Refusal::Denied(d) => d.error.clone(),
+            Refusal::Failed(e) => e.message(),
+        }
+    }
+
+    pub fn outcome(&self) -> AuditOutcome {
+        match self {
+            Refusal::Denied(_) => AuditOutcome::Denied,
+            Refusal::Failed(_) => AuditOutcome::Error,
+        }
+    }
+}
+
+impl IntoResponse for Refusal {
+    fn into_response(self) -> Response {
+        match self {
+            Refusal::Denied(d) => d.into_response(),
+            Refusal::Failed(e) => e.into_response(),
+        }
+    }
+}
+
+fn quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', r"'\''"))
+}
+
+impl TailQuery {
+    pub fn target(&self) -> Result<TailTarget, String> {
+        let file = self.file.as_deref().map(str::trim).filter(|s| !s.is_empty());
+        let unit = self.unit.as_deref().map(str::trim).filter(|s| !s.is_empty());
+        match (file, unit) {
+            (Some(file), None) => {
+                if !file.starts_with('/') || file.chars().any(char::is_control) {
+                    return Err(format!("file must be an absolute path, got {:?}", file));
+                }
+                Ok(TailTarget::File(file.to_string()))
+            }
+            (None, Some(unit)) => {
+                if !unit.chars().all(|c| c.is_ascii_alphanumeric() || "@._:-".contains(c)) || unit.starts_with('-') {
+                    return Err(format!("invalid unit name {:?}", unit));
+                }
+                Ok(TailTarget::Unit(unit.to_string()))
+            }
+            (Some(_), Some(_)) => Err("pass either file or unit, not both".into()),
+            (None, None) => Err("file or unit is required".into()),
+        }
+    }
+}
+
+impl TailTarget {
+    pub fn source(&self, config: &LogsConfig) -> TailSource {
+        match self {
+            TailTarget::File(file) => TailSource {
+                name: format!("file:{}", file),
+                command: format!("tail -n {} -F -- {}", config.backlog_lines, quote(file)),
+            },
+            TailTarget::Unit(unit) => TailSource {
+                name: format!("unit:{}", unit),
+                command: format!("journalctl -f -n {} -o short-iso --no-pager -u {}", config.backlog_lines, unit),
+            },
+        }
+    }
+
+    // Файл — только внутри каталогов files политики (как /rust/files/*), unit — только из units.
+    // Путь раскрывается realpath на хосте, tail получает уже канонический: симлинк наружу не пройдёт
+    pub async fn authorize(self, registry: &RelayRegistry, target: &RelayTarget, policy: &Effective) -> Result<TailTarget, Refusal> {
+        let file = match self {
+            TailTarget::Unit(unit) => return policy.check_unit(&unit).map(|_| TailTarget::Unit(unit)).map_err(Refusal::Denied),
+            TailTarget::File(file) => file,
+        };
+        // Относительные каталоги политики — от домашнего, как у SFTP
+        let roots: Vec<String> = if policy.policy.files.is_empty() {
+            vec!["\"$HOME\"".into()]
+        } else {
+            policy.policy.files.iter()
+                .map(|r| if r.starts_with('/') { quote(r) } else { format!("\"$HOME\"/{}", quote(r)) })
+                .collect()
+        };
+        let command = format!("realpath -m -- {} {}", quote(&file), roots.join(" "));
+        let output = registry.post_text(target, "/ssh/execute", &json!({"command": command})).await.map_err(Refusal::Failed)?;
+        let mut lines = output.lines().map(str::trim).filter(|l| l.starts_with('/'));
+        let Some(real) = lines.next().map(str::to_string) else {
+            return Err(Refusal::Failed(RelayError::InvalidResponse(format!("realpath {}: unexpected output", file))));
+        };
+        let roots: Vec<String> = lines.map(str::to_string).collect();
+        policy.check_path(&real, &roots).map_err(Refusal::Denied)?;
+        Ok(TailTarget::File(real))
+    }
+}
+
+struct Filter {
+    include: Option<Regex>,
+    exclude: Option<Regex>,
+}
+
+impl Filter {
+    fn parse(q: &TailQuery) -> Result<Filter, String> {
+        let build = |name: &str, pattern: &Option<String>| -> Result<Option<Regex>, String> {
+            match pattern.as_deref().filter(|p| !p.is_empty()) {
+                Some(p) => RegexBuilder::new(p)
+                    .size_limit(REGEX_SIZE_LIMIT)
+                    .build()
+                    .map(Some)
+                    .map_err(|e| format!("invalid {} regex: {}", name, e)),
+                None => Ok(None),
+            }
+        };
+        Ok(Filter { include: build("include", &q.include)?, exclude: build("exclude", &q.exclude)? })
+    }
+
+    // Сообщения самого tail/journalctl (stderr) не фильтруются
+    fn pass(&self, line: &LogLine) -> bool {
+        line.stream != "stdout"
+            || (self.include.as_ref().is_none_or(|re| re.is_match(&line.line))
+                && !self.exclude.as_ref().is_some_and(|re| re.is_match(&line.line)))
+    }
+}
+
+#[derive(Serialize)]
+struct LogLine {
+    seq: u64,
+    ts: i64,
+    stream: &'static str,
+    line: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    severity: Option<&'static str>,
+}
+
+#[derive(Clone)]
+enum TailEvent {
+    Line(Arc<LogLine>),
+    // Процесс на хосте завершился: {"code", "signal"} или {"error"}
+    End(Arc<serde_json::Value>),
+}
+
+// relay, SSH-сессия, источник
+type TailKey = (String, Option<String>, String);
+
+struct Tail {
+    // Номер запуска: seq из Last-Event-ID прежнего запуска не относится к этому
+    run: u64,
+    command: String,
+    started_at: DateTime<Utc>,
+    backlog: VecDeque<Arc<LogLine>>,
+    next_seq: u64,
+    tx: broadcast::Sender<TailEvent>,
+    clients: usize,
+    // С какого момента нет клиентов
+    idle_since: Option<Instant>,
+    // Пока жив — процесс на хосте работает; удаление Tail из таблицы его останавливает
+    _cancel: oneshot::Sender<()>,
+}
+
+#[derive(Default)]
+pub struct LogTails {
+    next_run: AtomicU64,
+    tails: Mutex<HashMap<TailKey, Tail>>,
+}
+
+// Снимает клиента с учёта, когда SSE-ответ закрыт
+struct Client {
+    tails: Arc<LogTails>,
+    key: TailKey,
+    run: u64,
+}
+
+impl Drop for Client {
+    fn drop(&mut self) {
+        if let Ok(mut tails) = self.tails.tails.lock() {
+            if let Some(tail) = tails.get_mut(&self.key).filter(|t| t.run == self.run) {
+                tail.clients = tail.clients.saturating_sub(1);
+                if tail.clients == 0 {
+                    tail.idle_since = Some(Instant::now());
+                }
+            }
+        }
+    }
+}
+
+struct ClientState {
+    replay: VecDeque<Arc<LogLine>>,
+    rx: broadcast::Receiver<TailEvent>,
+    filter: Filter,
+    // Последний seq, который клиент видел или пропустил фильтром: живые строки до него — дубли из replay
+    last_seq: u64,
+    done: bool,
+    client: Client,
+}
+
+fn line_event(run: u64, line: &LogLine) -> Event {
+    Event::default()
+        .event("line")
+        .id(format!("{}:{}", run, line.seq))
+        .data(serde_json::to_string(line).unwrap_or_default())
+}
+
+// "run:seq" из Last-Event-ID
+fn parse_last_id(id: Option<&str>) -> Option<(u64, u64)> {
+    let (run, seq) = id?.split_once(':')?;
+    Some((run.parse().ok()?, seq.parse().ok()?))
+}
+
+fn severity(line: &str) -> Option<&'static str> {
+    SEVERITY.iter().find(|(_, re)| re.is_match(line)).map(|(name, _)| *name)
+}
+
+fn truncate(mut line: String, max: usize) -> String {
+    if line.len() > max {
+        let mut end = max;
+        while !line.is_char_boundary(end) {
+            end -= 1;
+        }
+        line.truncate(end);
+        line.push('…');
+    }
+    line
+}
+
+impl LogTails {
+    // GET /rust/logs/tails
+    pub fn describe(&self) -> serde_json::Value {
+        let Ok(tails) = self.tails.lock() else { return json!({"tails": []}) };
+        let list: Vec<_> = tails.iter().map(|((relay, session, source), tail)| json!({
+            "relay": relay,
+            "session": session,
+            "source": source,
+            "command": tail.command,
+            "started_at": tail.started_at,
+            "clients": tail.clients,
+            "lines": tail.next_seq - 1,
+            "buffered": tail.backlog.len(),
+        })).collect();
+        json!({"tails": list})
+    }
+
+    // Подключает клиента к уже идущему tail; фильтр возвращается, если tail для ключа нет
+    fn attach(self: &Arc<Self>, tails: &mut HashMap<TailKey, Tail>, key: &TailKey, last: Option<(u64, u64)>, backlog: usize, filter: Filter) -> Result<(ClientState, serde_json::Value), Filter> {
+        let Some(tail) = tails.get_mut(key) else { return Err(filter) };
+        let (replay, missed): (VecDeque<_>, u64) = match last {
+            // Клиент отстал больше, чем держит буфер: часть строк уже не вернуть
+            Some((run, seq)) if run == tail.run => {
+                let first = tail.backlog.front().map_or(tail.next_seq, |l| l.seq);
+                (tail.backlog.iter().filter(|l| l.seq > seq).cloned().collect(), first.saturating_sub(seq + 1))
+            }
+            _ => (tail.backlog.iter().skip(tail.backlog.len().saturating_sub(backlog)).cloned().collect(), 0),
+        };
+        tail.clients += 1;
+        tail.idle_since = None;
+        let ready = json!({
+            "source": key.2,
+            "command": tail.command,
+            "relay": key.0,
+            "session": key.1,
+            "started_at": tail.started_at,
+            "replayed": replay.len(),
+            "missed": missed,
+            "clients": tail.clients,
+        });
+        let state = ClientState {
+            replay,
+            rx: tail.tx.subscribe(),
+            filter,
+            last_seq: tail.next_seq - 1,
+            done: false,
+            client: Client { tails: self.clone(), key: key.clone(), run: tail.run },
+        };
+        Ok((state, ready))
+    }
+
+    // Запускает процесс на хосте и сразу подключает клиента — под той же блокировкой, чтобы мгновенно
+    // завершившийся процесс не убрал tail раньше
+    async fn start(self: &Arc<Self>, registry: &Arc<RelayRegistry>, target: &RelayTarget, key: &TailKey, command: &str, config: &LogsConfig, client: (Option<(u64, u64)>, usize, Filter)) -> Result<(ClientState, serde_json::Value), RelayError> {
+        let (last, backlog, filter) = client;
+        let (cancel_tx, cancel_rx) = oneshot::channel();
+        let rx = exec_stream::spawn(registry, target, command, cancel_rx).await?;
+        let run = self.next_run.fetch_add(1, Ordering::Relaxed) + 1;
+        let mut tails = self.tails.lock().map_err(|_| RelayError::Transport("log tails are unavailable".into()))?;
+        // Пока процесс запускался, его успел запустить другой клиент: этот останавливается вместе с cancel_tx
+        if !tails.contains_key(key) {
+            tails.insert(key.clone(), Tail {
+                run,
+                command: command.to_string(),
+                started_at: Utc::now(),
+                backlog: VecDeque::new(),
+                next_seq: 1,
+                tx: broadcast::channel(QUEUE).0,
+                clients: 0,
+                idle_since: None,
+                _cancel: cancel_tx,
+            });
+            info!("logs: tail {} {:?} {} started ({})", key.0, key.1, key.2, command);
+            let target = RelayTarget { backend: target.backend.clone(), session: target.session.clone(), explicit: target.explicit };
+            tokio::spawn(self.clone().pump(registry.clone(), target, key.clone(), run, rx, config.clone()));
+        }
+        self.attach(&mut tails, key, last, backlog, filter).map_err(|_| RelayError::Transport("log tail is gone".into()))
+    }
+
+    fn push(&self, key: &TailKey, run: u64, stream: &'static str, line: String, config: &LogsConfig) {
+        let Ok(mut tails) = self.tails.lock() else { return };
+        let Some(tail) = tails.get_mut(key).filter(|t| t.run == run) else { return };
+        let line = truncate(line, config.max_line_bytes);
+        let severity = if stream == "stdout" { severity(&line) } else { Some("warning") };
+        let line = Arc::new(LogLine { seq: tail.next_seq, ts: Utc::now().timestamp_millis(), stream, line, severity });
+        tail.next_seq += 1;
+        tail.backlog.push_back(line.clone());
+        while tail.backlog.len() > config.backlog_lines {
+            tail.backlog.pop_front();
+        }
+        // Буфер и рассылка под одной блокировкой: attach не пропустит и не задвоит строку
+        let _ = tail.tx.send(TailEvent::Line(line));
+    }
+
+    fn finish(&self, key: &TailKey, run: u64, end: serde_json::Value) {
+        let Ok(mut tails) = self.tails.lock() else { return };
+        if tails.get(key).is_some_and(|t| t.run == run) {
+            if let Some(tail) = tails.remove(key) {
+                let _ = tail.tx.send(TailEvent::End(Arc::new(end)));
+            }
+        }
+    }
+
+    // Вывод процесса -> строки; пока есть клиенты, сессия считается активной, без клиентов дольше linger_secs tail останавливается
+    async fn pump(self: Arc<Self>, registry: Arc<RelayRegistry>, target: RelayTarget, key: TailKey, run: u64, mut rx: mpsc::Receiver<ExecEvent>, config: LogsConfig) {
+        let linger = Duration::from_secs(config.linger_secs);
+        // Незавершённая строка stdout и stderr: вывод приходит кусками
+        let mut partial: HashMap<&'static str, String> = HashMap::new();
+        let mut check = tokio::time::interval(CHECK_EVERY);
+        loop {
+            tokio::select! {
+                event = rx.recv() => match event {
+                    Some(ExecEvent::Output { stream, data }) => {
+                        let buf = partial.entry(stream).or_default();
+                        buf.push_str(&data);
+                        while let Some(pos) = buf.find('\n') {
+                            let line: String = buf.drain(..=pos).collect();
+                            self.push(&key, run, stream, line.trim_end_matches(['\n', '\r']).to_string(), &config);
+                        }
+                        // Строка без конца длиннее лимита не ждёт перевода строки
+                        if buf.len() > config.max_line_bytes {
+                            let line = std::mem::take(buf);
+                            self.push(&key, run, stream, line, &config);
+                        }
+                    }
+                    Some(ExecEvent::Exit { code, signal, cancelled, .. }) => {
+                        for (stream, rest) in partial.drain() {
+                            if !rest.is_empty() {
+                                self.push(&key, run, stream, rest, &config);
+                            }
+                        }
+                        info!("logs: tail {} {:?} {} exited (code {:?}, cancelled {})", key.0, key.1, key.2, code, cancelled);
+                        self.finish(&key, run, json!({"code": code, "signal": signal, "cancelled": cancelled}));
+                        return;
+                    }
+                    Some(ExecEvent::Error(e)) => {
+                        debug!("logs: tail {} {:?} {}: {}", key.0, key.1, key.2, e);
+                        self.finish(&key, run, json!({"error": e}));
+                        return;
+                    }
+                    None => {
+                        self.finish(&key, run, json!({"error": "output stream closed"}));
+                        return;
+                    }
+                },
+                _ = check.tick() => {
+                    let Ok(mut tails) = self.tails.lock() else { return };
+                    let Some(tail) = tails.get(&key).filter(|t| t.run == run) else { return };
+                    if tail.clients > 0 {
+                        if let Some(id) = &target.session {
+                            registry.sessions.touch(id);
+                        }
+                    } else if tail.idle_since.is_some_and(|at| at.elapsed() >= linger) {
+                        tails.remove(&key);
+                        info!("logs: tail {} {:?} {} stopped, no clients", key.0, key.1, key.2);
+                        return;
+                    }
+                }
+            }
+        }
+    }
+}
+
+// Ошибки запроса и запуска — обычным JSON до начала SSE
+pub async fn open(tails: Arc<LogTails>, registry: Arc<RelayRegistry>, target: RelayTarget, config: LogsConfig, q: TailQuery, source: TailSource, last_event_id: Option<String>) -> Result<Response, RelayError> {
+    let filter = Filter::parse(&q).map_err(RelayError::InvalidQuery)?;
+    let key = (target.backend.name.clone(), target.session.clone(), source.name);
+    let backlog = q.backlog.unwrap_or(DEFAULT_BACKLOG).min(config.backlog_lines);
+    let last = parse_last_id(last_event_id.as_deref());
+    let attached = match tails.tails.lock() {
+        Ok(mut map) => tails.attach(&mut map, &key, last, backlog, filter),
+        Err(_) => Err(filter),
+    };
+    let (state, ready) = match attached {
+        Ok(attached) => attached,
+        Err(filter) => tails.start(&registry, &target, &key, &source.command, &config, (last, backlog, filter)).await?,
+    };
+    let run = state.client.run;
+    let first = Event::default().event("ready").data(ready.to_string());
+    let lines = stream::unfold(state, move |mut st| async move {
+        loop {
+            if st.done {
+                return None;
+            }
+            if let Some(line) = st.replay.pop_front() {
+                if st.filter.pass(&line) {
+                    return Some((Ok::<_, Infallible>(line_event(run, &line)), st));
+                }
+                continue;
+            }
+            match st.rx.recv().await {
+                Ok(TailEvent::Line(line)) => {
+                    if line.seq <= st.last_seq {
+                        continue;
+                    }
+                    st.last_seq = line.seq;
+                    if st.filter.pass(&line) {
+                        return Some((Ok(line_event(run, &line)), st));
+                    }
+                }
+                Ok(TailEvent::End(end)) => {
+                    st.done = true;
+                    return Some((Ok(Event::default().event("end").data(end.to_string())), st));
+                }
+                Err(broadcast::error::RecvError::Lagged(missed)) => {
+                    return Some((Ok(Event::default().event("gap").data(json!({"missed": missed}).to_string())), st));
+                }
+                Err(broadcast::error::RecvError::Closed) => return None,
+            }
+        }
+    });
+    let events = futures_util::StreamExt::chain(stream::iter([Ok(first)]), lines);
+    Ok(Sse::new(events).keep_alive(KeepAlive::default()).into_response())
+}
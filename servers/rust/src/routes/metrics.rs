@@ -0,0 +1,182 @@
+// Метрики в текстовом формате Prometheus (/metrics): HTTP-запросы по маршрутам, запросы и ошибки relay,
+// время ответа upstream у /rust/ajax/proxy и последние значения метрик хостов из поллера
+use axum::{
+    extract::{MatchedPath, Request},
+    http::header,
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use once_cell::sync::Lazy;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use super::history::MetricStore;
+
+// Границы корзин гистограмм, секунды
+const BUCKETS: &[f64] = &[0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0];
+
+// Метрика поллера -> имя в Prometheus и описание
+const HOST_GAUGES: &[(&str, &str, &str)] = &[
+    ("cpu", "rust_server_host_cpu_percent", "CPU usage of the host, percent"),
+    ("load", "rust_server_host_load1", "1-minute load average of the host"),
+    ("gpu", "rust_server_host_gpu_percent", "GPU usage of the host, percent"),
+    ("ram_used", "rust_server_host_memory_used_gigabytes", "Memory used on the host, GB"),
+    ("ram_pct", "rust_server_host_memory_used_percent", "Memory used on the host, percent"),
+    ("disk", "rust_server_host_disk_used_percent", "Usage of the root filesystem of the host, percent"),
+    ("net_rx", "rust_server_host_network_receive_mbps", "Network receive rate of the host, Mbit/s"),
+    ("net_tx", "rust_server_host_network_transmit_mbps", "Network transmit rate of the host, Mbit/s"),
+];
+
+#[derive(Clone)]
+struct Histogram {
+    // Не накопительные: сумма считается при выводе
+    counts: Vec<u64>,
+    sum: f64,
+    count: u64,
+}
+
+impl Histogram {
+    fn new() -> Histogram {
+        Histogram { counts: vec![0; BUCKETS.len()], sum: 0.0, count: 0 }
+    }
+
+    fn observe(&mut self, secs: f64) {
+        if let Some(i) = BUCKETS.iter().position(|b| secs <= *b) {
+            self.counts[i] += 1;
+        }
+        self.sum += secs;
+        self.count += 1;
+    }
+}
+
+#[derive(Default)]
+struct Registry {
+    // method, route, status
+    requests: BTreeMap<(String, String, u16), u64>,
+    // method, route
+    latency: BTreeMap<(String, String), Histogram>,
+    // relay, path
+    relay_requests: BTreeMap<(String, String), u64>,
+    // relay, path, kind
+    relay_errors: BTreeMap<(String, String, &'static str), u64>,
+    // method, outcome ("2xx", "4xx", ..., "error")
+    ajax: BTreeMap<(String, String), Histogram>,
+}
+
+static REGISTRY: Lazy<Mutex<Registry>> = Lazy::new(Default::default);
+
+// Слой рядом с TraceLayer: маршрут берётся из MatchedPath, чтобы /rust/x?id=1 и /rust/x?id=2 были одной серией
+pub async fn track(req: Request, next: Next) -> Response {
+    let started = Instant::now();
+    let method = req.method().to_string();
+    let route = req.extensions().get::<MatchedPath>()
+        .map(|p| p.as_str().to_string())
+        .unwrap_or_else(|| "unmatched".to_string());
+    let response = next.run(req).await;
+    let status = response.status().as_u16();
+    if let Ok(mut r) = REGISTRY.lock() {
+        *r.requests.entry((method.clone(), route.clone(), status)).or_default() += 1;
+        r.latency.entry((method, route)).or_insert_with(Histogram::new).observe(started.elapsed().as_secs_f64());
+    }
+    response
+}
+
+pub fn relay_request(relay: &str, path: &str) {
+    if let Ok(mut r) = REGISTRY.lock() {
+        *r.relay_requests.entry((relay.to_string(), path.to_string())).or_default() += 1;
+    }
+}
+
+pub fn relay_error(relay: &str, path: &str, kind: &'static str) {
+    if let Ok(mut r) = REGISTRY.lock() {
+        *r.relay_errors.entry((relay.to_string(), path.to_string(), kind)).or_default() += 1;
+    }
+}
+
+// status: None — upstream не ответил
+pub fn ajax_upstream(method: &str, status: Option<u16>, elapsed: Duration) {
+    let outcome = status.map(|s| format!("{}xx", s / 100)).unwrap_or_else(|| "error".to_string());
+    if let Ok(mut r) = REGISTRY.lock() {
+        r.ajax.entry((method.to_string(), outcome)).or_insert_with(Histogram::new).observe(elapsed.as_secs_f64());
+    }
+}
+
+fn escape(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+fn labels(pairs: &[(&str, &str)]) -> String {
+    let inner: Vec<_> = pairs.iter().map(|(k, v)| format!("{}=\"{}\"", k, escape(v))).collect();
+    format!("{{{}}}", inner.join(","))
+}
+
+fn family(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+}
+
+fn histogram(out: &mut String, name: &str, pairs: &[(&str, &str)], h: &Histogram) {
+    let mut cumulative = 0;
+    for (bound, count) in BUCKETS.iter().zip(&h.counts) {
+        cumulative += count;
+        let le = bound.to_string();
+        let mut with_le = pairs.to_vec();
+        with_le.push(("le", &le));
+        let _ = writeln!(out, "{}_bucket{} {}", name, labels(&with_le), cumulative);
+    }
+    let mut with_le = pairs.to_vec();
+    with_le.push(("le", "+Inf"));
+    let _ = writeln!(out, "{}_bucket{} {}", name, labels(&with_le), h.count);
+    let _ = writeln!(out, "{}_sum{} {}", name, labels(pairs), h.sum);
+    let _ = writeln!(out, "{}_count{} {}", name, labels(pairs), h.count);
+}
+
+pub fn render(store: &MetricStore) -> String {
+    let mut out = String::new();
+    family(&mut out, "rust_server_build_info", "gauge", "rust-server version");
+    let _ = writeln!(out, "rust_server_build_info{} 1", labels(&[("version", env!("CARGO_PKG_VERSION"))]));
+    if let Ok(r) = REGISTRY.lock() {
+        family(&mut out, "rust_server_http_requests_total", "counter", "HTTP requests by route and status");
+        for ((method, route, status), n) in &r.requests {
+            let status = status.to_string();
+            let _ = writeln!(out, "rust_server_http_requests_total{} {}", labels(&[("method", method), ("route", route), ("status", &status)]), n);
+        }
+        family(&mut out, "rust_server_http_request_duration_seconds", "histogram", "HTTP request latency by route");
+        for ((method, route), h) in &r.latency {
+            histogram(&mut out, "rust_server_http_request_duration_seconds", &[("method", method), ("route", route)], h);
+        }
+        family(&mut out, "rust_server_relay_requests_total", "counter", "Requests to relays by relay endpoint");
+        for ((relay, path), n) in &r.relay_requests {
+            let _ = writeln!(out, "rust_server_relay_requests_total{} {}", labels(&[("relay", relay), ("path", path)]), n);
+        }
+        family(&mut out, "rust_server_relay_errors_total", "counter", "Failed relay requests by error kind");
+        for ((relay, path, kind), n) in &r.relay_errors {
+            let _ = writeln!(out, "rust_server_relay_errors_total{} {}", labels(&[("relay", relay), ("path", path), ("kind", kind)]), n);
+        }
+        family(&mut out, "rust_server_ajax_upstream_duration_seconds", "histogram", "Upstream response time of /rust/ajax/proxy");
+        for ((method, outcome), h) in &r.ajax {
+            histogram(&mut out, "rust_server_ajax_upstream_duration_seconds", &[("method", method), ("outcome", outcome)], h);
+        }
+    }
+    // Последние точки поллера; хост, пропавший из опроса, выдаёт себя старым last_seen
+    let hosts = store.hosts();
+    family(&mut out, "rust_server_host_last_seen_timestamp_seconds", "gauge", "Time of the last successful poll of the host");
+    for h in &hosts {
+        let _ = writeln!(out, "rust_server_host_last_seen_timestamp_seconds{} {}", labels(&[("host", &h.host), ("relay", &h.relay)]), h.last_seen.timestamp());
+    }
+    for (metric, name, help) in HOST_GAUGES {
+        family(&mut out, name, "gauge", help);
+        for h in &hosts {
+            if let Some(sample) = store.latest(&h.host, metric) {
+                let _ = writeln!(out, "{}{} {}", name, labels(&[("host", &h.host), ("relay", &h.relay)]), sample.value);
+            }
+        }
+    }
+    out
+}
+
+pub fn response(store: &MetricStore) -> Response {
+    ([(header::CONTENT_TYPE, "text/plain; version=0.0.4; charset=utf-8")], render(store)).into_response()
+}
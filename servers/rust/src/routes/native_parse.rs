@@ -0,0 +1,311 @@
+// Разбор вывода ps/ss/df/who/systemctl для встроенного SSH-клиента; повторяет parse* из servers/go/relay.go
+use super::relay_models::{ConnectionRow, DiskUsageRow, PortRow, ProcessRow, ServiceRow, UserRow};
+
+fn truncate(s: &str, max: usize) -> String {
+    s.chars().take(max).collect()
+}
+
+// users:(("sshd",pid=812,fd=3)) -> ("812", "sshd")
+fn process_info(field: &str) -> (String, String) {
+    let pid = field.find("pid=")
+        .map(|i| field[i + 4..].chars().take_while(|c| c.is_ascii_digit()).collect())
+        .unwrap_or_default();
+    let name = field.split('"').nth(1).unwrap_or_default().to_string();
+    (pid, name)
+}
+
+fn base_name(path: &str) -> String {
+    path.rsplit('/').next().unwrap_or(path).to_string()
+}
+
+// ps -eo pid,user,comm,pcpu,rss
+pub fn parse_ps(out: &str) -> Vec<ProcessRow> {
+    out.lines().skip(1).filter_map(|ln| {
+        let f: Vec<&str> = ln.split_whitespace().collect();
+        if f.len() < 5 {
+            return None;
+        }
+        let cpu = f[3].parse::<f64>().ok()
+            .filter(|c| (0.0..=999.0).contains(c))
+            .map(|c| format!("{:.1}", c))
+            .unwrap_or_else(|| "0.0".into());
+        // rss в КБ, отдаём МБ
+        let rss = f[4].parse::<i64>().ok()
+            .filter(|r| (0..=1024 * 1024 * 1024).contains(r))
+            .map(|r| (r / 1024).to_string())
+            .unwrap_or_else(|| "0".into());
+        let name = truncate(f[2], 30);
+        Some(ProcessRow { pid: truncate(f[0], 10), user: truncate(f[1], 20), cmd: name.clone(), name, cpu, rss })
+    }).collect()
+}
+
+// ss -lntup: Netid State Recv-Q Send-Q Local:Port Peer:Port Process
+pub fn parse_listen_ports(out: &str) -> Vec<PortRow> {
+    out.lines().map(str::trim)
+        .filter(|ln| !ln.is_empty() && !ln.starts_with("Netid") && !ln.starts_with("Proto"))
+        .filter_map(|ln| {
+            let f: Vec<&str> = ln.split_whitespace().collect();
+            if f.len() < 5 {
+                return None;
+            }
+            let (pid, proc) = if f.len() > 5 { process_info(&f[5..].join(" ")) } else { Default::default() };
+            Some(PortRow { proto: f[0].to_uppercase(), local: f[4].to_string(), pid, proc })
+        })
+        .collect()
+}
+
+// ss -tuanp
+pub fn parse_connections(out: &str) -> Vec<ConnectionRow> {
+    out.lines().skip(1).filter_map(|ln| {
+        let f: Vec<&str> = ln.split_whitespace().collect();
+        if f.len() < 6 {
+            return None;
+        }
+        let (pid, proc) = f.get(6).map(|u| process_info(u)).unwrap_or_default();
+        Some(ConnectionRow {
+            proto: f[0].to_uppercase(),
+            state: f[1].to_string(),
+            local: f[4].to_string(),
+            peer: f[5].to_string(),
+            pid,
+            proc,
+        })
+    }).collect()
+}
+
+// Уникальные адреса собеседников из ss -tuan без loopback и wildcard
+pub fn parse_client_ips(out: &str) -> Vec<String> {
+    let mut ips: Vec<String> = out.lines().skip(1)
+        .filter_map(|ln| ln.split_whitespace().nth(5))
+        .filter_map(|peer| peer.rsplit_once(':').map(|(ip, _)| ip.trim_matches(|c| c == '[' || c == ']').to_string()))
+        .filter(|ip| !ip.is_empty() && ip != "*" && ip != "::1" && ip != "::" && ip != "0.0.0.0" && !ip.starts_with("127."))
+        .collect();
+    ips.sort();
+    ips.dedup();
+    ips
+}
+
+const SERVICE_NOISE: &[&str] = &["System has", "Failed to", "systemd as init", "Can't operate", "bus:", "Host is down"];
+
+// Вывод зависит от того, какая из команд servicesHandler сработала
+pub fn parse_services(out: &str, cmd: &str) -> Vec<ServiceRow> {
+    let row = |name: String, load: &str, active: &str, sub: &str, description: String| ServiceRow {
+        name, load: load.into(), active: active.into(), sub: sub.into(), description,
+    };
+    let mut res = vec![];
+    for ln in out.lines().map(str::trim).filter(|l| !l.is_empty()) {
+        let f: Vec<&str> = ln.split_whitespace().collect();
+        if cmd.contains("systemctl") {
+            if ln.starts_with("UNIT") || ln.starts_with('●') || ln.contains("LOAD") || SERVICE_NOISE.iter().any(|n| ln.contains(n)) {
+                continue;
+            }
+            if f.len() < 4 || !f[0].contains("service") {
+                continue;
+            }
+            res.push(row(f[0].into(), f[1], f[2], f[3], f[4..].join(" ")));
+        } else if cmd.contains("/etc/init.d") && cmd.contains("status") {
+            if ln.contains("running") || ln.contains("active") {
+                res.push(row(base_name(f[0]), "loaded", "active", "running", "SysV init service".into()));
+            }
+        } else if cmd.contains("ls /etc/init.d") {
+            if !ln.contains("README") && !ln.contains("skeleton") {
+                res.push(row(ln.into(), "available", "unknown", "init-script", "Available init script".into()));
+            }
+        } else if cmd.contains("ps -eo") {
+            if f.len() >= 3 && !ln.contains("PID") {
+                res.push(row(f[2].into(), "loaded", "active", "running", format!("Process PID {}, User {}", f[0], f[1])));
+            }
+        } else if cmd.contains("ps aux") {
+            if f.len() < 11 || ln.contains("PID") {
+                continue;
+            }
+            let name = base_name(f[10]);
+            if name.contains("grep") || name.contains("awk") || name.contains("head") || name == "ps" {
+                continue;
+            }
+            let desc = format!("Process {} (PID {}, User {})", f[10..].join(" "), f[1], f[0]);
+            res.push(row(name, "loaded", "active", "running", desc));
+        }
+    }
+    res
+}
+
+// du -sh <dir>
+pub fn parse_disk_usage(out: &str) -> Vec<DiskUsageRow> {
+    out.lines().filter_map(|ln| {
+        let mut f = ln.split_whitespace();
+        Some(DiskUsageRow { size: f.next()?.to_string(), path: f.next()?.to_string() })
+    }).collect()
+}
+
+pub fn parse_users(out: &str, cmd: &str) -> Vec<UserRow> {
+    let row = |user: &str, tty: &str, time: &str, ip: &str| UserRow {
+        user: user.into(), tty: tty.into(), time: time.into(), ip: ip.into(),
+    };
+    let mut res = vec![];
+    for ln in out.lines().filter(|l| !l.trim().is_empty()) {
+        let f: Vec<&str> = ln.split_whitespace().collect();
+        if cmd.contains("who") || cmd.contains("w -h") {
+            if f.len() < 2 {
+                continue;
+            }
+            let time = if f.len() >= 4 { format!("{} {}", f[2], f[3]) } else { String::new() };
+            let ip = f.get(4).filter(|s| s.contains('(')).map(|s| s.trim_matches(|c| c == '(' || c == ')')).unwrap_or("");
+            res.push(row(f[0], f[1], &time, ip));
+        } else if cmd.contains("users") {
+            res.extend(f.iter().map(|u| row(u, "unknown", "active", "")));
+        } else if cmd.contains("/etc/passwd") || cmd.contains("getent passwd") {
+            let mut parts = ln.splitn(2, ':');
+            if let (Some(user), Some(gecos)) = (parts.next(), parts.next()) {
+                res.push(row(user, "system", "account", gecos));
+            }
+        } else if cmd.contains("loginctl") {
+            if f.len() >= 4 {
+                res.push(row(f[2], f.get(4).copied().unwrap_or(""), "session", f[0]));
+            }
+        } else if cmd.contains("ps aux | awk") {
+            if f.len() >= 2 {
+                res.push(row(f[0], f[1], "active", "terminal"));
+            }
+        } else if cmd.contains("netstat") && !ln.contains("127.0.0.1") {
+            res.push(row("ssh-client", "remote", "connected", ln.trim()));
+        }
+    }
+    res
+}
+
+// Строка "cpu ..." из /proc/stat: (всего, простой)
+pub fn parse_cpu_stat(line: &str) -> Option<(i64, i64)> {
+    let vals: Vec<i64> = line.split_whitespace().skip(1).map(|x| x.parse().ok()).collect::<Option<_>>()?;
+    if vals.len() < 4 {
+        return None;
+    }
+    let idle = vals[3] + vals.get(4).copied().unwrap_or(0);
+    Some((vals.iter().sum(), idle))
+}
+
+pub fn cpu_usage(first: (i64, i64), second: (i64, i64)) -> f64 {
+    let total = second.0 - first.0;
+    let idle = second.1 - first.1;
+    if total <= 0 {
+        return 0.0;
+    }
+    ((total - idle) as f64 * 100.0 / total as f64).clamp(0.0, 100.0)
+}
+
+// Первое число в строке (free/df/uptime/nvidia-smi)
+pub fn first_number(s: &str) -> Option<f64> {
+    s.split(|c: char| !(c.is_ascii_digit() || c == '.')).find(|t| !t.is_empty()).and_then(|t| t.parse().ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ps_rows() {
+        let out = "    PID USER     COMMAND         %CPU   RSS
+    812 root     sshd             1.25  8192
+   1024 www-data nginx-with-a-very-long-process-name 1200 -5
+ broken line";
+        let rows = parse_ps(out);
+        assert_eq!(rows.len(), 2);
+        assert_eq!((rows[0].pid.as_str(), rows[0].user.as_str(), rows[0].name.as_str()), ("812", "root", "sshd"));
+        assert_eq!((rows[0].cpu.as_str(), rows[0].rss.as_str()), ("1.2", "8"));
+        // %CPU вне 0..999 и отрицательный RSS обнуляются, имя режется до 30 символов
+        assert_eq!((rows[1].cpu.as_str(), rows[1].rss.as_str()), ("0.0", "0"));
+        assert_eq!(rows[1].name.chars().count(), 30);
+        assert_eq!(rows[1].cmd, rows[1].name);
+    }
+
+    #[test]
+    fn listen_ports_and_connections() {
+        let out = "Netid State  Recv-Q Send-Q Local Address:Port Peer Address:Port Process
+tcp   LISTEN 0      128    0.0.0.0:22         0.0.0.0:*         users:((\"sshd\",pid=812,fd=3))
+udp   UNCONN 0      0      127.0.0.53%lo:53   0.0.0.0:*
+";
+        let ports = parse_listen_ports(out);
+        assert_eq!(ports.len(), 2);
+        assert_eq!((ports[0].proto.as_str(), ports[0].local.as_str()), ("TCP", "0.0.0.0:22"));
+        assert_eq!((ports[0].pid.as_str(), ports[0].proc.as_str()), ("812", "sshd"));
+        assert_eq!((ports[1].pid.as_str(), ports[1].proc.as_str()), ("", ""));
+
+        let out = "Netid State Recv-Q Send-Q Local Address:Port Peer Address:Port Process
+tcp ESTAB 0 0 10.0.0.5:22 203.0.113.7:51234 users:((\"sshd\",pid=900,fd=4))
+tcp TIME-WAIT 0 0 10.0.0.5:80 198.51.100.2:40000";
+        let conns = parse_connections(out);
+        assert_eq!(conns.len(), 2);
+        assert_eq!((conns[0].state.as_str(), conns[0].peer.as_str(), conns[0].pid.as_str()), ("ESTAB", "203.0.113.7:51234", "900"));
+        assert_eq!(conns[1].proc, "");
+    }
+
+    #[test]
+    fn client_ips_skip_local_and_duplicates() {
+        let out = "Netid State Recv-Q Send-Q Local Address:Port Peer Address:Port
+tcp ESTAB 0 0 10.0.0.5:22 203.0.113.7:51234
+tcp ESTAB 0 0 10.0.0.5:22 203.0.113.7:51235
+tcp ESTAB 0 0 [::1]:8080 [::1]:40000
+tcp ESTAB 0 0 [2001:db8::5]:22 [2001:db8::9]:40000
+tcp LISTEN 0 0 0.0.0.0:22 0.0.0.0:*
+tcp ESTAB 0 0 127.0.0.1:5432 127.0.0.1:40000";
+        assert_eq!(parse_client_ips(out), vec!["2001:db8::9", "203.0.113.7"]);
+    }
+
+    #[test]
+    fn services_by_source_command() {
+        let systemctl = "UNIT             LOAD   ACTIVE SUB     DESCRIPTION
+nginx.service    loaded active running A high performance web server
+● cron.service   loaded failed failed  Regular background program processing daemon
+ssh.socket       loaded active listening OpenBSD Secure Shell server socket
+System has not been booted with systemd as init system (PID 1). Can't operate.";
+        let rows = parse_services(systemctl, "systemctl list-units --type=service --all --no-pager");
+        assert_eq!(rows.len(), 1);
+        assert_eq!((rows[0].name.as_str(), rows[0].sub.as_str()), ("nginx.service", "running"));
+        assert_eq!(rows[0].description, "A high performance web server");
+
+        let rows = parse_services("README\ncron\nskeleton\nnginx\n", "ls /etc/init.d");
+        let names: Vec<_> = rows.iter().map(|r| r.name.as_str()).collect();
+        assert_eq!(names, vec!["cron", "nginx"]);
+
+        let ps = "USER PID %CPU %MEM VSZ RSS TTY STAT START TIME COMMAND
+root 1 0.0 0.1 1000 100 ? Ss 10:00 0:01 /sbin/init splash
+root 77 0.0 0.0 1000 100 ? S 10:00 0:00 grep nginx
+www 80 0.1 0.2 1000 100 ? S 10:00 0:02 /usr/sbin/nginx -g daemon";
+        let rows = parse_services(ps, "ps aux | head -20");
+        let names: Vec<_> = rows.iter().map(|r| r.name.as_str()).collect();
+        assert_eq!(names, vec!["init", "nginx"]);
+        assert_eq!(rows[1].description, "Process /usr/sbin/nginx -g daemon (PID 80, User www)");
+    }
+
+    #[test]
+    fn disk_usage_and_users() {
+        let rows = parse_disk_usage("1.2G\t/var\n640K\t/etc\n\n");
+        assert_eq!(rows.len(), 2);
+        assert_eq!((rows[0].size.as_str(), rows[0].path.as_str()), ("1.2G", "/var"));
+
+        let rows = parse_users("alice pts/0 2026-10-18 09:12 (203.0.113.7)\nbob tty1 2026-10-18 08:00\n", "who");
+        assert_eq!(rows.len(), 2);
+        assert_eq!((rows[0].user.as_str(), rows[0].tty.as_str(), rows[0].time.as_str(), rows[0].ip.as_str()), ("alice", "pts/0", "2026-10-18 09:12", "203.0.113.7"));
+        assert_eq!(rows[1].ip, "");
+
+        let rows = parse_users("root:x:0:0:root:/root:/bin/bash\n", "cat /etc/passwd");
+        assert_eq!((rows[0].user.as_str(), rows[0].ip.as_str()), ("root", "x:0:0:root:/root:/bin/bash"));
+        let rows = parse_users("alice bob\n", "users");
+        assert_eq!(rows.iter().map(|r| r.user.as_str()).collect::<Vec<_>>(), vec!["alice", "bob"]);
+    }
+
+    #[test]
+    fn cpu_stat_and_numbers() {
+        let first = parse_cpu_stat("cpu  100 0 100 700 100 0 0 0 0 0").unwrap();
+        assert_eq!(first, (1000, 800));
+        let second = parse_cpu_stat("cpu  200 0 200 1300 100 0 0 0 0 0").unwrap();
+        assert!((cpu_usage(first, second) - 25.0).abs() < 1e-9);
+        assert_eq!(cpu_usage(second, second), 0.0);
+        assert_eq!(parse_cpu_stat("cpu 1 2 3"), None);
+        assert_eq!(parse_cpu_stat("cpu 1 2 x 4"), None);
+
+        assert_eq!(first_number("Mem: 7.6Gi used"), Some(7.6));
+        assert_eq!(first_number(" load average: 0.52, 0.58"), Some(0.52));
+        assert_eq!(first_number("no digits"), None);
+    }
+}
@@ -0,0 +1,758 @@
+// Встроенный SSH-клиент (russh): relay с kind = "native" отвечает на те же пути, что и Go relay,
+// поэтому обработчики дашборда работают с ним через RelayRegistry без изменений
+use axum::http::{Method, StatusCode};
+use russh::client::{self, Handle};
+use russh::keys::agent::client::AgentClient;
+use russh::keys::{self, PrivateKeyWithHashAlg, PublicKey, PublicKeyOrCertificate};
+use russh::{ChannelMsg, Disconnect};
+use russh_sftp::client::SftpSession;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tracing::{debug, info, warn};
+
+use crate::config::NativeSshConfig;
+use super::native_parse::*;
+use super::history::parse_net_dev;
+use super::relay::RelayError;
+use super::relay_models::*;
+
+// Запросы без X-SSH-Session (как к Go relay с одним подключением)
+const DEFAULT_SESSION: &str = "default";
+
+// Проверка ключа хоста по known_hosts, если он задан в [native_ssh]
+pub struct HostKeys {
+    host: String,
+    port: u16,
+    known_hosts: Option<PathBuf>,
+}
+
+impl client::Handler for HostKeys {
+    type Error = russh::Error;
+
+    async fn check_server_key(&mut self, key: &PublicKeyOrCertificate) -> Result<bool, Self::Error> {
+        let Some(path) = &self.known_hosts else { return Ok(true) };
+        let key = match key {
+            PublicKeyOrCertificate::PublicKey { key, .. } => key.clone(),
+            PublicKeyOrCertificate::Certificate(cert) => PublicKey::new(cert.public_key().clone(), ""),
+        };
+        match keys::check_known_hosts_path(&self.host, self.port, &key, path) {
+            Ok(true) => Ok(true),
+            Ok(false) => {
+                warn!("native ssh: {}:{} is not in {}", self.host, self.port, path.display());
+                Ok(false)
+            }
+            Err(e) => {
+                warn!("native ssh: host key check for {}:{} failed: {}", self.host, self.port, e);
+                Ok(false)
+            }
+        }
+    }
+}
+
+// Тело /ssh/connect — то же, что принимает Go relay
+#[derive(Deserialize)]
+pub struct ConnectReq {
+    pub host: String,
+    pub user: String,
+    #[serde(default)]
+    pub port: u16,
+    pub password: Option<String>,
+    pub private_key: Option<String>,
+    pub passphrase: Option<String>,
+    #[serde(default)]
+    pub agent: bool,
+    pub agent_socket: Option<String>,
+    // Jump host'ы по порядку, их собственные jumps не смотрятся
+    #[serde(default)]
+    pub jumps: Vec<ConnectReq>,
+}
+
+// Ошибка подключения и индекс hop'а: jump host'ы, затем цель
+pub struct ConnectError {
+    pub hop: usize,
+    pub message: String,
+}
+
+// Shell с PTY для /rust/ssh/terminal и канал потокового execute
+pub type ShellChannel = russh::Channel<client::Msg>;
+
+pub struct ExecOutput {
+    // stdout и stderr вперемешку, как CombinedOutput в Go
+    pub output: String,
+    pub exit_status: Option<u32>,
+}
+
+pub struct SshSession {
+    pub host: String,
+    pub user: String,
+    handle: Handle<HostKeys>,
+    // Держат туннель до цели; закрываются вместе с сессией
+    jumps: Vec<Handle<HostKeys>>,
+    cwd: Mutex<String>,
+    // Прошлый замер /proc/net/dev: байты rx, tx и время
+    net: Mutex<Option<(i64, i64, Instant)>>,
+}
+
+fn ssh_err(e: impl std::fmt::Display) -> RelayError {
+    RelayError::Transport(format!("ssh: {}", e))
+}
+
+fn not_connected() -> RelayError {
+    RelayError::Upstream(StatusCode::BAD_GATEWAY, "ssh not connected".into())
+}
+
+fn port_or_default(port: u16) -> u16 {
+    if port == 0 { 22 } else { port }
+}
+
+// Одинарные кавычки для путей в cd
+fn shell_quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', r"'\''"))
+}
+
+impl SshSession {
+    // Каждый следующий hop — через direct-tcpip канал предыдущего; как в Go relay
+    pub async fn connect(req: &ConnectReq, config: &NativeSshConfig) -> Result<SshSession, ConnectError> {
+        let hops: Vec<&ConnectReq> = req.jumps.iter().chain(std::iter::once(req)).collect();
+        let mut handles: Vec<Handle<HostKeys>> = vec![];
+        for (i, hop) in hops.iter().enumerate() {
+            match Self::dial(hop, config, handles.last()).await {
+                Ok(handle) => handles.push(handle),
+                Err(e) => {
+                    for h in handles.iter().rev() {
+                        let _ = h.disconnect(Disconnect::ByApplication, "", "en").await;
+                    }
+                    let message = match (hops.len(), i + 1 == hops.len()) {
+                        (1, _) => e,
+                        (_, true) => format!("target {}@{}:{}: {}", hop.user, hop.host, port_or_default(hop.port), e),
+                        (_, false) => format!("jump {} {}@{}:{}: {}", i + 1, hop.user, hop.host, port_or_default(hop.port), e),
+                    };
+                    return Err(ConnectError { hop: i, message });
+                }
+            }
+        }
+        let Some(handle) = handles.pop() else { unreachable!("chain always ends with the target") };
+        Ok(SshSession {
+            host: req.host.clone(),
+            user: req.user.clone(),
+            handle,
+            jumps: handles,
+            cwd: Mutex::new(String::new()),
+            net: Mutex::new(None),
+        })
+    }
+
+    async fn dial(req: &ConnectReq, config: &NativeSshConfig, via: Option<&Handle<HostKeys>>) -> Result<Handle<HostKeys>, String> {
+        let port = port_or_default(req.port);
+        let ssh_config = Arc::new(client::Config {
+            keepalive_interval: (config.keepalive_secs > 0).then(|| Duration::from_secs(config.keepalive_secs)),
+            ..Default::default()
+        });
+        let handler = HostKeys { host: req.host.clone(), port, known_hosts: config.known_hosts.clone() };
+        let connect = async {
+            match via {
+                None => client::connect(ssh_config, (req.host.as_str(), port), handler).await,
+                Some(jump) => {
+                    let channel = jump.channel_open_direct_tcpip(req.host.as_str(), port as u32, "127.0.0.1", 0).await?;
+                    client::connect_stream(ssh_config, channel.into_stream(), handler).await
+                }
+            }
+        };
+        let mut handle = tokio::time::timeout(Duration::from_secs(config.connect_timeout_secs), connect)
+            .await
+            .map_err(|_| format!("dial tcp {}:{}: i/o timeout", req.host, port))?
+            .map_err(|e| e.to_string())?;
+
+        let mut attempted = vec![];
+        let mut authenticated = false;
+        if let Some(pem) = req.private_key.as_deref().filter(|k| !k.is_empty()) {
+            attempted.push("publickey");
+            let key = keys::decode_secret_key(pem, req.passphrase.as_deref().filter(|p| !p.is_empty()))
+                .map_err(|e| format!("private key: {}", e))?;
+            let hash = handle.best_supported_rsa_hash().await.map_err(|e| e.to_string())?.flatten();
+            authenticated = handle.authenticate_publickey(&req.user, PrivateKeyWithHashAlg::new(Arc::new(key), hash))
+                .await
+                .map_err(|e| e.to_string())?
+                .success();
+        }
+        if !authenticated && req.agent {
+            attempted.push("agent");
+            authenticated = Self::auth_agent(&mut handle, &req.user, req.agent_socket.as_deref()).await?;
+        }
+        if !authenticated {
+            if let Some(password) = req.password.as_deref().filter(|p| !p.is_empty()) {
+                attempted.push("password");
+                authenticated = handle.authenticate_password(&req.user, password)
+                    .await
+                    .map_err(|e| e.to_string())?
+                    .success();
+            }
+        }
+        if attempted.is_empty() {
+            return Err("no password, key or agent provided".into());
+        }
+        if !authenticated {
+            return Err(format!("ssh: unable to authenticate, attempted methods [{}]", attempted.join(" ")));
+        }
+        Ok(handle)
+    }
+
+    async fn auth_agent(handle: &mut Handle<HostKeys>, user: &str, socket: Option<&str>) -> Result<bool, String> {
+        let mut agent = match socket.filter(|s| !s.is_empty()) {
+            Some(path) => AgentClient::connect_uds(path).await,
+            None => AgentClient::connect_env().await,
+        }.map_err(|e| format!("ssh-agent: {}", e))?;
+        let identities = agent.request_identities().await.map_err(|e| format!("ssh-agent: {}", e))?;
+        let hash = handle.best_supported_rsa_hash().await.map_err(|e| e.to_string())?.flatten();
+        for identity in identities {
+            let key = identity.public_key().into_owned();
+            match handle.authenticate_publickey_with(user, key, hash, &mut agent).await {
+                Ok(result) if result.success() => return Ok(true),
+                Ok(_) => continue,
+                Err(e) => return Err(format!("ssh-agent: {}", e)),
+            }
+        }
+        Ok(false)
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.handle.is_closed()
+    }
+
+    pub fn current_dir(&self) -> String {
+        self.cwd.lock().map(|c| c.clone()).unwrap_or_default()
+    }
+
+    pub async fn close(&self) {
+        let _ = self.handle.disconnect(Disconnect::ByApplication, "", "en").await;
+        for jump in self.jumps.iter().rev() {
+            let _ = jump.disconnect(Disconnect::ByApplication, "", "en").await;
+        }
+    }
+
+    pub async fn exec(&self, cmd: &str, timeout: Duration) -> Result<ExecOutput, RelayError> {
+        let mut channel = self.handle.channel_open_session().await.map_err(ssh_err)?;
+        channel.exec(true, cmd).await.map_err(ssh_err)?;
+        let mut output = Vec::new();
+        let mut exit_status = None;
+        let collect = async {
+            while let Some(msg) = channel.wait().await {
+                match msg {
+                    ChannelMsg::Data { data } | ChannelMsg::ExtendedData { data, .. } => output.extend_from_slice(&data),
+                    ChannelMsg::ExitStatus { exit_status: code } => exit_status = Some(code),
+                    _ => {}
+                }
+            }
+        };
+        if tokio::time::timeout(timeout, collect).await.is_err() {
+            let _ = channel.close().await;
+            return Err(RelayError::Timeout(timeout.as_millis() as u64));
+        }
+        Ok(ExecOutput { output: String::from_utf8_lossy(&output).into_owned(), exit_status })
+    }
+
+    pub async fn open_shell(&self, term: &str, cols: u32, rows: u32) -> Result<ShellChannel, RelayError> {
+        let channel = self.handle.channel_open_session().await.map_err(ssh_err)?;
+        channel.request_pty(true, term, cols, rows, 0, 0, &[]).await.map_err(ssh_err)?;
+        channel.request_shell(true).await.map_err(ssh_err)?;
+        Ok(channel)
+    }
+
+    // Новая SFTP-подсистема на каждый запрос /rust/files/*: открытые файлы держат её сами
+    pub async fn sftp(&self) -> Result<SftpSession, RelayError> {
+        let channel = self.handle.channel_open_session().await.map_err(ssh_err)?;
+        channel.request_subsystem(true, "sftp").await.map_err(ssh_err)?;
+        SftpSession::new(channel.into_stream()).await.map_err(|e| ssh_err(format!("sftp: {}", e)))
+    }
+
+    // Команда на отдельном канале в текущем каталоге; вывод читает /rust/ssh/execute/stream
+    pub async fn start(&self, cmd: &str) -> Result<ShellChannel, RelayError> {
+        let cwd = self.current_dir();
+        let cmd = if cwd.is_empty() { cmd.to_string() } else { format!("cd {} && {}", shell_quote(&cwd), cmd) };
+        let channel = self.handle.channel_open_session().await.map_err(ssh_err)?;
+        channel.exec(true, cmd).await.map_err(ssh_err)?;
+        Ok(channel)
+    }
+
+    // Вывод команды, ненулевой код возврата — ошибка
+    pub async fn run(&self, cmd: &str, timeout: Duration) -> Result<String, RelayError> {
+        let out = self.exec(cmd, timeout).await?;
+        match out.exit_status {
+            Some(0) => Ok(out.output),
+            status => {
+                let status = status.map(|s| s.to_string()).unwrap_or_else(|| "unknown".into());
+                Err(RelayError::Upstream(StatusCode::BAD_GATEWAY, format!("{}Process exited with status {}", out.output, status)))
+            }
+        }
+    }
+
+    // Команда в текущем каталоге сессии; cd и pwd обрабатываются здесь, как в Go relay
+    pub async fn run_in_cwd(&self, cmd: &str, timeout: Duration) -> Result<String, RelayError> {
+        let trimmed = cmd.trim();
+        let cwd = self.current_dir();
+        if trimmed == "cd" || trimmed.starts_with("cd ") {
+            let Some(target) = trimmed.split_whitespace().nth(1) else {
+                self.set_cwd(String::new());
+                return Ok("Changed to home directory".into());
+            };
+            let test = if cwd.is_empty() || target.starts_with('/') {
+                format!("cd {} && pwd", shell_quote(target))
+            } else {
+                format!("cd {} && cd {} && pwd", shell_quote(&cwd), shell_quote(target))
+            };
+            let dir = self.run(&test, timeout).await
+                .map_err(|_| RelayError::Upstream(StatusCode::BAD_GATEWAY, format!("cd: {}: No such file or directory", target)))?;
+            let dir = dir.trim().to_string();
+            self.set_cwd(dir.clone());
+            return Ok(format!("Changed directory to: {}", dir));
+        }
+        if trimmed == "pwd" && !cwd.is_empty() {
+            return Ok(cwd);
+        }
+        if cwd.is_empty() {
+            self.run(cmd, timeout).await
+        } else {
+            self.run(&format!("cd {} && {}", shell_quote(&cwd), cmd), timeout).await
+        }
+    }
+
+    fn set_cwd(&self, dir: String) {
+        if let Ok(mut c) = self.cwd.lock() {
+            *c = dir;
+        }
+    }
+
+    // Скорость сети по разнице с прошлым замером, Мбит/с
+    fn net_speed(&self, rx: i64, tx: i64) -> (f64, f64) {
+        let now = Instant::now();
+        let Ok(mut last) = self.net.lock() else { return (0.0, 0.0) };
+        let speed = match *last {
+            Some((prev_rx, prev_tx, at)) => {
+                let secs = now.duration_since(at).as_secs_f64();
+                let mbps = |d: i64| if secs > 0.0 { (d as f64 / secs / (1024.0 * 1024.0) * 8.0).max(0.0) } else { 0.0 };
+                (mbps(rx - prev_rx), mbps(tx - prev_tx))
+            }
+            None => (0.0, 0.0),
+        };
+        *last = Some((rx, tx, now));
+        speed
+    }
+}
+
+// Открытые SSH-сессии по ключу
+#[derive(Default)]
+pub struct SshPool {
+    sessions: Mutex<HashMap<String, Arc<SshSession>>>,
+}
+
+impl SshPool {
+    pub fn get(&self, id: &str) -> Option<Arc<SshSession>> {
+        let mut sessions = self.sessions.lock().ok()?;
+        match sessions.get(id) {
+            Some(s) if s.is_closed() => {
+                info!("native ssh: session {} to {} was closed by the server", id, s.host);
+                sessions.remove(id);
+                None
+            }
+            other => other.cloned(),
+        }
+    }
+
+    pub fn insert(&self, id: &str, session: Arc<SshSession>) -> Option<Arc<SshSession>> {
+        self.sessions.lock().ok()?.insert(id.to_string(), session)
+    }
+
+    pub fn remove(&self, id: &str) -> Option<Arc<SshSession>> {
+        self.sessions.lock().ok()?.remove(id)
+    }
+}
+
+#[derive(Deserialize)]
+struct ExecuteReq { command: String }
+
+#[derive(Deserialize)]
+struct ProcessActionReq { pid: String, action: String }
+
+fn body<T: DeserializeOwned>(body: Option<Value>) -> Result<T, RelayError> {
+    serde_json::from_value(body.unwrap_or(Value::Null))
+        .map_err(|e| RelayError::Upstream(StatusCode::BAD_REQUEST, e.to_string()))
+}
+
+fn to_json<T: Serialize>(value: &T) -> Result<String, RelayError> {
+    serde_json::to_string(value).map_err(|e| RelayError::InvalidResponse(e.to_string()))
+}
+
+pub struct NativeRelay {
+    name: String,
+    config: NativeSshConfig,
+    pool: SshPool,
+}
+
+impl NativeRelay {
+    pub fn new(name: &str, config: NativeSshConfig) -> NativeRelay {
+        NativeRelay { name: name.to_string(), config, pool: SshPool::default() }
+    }
+
+    fn session(&self, id: &str) -> Result<Arc<SshSession>, RelayError> {
+        self.pool.get(id).ok_or_else(not_connected)
+    }
+
+    pub async fn open_shell(&self, session: Option<&str>, term: &str, cols: u32, rows: u32) -> Result<ShellChannel, RelayError> {
+        let s = self.session(session.unwrap_or(DEFAULT_SESSION))?;
+        info!("native ssh {}: opening {}x{} terminal on {}@{}", self.name, cols, rows, s.user, s.host);
+        s.open_shell(term, cols, rows).await
+    }
+
+    pub async fn start(&self, session: Option<&str>, cmd: &str) -> Result<ShellChannel, RelayError> {
+        let s = self.session(session.unwrap_or(DEFAULT_SESSION))?;
+        debug!("native ssh {}: streaming command on {}@{}", self.name, s.user, s.host);
+        s.start(cmd).await
+    }
+
+    pub async fn sftp(&self, session: Option<&str>) -> Result<SftpSession, RelayError> {
+        let s = self.session(session.unwrap_or(DEFAULT_SESSION))?;
+        debug!("native ssh {}: opening sftp on {}@{}", self.name, s.user, s.host);
+        s.sftp().await
+    }
+
+    // Аналог HTTP-запроса к Go relay: путь без префикса /go, ответ — тело как текст
+    pub async fn handle(&self, method: &Method, path: &str, session: Option<&str>, req: Option<Value>, timeout: Duration) -> Result<String, RelayError> {
+        let id = session.unwrap_or(DEFAULT_SESSION);
+        debug!("native relay {} {} {} session={}", self.name, method, path, id);
+        let work = async {
+            match (method.as_str(), path) {
+                ("POST", "/ssh/connect") => self.connect(id, body(req)?).await,
+                ("POST", "/ssh/disconnect") => self.disconnect(id).await,
+                ("GET", "/ssh/status") => self.status(id),
+                ("POST", "/ssh/execute") => {
+                    let req: ExecuteReq = body(req)?;
+                    self.session(id)?.run_in_cwd(&req.command, timeout).await
+                }
+                ("POST", "/process/action") => self.process_action(self.session(id)?.as_ref(), body(req)?, timeout).await,
+                ("GET", p) if p.starts_with("/data/") => self.data(self.session(id)?.as_ref(), &p[6..], timeout).await,
+                _ => Err(RelayError::Upstream(StatusCode::NOT_FOUND, "404 page not found".into())),
+            }
+        };
+        tokio::time::timeout(timeout, work).await.unwrap_or(Err(RelayError::Timeout(timeout.as_millis() as u64)))
+    }
+
+    async fn connect(&self, id: &str, req: ConnectReq) -> Result<String, RelayError> {
+        info!("native ssh {}: session {} connecting to {}@{}:{} via {} jump hosts", self.name, id, req.user, req.host, req.port, req.jumps.len());
+        let result = match SshSession::connect(&req, &self.config).await {
+            Ok(session) => {
+                if let Some(old) = self.pool.insert(id, Arc::new(session)) {
+                    old.close().await;
+                }
+                SshConnectResult { ok: true, connected: true, message: "ok".into(), failed_hop: None }
+            }
+            Err(e) => {
+                warn!("native ssh {}: connect to {} failed: {}", self.name, req.host, e.message);
+                let failed_hop = (!req.jumps.is_empty()).then_some(e.hop);
+                SshConnectResult { ok: true, connected: false, message: e.message, failed_hop }
+            }
+        };
+        to_json(&result)
+    }
+
+    async fn disconnect(&self, id: &str) -> Result<String, RelayError> {
+        if let Some(session) = self.pool.remove(id) {
+            session.close().await;
+        }
+        to_json(&SshDisconnectResult { ok: true, connected: false })
+    }
+
+    fn status(&self, id: &str) -> Result<String, RelayError> {
+        let status = match self.pool.get(id) {
+            Some(s) => SshStatus { connected: true, host: s.host.clone(), user: s.user.clone(), current_dir: s.current_dir() },
+            None => SshStatus::default(),
+        };
+        to_json(&status)
+    }
+
+    async fn data(&self, s: &SshSession, what: &str, timeout: Duration) -> Result<String, RelayError> {
+        match what {
+            "processes" => {
+                let out = s.run("ps -eo pid,user,comm,pcpu,rss --sort=-pcpu | head -n 15", timeout).await?;
+                to_json(&ProcessList { rows: parse_ps(&out) })
+            }
+            "ports" => to_json(&PortList { rows: parse_listen_ports(&s.run("ss -lntup", timeout).await?) }),
+            "connections" => to_json(&parse_connections(&s.run("ss -tuanp | head -n 20", timeout).await?)),
+            "client-ips" => to_json(&parse_client_ips(&s.run("ss -tuan", timeout).await?)),
+            "process-logs" => {
+                let out = s.run("journalctl --no-pager -n 20 --output=short", timeout).await?;
+                let logs: ProcessLogs = [("system".to_string(), out.split('\n').map(String::from).collect())].into();
+                to_json(&logs)
+            }
+            "resources" => to_json(&resources(s, timeout).await),
+            "network" => {
+                let (rx, tx) = parse_net_dev(&s.run("cat /proc/net/dev", timeout).await?).unwrap_or_default();
+                let (rx, tx) = s.net_speed(rx, tx);
+                // Та же форма, что у Go: 60 точек вокруг текущей скорости
+                let points: Vec<_> = (0..60).map(|i| NetworkSample {
+                    t: i,
+                    rx: rx * (0.9 + 0.2 * (i % 5) as f64 / 5.0),
+                    tx: tx * (0.8 + 0.4 * (i % 7) as f64 / 7.0),
+                }).collect();
+                to_json(&points)
+            }
+            "services" => to_json(&ServiceList { services: services(s, timeout).await }),
+            "disk-usage" => to_json(&DiskUsage { directories: disk_usage(s, timeout).await }),
+            "users" => to_json(&UserList { users: users(s, timeout).await }),
+            "system-info" => to_json(&system_info(s, timeout).await),
+            _ => Err(RelayError::Upstream(StatusCode::NOT_FOUND, "404 page not found".into())),
+        }
+    }
+
+    async fn process_action(&self, s: &SshSession, req: ProcessActionReq, timeout: Duration) -> Result<String, RelayError> {
+        if req.pid.is_empty() || !req.pid.chars().all(|c| c.is_ascii_digit()) {
+            return Err(RelayError::Upstream(StatusCode::BAD_REQUEST, format!("invalid pid {:?}", req.pid)));
+        }
+        info!("native ssh {}: process_action pid={} action={}", self.name, req.pid, req.action);
+        let cmd = match req.action.as_str() {
+            "kill" => format!("kill -9 {}", req.pid),
+            "restart" => {
+                let cmdline = s.run(&format!("ps -p {} -o cmd --no-headers", req.pid), timeout).await
+                    .map_err(|e| RelayError::Upstream(StatusCode::BAD_GATEWAY, format!("failed to get process command: {}", e.message())))?;
+                let cmdline = cmdline.trim().to_string();
+                if cmdline.is_empty() {
+                    return Err(RelayError::Upstream(StatusCode::NOT_FOUND, "process not found or no command available".into()));
+                }
+                s.run(&format!("kill {}", req.pid), timeout).await
+                    .map_err(|e| RelayError::Upstream(StatusCode::BAD_GATEWAY, format!("failed to kill process: {}", e.message())))?;
+                tokio::time::sleep(Duration::from_secs(1)).await;
+                format!("nohup {} > /dev/null 2>&1 &", cmdline)
+            }
+            _ => return Err(RelayError::Upstream(StatusCode::BAD_REQUEST, "invalid action, use 'kill' or 'restart'".into())),
+        };
+        let output = s.run(&cmd, timeout).await
+            .map_err(|e| RelayError::Upstream(StatusCode::BAD_GATEWAY, format!("command failed: {}", e.message())))?;
+        to_json(&ProcessActionResult { success: true, action: req.action, pid: req.pid, output })
+    }
+}
+
+// Пустая строка, если команда не сработала: у этих метрик есть запасные варианты
+async fn try_run(s: &SshSession, cmd: &str, timeout: Duration) -> String {
+    s.run(cmd, timeout).await.map(|o| o.trim().to_string()).unwrap_or_default()
+}
+
+async fn cpu_usage_of(s: &SshSession, timeout: Duration) -> f64 {
+    let first = try_run(s, "grep '^cpu ' /proc/stat", timeout).await;
+    tokio::time::sleep(Duration::from_millis(250)).await;
+    let second = try_run(s, "grep '^cpu ' /proc/stat", timeout).await;
+    match (parse_cpu_stat(&first), parse_cpu_stat(&second)) {
+        (Some(a), Some(b)) => cpu_usage(a, b),
+        _ => 0.0,
+    }
+}
+
+async fn resources(s: &SshSession, timeout: Duration) -> Resources {
+    let (cpu, mem, disk, load, gpu) = tokio::join!(
+        cpu_usage_of(s, timeout),
+        try_run(s, "free -m | awk 'NR==2{printf \"%.2f %.2f\", ($2-$7)/1024, $2/1024}'", timeout),
+        try_run(s, "df / | awk 'NR==2{print $5}' | tr -d '%'", timeout),
+        try_run(s, "cat /proc/loadavg", timeout),
+        try_run(s, "nvidia-smi --query-gpu=utilization.gpu --format=csv,noheader,nounits 2>/dev/null | head -1 || cat /sys/class/drm/card0/device/gpu_busy_percent 2>/dev/null", timeout),
+    );
+    let mut mem = mem.split_whitespace().filter_map(|x| x.parse::<f64>().ok());
+    let used = mem.next().unwrap_or(0.0).max(0.0);
+    let total = mem.next().filter(|t| *t >= 0.1).unwrap_or(8.0).min(1024.0);
+    Resources {
+        cpu: CpuStats { usage: cpu, load: first_number(&load).unwrap_or(0.0).clamp(0.0, 50.0) },
+        gpu: GpuStats { usage: first_number(&gpu).unwrap_or(0.0).clamp(0.0, 100.0) },
+        ram: MemStats { used: used.min(total), total },
+        vram: MemStats::default(),
+        ssd: DiskStats { util: first_number(&disk).unwrap_or(0.0).clamp(0.0, 100.0), ..Default::default() },
+    }
+}
+
+const SERVICE_COMMANDS: &[&str] = &[
+    "systemctl list-units --type=service --state=running --no-pager --no-legend | head -15",
+    "/etc/init.d/* status 2>/dev/null | grep running | head -10",
+    "ls /etc/init.d/ | head -10",
+    "ps aux | grep -E '[s]shd|[n]ginx|[a]pache|[m]ysql|[p]ostgres|[r]edis|[d]ocker' | head -10",
+    "ps -eo pid,user,comm | head -15",
+];
+
+// Первая команда, давшая осмысленный вывод
+async fn services(s: &SshSession, timeout: Duration) -> Vec<ServiceRow> {
+    for cmd in SERVICE_COMMANDS {
+        let out = try_run(s, cmd, timeout).await;
+        if out.is_empty() || ["System has", "Failed to", "Can't operate", "Host is down"].iter().any(|n| out.contains(n)) {
+            continue;
+        }
+        let rows = parse_services(&out, cmd);
+        if !rows.is_empty() {
+            return rows;
+        }
+    }
+    vec![]
+}
+
+async fn disk_usage(s: &SshSession, timeout: Duration) -> Vec<DiskUsageRow> {
+    let out = try_run(s, "du -sh /var /tmp /home /opt /usr /etc /root 2>/dev/null; true", timeout).await;
+    let rows = parse_disk_usage(&out);
+    if !rows.is_empty() {
+        return rows;
+    }
+    parse_disk_usage(&try_run(s, "du -sh /* 2>/dev/null | head -10; true", timeout).await)
+}
+
+const USER_COMMANDS: &[&str] = &[
+    "who",
+    "w -h",
+    "users",
+    "getent passwd | grep -E '/bin/(bash|sh|zsh|fish)$' | cut -d: -f1,5 | head -10",
+    "loginctl list-sessions --no-legend 2>/dev/null",
+];
+
+async fn users(s: &SshSession, timeout: Duration) -> Vec<UserRow> {
+    for cmd in USER_COMMANDS {
+        let out = try_run(s, cmd, timeout).await;
+        if !out.is_empty() {
+            return parse_users(&out, cmd);
+        }
+    }
+    vec![]
+}
+
+async fn system_info(s: &SshSession, timeout: Duration) -> SystemInfo {
+    let (uptime, kernel, arch, distro) = tokio::join!(
+        try_run(s, "uptime", timeout),
+        try_run(s, "uname -r", timeout),
+        try_run(s, "uname -m", timeout),
+        try_run(s, "grep PRETTY_NAME /etc/os-release | cut -d= -f2 | tr -d '\"'", timeout),
+    );
+    let distro = if distro.is_empty() {
+        let fallback = try_run(s, "lsb_release -ds 2>/dev/null || cat /etc/redhat-release 2>/dev/null", timeout).await;
+        if fallback.is_empty() { "Unknown Linux".into() } else { fallback }
+    } else {
+        distro
+    };
+    SystemInfo { uptime, distro, kernel, arch }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use russh::server::{self, Auth, Msg, Session};
+    use russh::{Channel, ChannelId};
+    use serde_json::json;
+    use tokio::sync::mpsc;
+
+    const TIMEOUT: Duration = Duration::from_secs(5);
+
+    // SSH-сервер в процессе: пароль "secret", exec отвечает заготовленным выводом вместо запуска команд
+    struct FakeHost {
+        commands: mpsc::UnboundedSender<String>,
+    }
+
+    impl server::Handler for FakeHost {
+        type Error = russh::Error;
+
+        async fn auth_password(&mut self, _user: &str, password: &str) -> Result<Auth, Self::Error> {
+            Ok(if password == "secret" { Auth::Accept } else { Auth::reject() })
+        }
+
+        async fn channel_open_session(&mut self, _channel: Channel<Msg>, reply: server::ChannelOpenHandle, _session: &mut Session) -> Result<(), Self::Error> {
+            reply.accept().await;
+            Ok(())
+        }
+
+        async fn exec_request(&mut self, channel: ChannelId, data: &[u8], session: &mut Session) -> Result<(), Self::Error> {
+            let cmd = String::from_utf8_lossy(data).into_owned();
+            let _ = self.commands.send(cmd.clone());
+            let (out, err, status) = match cmd.as_str() {
+                "echo hello" => ("hello\n", "", 0),
+                "cd '/srv' && pwd" => ("/srv\n", "", 0),
+                "cd '/srv' && ls" => ("app\n", "", 0),
+                _ => ("", "sh: not found\n", 127),
+            };
+            session.channel_success(channel)?;
+            session.data(channel, out.as_bytes().to_vec())?;
+            session.extended_data(channel, 1, err.as_bytes().to_vec())?;
+            session.exit_status_request(channel, status)?;
+            session.eof(channel)?;
+            session.close(channel)
+        }
+    }
+
+    // Порт сервера, полученные им команды и конец SSH-сессии со стороны сервера
+    async fn fake_host() -> (u16, mpsc::UnboundedReceiver<String>, mpsc::UnboundedReceiver<()>) {
+        let key = keys::ssh_key::private::Ed25519Keypair::from_seed(&[7; 32]);
+        let config = Arc::new(server::Config {
+            keys: vec![keys::PrivateKey::from(key)],
+            auth_rejection_time: Duration::from_millis(10),
+            ..Default::default()
+        });
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let (commands, received) = mpsc::unbounded_channel();
+        let (closed_tx, closed) = mpsc::unbounded_channel();
+        tokio::spawn(async move {
+            while let Ok((socket, _)) = listener.accept().await {
+                let (config, commands, closed_tx) = (config.clone(), commands.clone(), closed_tx.clone());
+                tokio::spawn(async move {
+                    if let Ok(session) = server::run_stream(config, socket, FakeHost { commands }).await {
+                        let _ = session.await;
+                    }
+                    let _ = closed_tx.send(());
+                });
+            }
+        });
+        (port, received, closed)
+    }
+
+    async fn call(relay: &NativeRelay, method: Method, path: &str, body: Option<Value>) -> Result<String, String> {
+        relay.handle(&method, path, Some("s1"), body, TIMEOUT).await.map_err(|e| e.message())
+    }
+
+    #[tokio::test]
+    async fn connect_execute_disconnect() {
+        let (port, mut received, mut closed) = fake_host().await;
+        let relay = NativeRelay::new("test", NativeSshConfig::default());
+        let login = |password: &str| json!({"host": "127.0.0.1", "port": port, "user": "ops", "password": password});
+
+        let result: SshConnectResult = serde_json::from_str(&call(&relay, Method::POST, "/ssh/connect", Some(login("wrong"))).await.unwrap()).unwrap();
+        assert!(!result.connected);
+        assert_eq!(result.message, "ssh: unable to authenticate, attempted methods [password]");
+        let status: SshStatus = serde_json::from_str(&call(&relay, Method::GET, "/ssh/status", None).await.unwrap()).unwrap();
+        assert!(!status.connected);
+
+        let result: SshConnectResult = serde_json::from_str(&call(&relay, Method::POST, "/ssh/connect", Some(login("secret"))).await.unwrap()).unwrap();
+        assert!(result.connected, "{}", result.message);
+        let status: SshStatus = serde_json::from_str(&call(&relay, Method::GET, "/ssh/status", None).await.unwrap()).unwrap();
+        assert_eq!((status.connected, status.host.as_str(), status.user.as_str()), (true, "127.0.0.1", "ops"));
+
+        let out = call(&relay, Method::POST, "/ssh/execute", Some(json!({"command": "echo hello"}))).await.unwrap();
+        assert_eq!(out, "hello\n");
+        assert_eq!(received.recv().await.unwrap(), "echo hello");
+
+        // Ненулевой код — ошибка с выводом, как у Go relay
+        match relay.handle(&Method::POST, "/ssh/execute", Some("s1"), Some(json!({"command": "missing"})), TIMEOUT).await {
+            Err(RelayError::Upstream(status, message)) => {
+                assert_eq!(status, StatusCode::BAD_GATEWAY);
+                assert_eq!(message, "sh: not found\nProcess exited with status 127");
+            }
+            other => panic!("unexpected {:?}", other.map_err(|e| e.message())),
+        }
+
+        // cd запоминается в сессии и подставляется в следующие команды
+        let out = call(&relay, Method::POST, "/ssh/execute", Some(json!({"command": "cd /srv"}))).await.unwrap();
+        assert_eq!(out, "Changed directory to: /srv");
+        let out = call(&relay, Method::POST, "/ssh/execute", Some(json!({"command": "ls"}))).await.unwrap();
+        assert_eq!(out, "app\n");
+        let sent: Vec<_> = std::iter::from_fn(|| received.try_recv().ok()).collect();
+        assert_eq!(sent, vec!["missing", "cd '/srv' && pwd", "cd '/srv' && ls"]);
+
+        let result: SshDisconnectResult = serde_json::from_str(&call(&relay, Method::POST, "/ssh/disconnect", None).await.unwrap()).unwrap();
+        assert!(!result.connected);
+        tokio::time::timeout(TIMEOUT, closed.recv()).await.expect("server session still open");
+        let status: SshStatus = serde_json::from_str(&call(&relay, Method::GET, "/ssh/status", None).await.unwrap()).unwrap();
+        assert!(!status.connected);
+        assert_eq!(call(&relay, Method::POST, "/ssh/execute", Some(json!({"command": "echo hello"}))).await, Err("ssh not connected".to_string()));
+    }
+}
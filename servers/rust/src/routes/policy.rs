@@ -0,0 +1,447 @@
+// Политика команд: проверяется до отправки в relay. Это ограждение от ошибок, а не песочница —
+// шаблоны сравниваются с текстом команды, shell на хосте всё равно может больше
+use axum::{http::StatusCode, response::{IntoResponse, Response}, Json};
+use serde::Serialize;
+use serde_json::json;
+
+use crate::config::CommandPolicy;
+use super::relay::{RelayRegistry, RelayTarget};
+use super::servers::ServerStore;
+
+// Программы, которые только читают состояние хоста (для read_only)
+const READ_ONLY_COMMANDS: &[&str] = &[
+    "cat", "ls", "head", "tail", "grep", "egrep", "fgrep", "zgrep", "zcat", "wc", "sort", "uniq", "cut", "tr",
+    "column", "ps", "pgrep", "pstree", "top", "free", "df", "du", "uptime", "w", "who", "whoami", "id", "groups",
+    "uname", "hostname", "date", "pwd", "cd", "echo", "printf", "stat", "file", "find", "which", "whereis",
+    "type", "printenv", "ss", "netstat", "lsof", "lsblk", "vmstat", "iostat", "nproc", "lscpu", "last",
+    "journalctl", "systemctl", "service", "true",
+];
+// Подкоманды systemctl/service без изменений
+const READ_ONLY_SYSTEMCTL: &[&str] = &["status", "show", "list-units", "list-unit-files", "list-timers", "is-active", "is-enabled", "is-failed", "cat"];
+// Аргументы, превращающие читающую программу в пишущую
+const WRITING_ARGS: &[&str] = &["-delete", "-exec", "-execdir", "-ok", "-okdir", "-fprint", "-fprint0", "-fprintf", "-fls", "--vacuum", "--rotate", "--flush"];
+// date и hostname с аргументами меняют часы и имя хоста: разрешены только эти
+const READ_ONLY_DATE_ARGS: &[&str] = &["-u", "--utc", "--universal", "-R", "--rfc-email"];
+const READ_ONLY_HOSTNAME_ARGS: &[&str] = &["-f", "--fqdn", "--long", "-s", "--short", "-d", "--domain", "-i", "--ip-address", "-I", "--all-ip-addresses", "-A", "--all-fqdns"];
+// Обёртки, за которыми идёт настоящая команда
+const WRAPPERS: &[&str] = &["sudo", "nohup", "time", "exec", "command", "env", "nice", "timeout", "xargs", "doas"];
+// Флаги обёрток со значением отдельным словом: sudo -u root
+const WRAPPER_VALUE_FLAGS: &[(&str, &[&str])] = &[
+    ("sudo", &["-u", "-g", "-C", "-D", "-h", "-p", "-r", "-t", "-U", "-T"]),
+    ("doas", &["-u", "-C"]),
+    ("env", &["-u", "-C", "-S"]),
+    ("nice", &["-n"]),
+    ("timeout", &["-s", "-k", "--signal", "--kill-after"]),
+    ("xargs", &["-I", "-n", "-P", "-L", "-s", "-d", "-E", "-a"]),
+];
+
+#[derive(Serialize)]
+pub struct PolicyDenied {
+    #[serde(skip)]
+    pub error: String,
+    // Имя сохранённого сервера, чья политика сработала; None — [policy] из конфигурации
+    pub server: Option<String>,
+    // deny, allow, read_only, action, pid, terminal, files, units, unavailable
+    pub rule: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pattern: Option<String>,
+    // Часть команды, на которой сработало правило
+    pub subject: String,
+}
+
+impl IntoResponse for PolicyDenied {
+    fn into_response(self) -> Response {
+        (StatusCode::FORBIDDEN, Json(json!({"error": self.error.clone(), "policy": self}))).into_response()
+    }
+}
+
+// Политика, действующая для конкретной SSH-сессии
+pub struct Effective {
+    pub server: Option<String>,
+    pub policy: CommandPolicy,
+    // Политику сервера не удалось прочитать: отказывается всё, пока файл не починят
+    pub unavailable: Option<String>,
+}
+
+// Сессия подключена по сохранённому серверу с политикой — она, иначе [policy]; deny из [policy] добавляется всегда
+pub fn for_target(registry: &RelayRegistry, servers: &ServerStore, global: &CommandPolicy, target: &RelayTarget) -> Effective {
+    let server = target.session.as_deref()
+        .and_then(|id| registry.sessions.get(id))
+        .and_then(|s| s.server);
+    let own = match server.as_deref().map(|name| servers.policy(name)) {
+        Some(Ok(own)) => own,
+        None => None,
+        Some(Err(e)) => {
+            let unavailable = Some(format!("policy of server {} cannot be loaded: {}", server.as_deref().unwrap_or(""), e.message()));
+            return Effective { server, policy: global.clone(), unavailable };
+        }
+    };
+    match own {
+        Some(mut policy) => {
+            for d in &global.deny {
+                if !policy.deny.contains(d) {
+                    policy.deny.push(d.clone());
+                }
+            }
+            Effective { server, policy, unavailable: None }
+        }
+        None => Effective { server: None, policy: global.clone(), unavailable: None },
+    }
+}
+
+impl Effective {
+    fn deny(&self, rule: &'static str, pattern: Option<&str>, subject: &str, error: String) -> PolicyDenied {
+        PolicyDenied { error, server: self.server.clone(), rule, pattern: pattern.map(|p| p.to_string()), subject: subject.to_string() }
+    }
+
+    fn available(&self, subject: &str) -> Result<(), PolicyDenied> {
+        match &self.unavailable {
+            Some(e) => Err(self.deny("unavailable", None, subject, e.clone())),
+            None => Ok(()),
+        }
+    }
+
+    pub fn check_command(&self, command: &str) -> Result<(), PolicyDenied> {
+        self.available(command.trim())?;
+        let p = &self.policy;
+        let whole = normalize(command);
+        let parts = segments(command);
+        for pattern in &p.deny {
+            let hit = std::iter::once(whole.clone())
+                .chain(parts.iter().flat_map(|s| [s.clone(), unwrap(&unquote(s))]))
+                .find(|s| glob(&normalize(pattern), &normalize(s)));
+            if let Some(s) = hit {
+                return Err(self.deny("deny", Some(pattern), &s, format!("command denied by policy: matches {:?}", pattern)));
+            }
+        }
+        if !p.allow.is_empty() {
+            if command.contains('`') || command.contains("$(") || command.contains("<(") || command.contains(">(") {
+                return Err(self.deny("allow", None, command.trim(), "command substitution is not allowed by policy".into()));
+            }
+            if let Some(s) = parts.iter().find(|s| !p.allow.iter().any(|a| glob(&normalize(a), s))) {
+                return Err(self.deny("allow", None, s, format!("command not allowed by policy: {:?}", s)));
+            }
+        }
+        if p.read_only {
+            if let Some(s) = parts.iter().find(|s| !is_read_only(s)) {
+                return Err(self.deny("read_only", None, s, format!("read-only policy: {:?} may change the host", s)));
+            }
+            if command.contains('`') || command.contains("$(") {
+                return Err(self.deny("read_only", None, command.trim(), "read-only policy: command substitution is not allowed".into()));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn check_action(&self, pid: &str, action: &str) -> Result<(), PolicyDenied> {
+        self.available(action)?;
+        // pid подставляется в shell-команду relay как есть
+        if pid.is_empty() || !pid.chars().all(|c| c.is_ascii_digit()) {
+            return Err(self.deny("pid", None, pid, format!("invalid pid {:?}", pid)));
+        }
+        if self.policy.read_only {
+            return Err(self.deny("read_only", None, action, "read-only policy: process actions are disabled".into()));
+        }
+        if let Some(actions) = &self.policy.actions {
+            if !actions.iter().any(|a| a == action) {
+                return Err(self.deny("action", None, action, format!("process action {:?} is not allowed by policy", action)));
+            }
+        }
+        Ok(())
+    }
+
+    // В интерактивном shell команды не проверить, поэтому его нет при read_only и allow
+    pub fn check_terminal(&self) -> Result<(), PolicyDenied> {
+        self.available("terminal")?;
+        if self.policy.read_only || !self.policy.allow.is_empty() {
+            return Err(self.deny("terminal", None, "terminal", "interactive terminal is disabled by policy".into()));
+        }
+        Ok(())
+    }
+
+    // path и roots — канонические пути с хоста (realpath): .. и симлинки уже раскрыты
+    pub fn check_path(&self, path: &str, roots: &[String]) -> Result<(), PolicyDenied> {
+        self.available(path)?;
+        let inside = |root: &String| root == "/" || path == root || path.strip_prefix(root.as_str()).is_some_and(|rest| rest.starts_with('/'));
+        if roots.iter().any(inside) {
+            return Ok(());
+        }
+        Err(self.deny("files", None, path, format!("path {} is outside allowed directories: {}", path, roots.join(", "))))
+    }
+
+    pub fn check_unit(&self, unit: &str) -> Result<(), PolicyDenied> {
+        self.available(unit)?;
+        if self.policy.units.iter().any(|u| glob(u, unit)) {
+            return Ok(());
+        }
+        if self.policy.units.is_empty() {
+            return Err(self.deny("units", None, unit, "unit logs are disabled by policy: list allowed units in units".into()));
+        }
+        Err(self.deny("units", None, unit, format!("unit {} is not allowed by policy: {}", unit, self.policy.units.join(", "))))
+    }
+
+    #[cfg_attr(not(feature = "native-ssh"), allow(dead_code))]
+    pub fn check_upload(&self, path: &str) -> Result<(), PolicyDenied> {
+        self.available(path)?;
+        if self.policy.read_only {
+            return Err(self.deny("read_only", None, path, "read-only policy: file uploads are disabled".into()));
+        }
+        Ok(())
+    }
+}
+
+fn normalize(s: &str) -> String {
+    s.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+// Без кавычек и экранирования: r''m -rf / — тоже rm -rf /
+fn unquote(s: &str) -> String {
+    normalize(&s.replace(['\'', '"', '\\'], ""))
+}
+
+// Без обёрток и присваиваний: sudo -u root FOO=1 rm -rf / -> rm -rf /
+fn unwrap(s: &str) -> String {
+    let words: Vec<&str> = s.split_whitespace().collect();
+    let mut i = 0;
+    // Обёртка, чьи флаги сейчас пропускаются
+    let mut wrapper: Option<&str> = None;
+    // timeout 5 cmd: первый аргумент timeout — срок
+    let mut duration = false;
+    while i < words.len() {
+        let w = words[i];
+        if WRAPPERS.contains(&w) {
+            wrapper = Some(w);
+            duration = w == "timeout";
+            i += 1;
+        } else if duration && !w.starts_with('-') {
+            duration = false;
+            i += 1;
+        } else if let Some(wr) = wrapper.filter(|_| w.starts_with('-')) {
+            let takes_value = WRAPPER_VALUE_FLAGS.iter().any(|(name, flags)| *name == wr && flags.contains(&w));
+            i += if takes_value { 2 } else { 1 };
+        } else if w.contains('=') && !w.starts_with('=') {
+            i += 1;
+        } else {
+            break;
+        }
+    }
+    words[i.min(words.len())..].join(" ")
+}
+
+// Простые команды из ; && || | & и подстановок $(...), `...`
+fn segments(command: &str) -> Vec<String> {
+    let mut out = vec![];
+    let mut cur = String::new();
+    let mut chars = command.chars().peekable();
+    while let Some(c) = chars.next() {
+        let split = match c {
+            ';' | '|' | '&' | '\n' | '`' | '(' | ')' => true,
+            '$' if chars.peek() == Some(&'(') => true,
+            _ => false,
+        };
+        if split {
+            // 2>&1, >&2 и &>file — перенаправление, а не &
+            if c == '&' && (cur.ends_with('>') || chars.peek() == Some(&'>')) {
+                cur.push(c);
+                continue;
+            }
+            out.push(normalize(&cur));
+            cur.clear();
+        } else {
+            cur.push(c);
+        }
+    }
+    out.push(normalize(&cur));
+    out.retain(|s| !s.is_empty());
+    out
+}
+
+fn is_read_only(segment: &str) -> bool {
+    let words: Vec<&str> = segment.split_whitespace().collect();
+    // Перенаправление в файл пишет на хост; в /dev/null и между потоками — нет
+    for (i, w) in words.iter().enumerate() {
+        if let Some(pos) = w.find('>') {
+            let target = &w[pos + 1..];
+            let target = if target.is_empty() { words.get(i + 1).copied().unwrap_or("") } else { target };
+            if !(target == "/dev/null" || target.starts_with('&')) {
+                return false;
+            }
+        }
+    }
+    let Some(program) = words.first() else { return true };
+    let program = program.rsplit('/').next().unwrap_or(program);
+    if !READ_ONLY_COMMANDS.contains(&program) {
+        return false;
+    }
+    let args = &words[1..];
+    if args.iter().any(|w| WRITING_ARGS.iter().any(|a| w == a || (a.starts_with("--") && w.starts_with(a)))) {
+        return false;
+    }
+    match program {
+        // systemctl --no-pager status x; service x status
+        "systemctl" => args.iter().find(|w| !w.starts_with('-')).is_some_and(|sub| READ_ONLY_SYSTEMCTL.contains(sub)),
+        "service" => words.get(2).is_some_and(|sub| *sub == "status") || words.get(1) == Some(&"--status-all"),
+        // date +%s — формат; date 0101... и -s ставят часы
+        "date" => args.iter().all(|w| w.starts_with('+') || w.starts_with("-I") || w.starts_with("--iso-8601") || READ_ONLY_DATE_ARGS.contains(w)),
+        "hostname" => args.iter().all(|w| READ_ONLY_HOSTNAME_ARGS.contains(w)),
+        // -o FILE, -oFILE, -ro FILE, --output=FILE
+        "sort" => !args.iter().any(|w| w.starts_with("--o") || (w.starts_with('-') && !w.starts_with("--") && w.contains('o'))),
+        // uniq IN OUT пишет в OUT
+        "uniq" => positional(args, &["-f", "-s", "-w"]).len() < 2,
+        // -fprint*, -fls пишут в файл
+        "find" => !args.iter().any(|w| w.starts_with("-fprint") || w.starts_with("-fls")),
+        // ss -K закрывает сокеты
+        "ss" => !args.iter().any(|w| *w == "--kill" || (w.starts_with('-') && !w.starts_with("--") && w.contains('K'))),
+        _ => true,
+    }
+}
+
+// Аргументы без флагов; у with_value значение идёт следующим словом
+fn positional<'a>(args: &[&'a str], with_value: &[&str]) -> Vec<&'a str> {
+    let mut out = vec![];
+    let mut skip = false;
+    for w in args {
+        if skip {
+            skip = false;
+        } else if with_value.contains(w) {
+            skip = true;
+        } else if !w.starts_with('-') || *w == "-" {
+            out.push(*w);
+        }
+    }
+    out
+}
+
+// * — любая последовательность, ? — один символ
+fn glob(pattern: &str, text: &str) -> bool {
+    let p: Vec<char> = pattern.chars().collect();
+    let t: Vec<char> = text.chars().collect();
+    let (mut pi, mut ti) = (0, 0);
+    let mut star: Option<(usize, usize)> = None;
+    while ti < t.len() {
+        if pi < p.len() && (p[pi] == '?' || p[pi] == t[ti]) {
+            pi += 1;
+            ti += 1;
+        } else if pi < p.len() && p[pi] == '*' {
+            star = Some((pi, ti));
+            pi += 1;
+        } else if let Some((sp, st)) = star {
+            pi = sp + 1;
+            ti = st + 1;
+            star = Some((sp, st + 1));
+        } else {
+            return false;
+        }
+    }
+    p[pi..].iter().all(|&c| c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy(p: CommandPolicy) -> Effective {
+        Effective { server: None, policy: p, unavailable: None }
+    }
+
+    #[test]
+    fn segments_split_on_operators_and_substitutions() {
+        assert_eq!(segments("ls -l; rm -rf /tmp/x && echo ok"), ["ls -l", "rm -rf /tmp/x", "echo ok"]);
+        assert_eq!(segments("ps aux | grep nginx || true"), ["ps aux", "grep nginx", "true"]);
+        assert_eq!(segments("echo $(whoami) `id`"), ["echo", "whoami", "id"]);
+        assert_eq!(segments("sleep 1 &\nuptime"), ["sleep 1", "uptime"]);
+    }
+
+    #[test]
+    fn segments_keep_redirections() {
+        assert_eq!(segments("cmd 2>&1 | tail"), ["cmd 2>&1", "tail"]);
+        assert_eq!(segments("cmd &>/dev/null"), ["cmd &>/dev/null"]);
+        assert_eq!(segments("cmd >&2"), ["cmd >&2"]);
+    }
+
+    #[test]
+    fn unwrap_strips_wrappers_and_assignments() {
+        assert_eq!(unwrap("sudo FOO=1 rm -rf /"), "rm -rf /");
+        assert_eq!(unwrap("sudo -u root env A=b nice -n 5 reboot"), "reboot");
+        assert_eq!(unwrap("timeout 5 ls"), "ls");
+        assert_eq!(unwrap("timeout -s KILL 5 ls"), "ls");
+        assert_eq!(unwrap("xargs -I {} rm {}"), "rm {}");
+        assert_eq!(unwrap("ls -l"), "ls -l");
+        assert_eq!(unwrap("sudo"), "");
+    }
+
+    #[test]
+    fn glob_matches_star_and_question_mark() {
+        assert!(glob("rm *", "rm -rf /"));
+        assert!(glob("*", ""));
+        assert!(glob("systemctl ?top *", "systemctl stop nginx"));
+        assert!(glob("a*b*c", "aXXbYYc"));
+        assert!(!glob("a*b*c", "aXXbYY"));
+        assert!(!glob("rm *", "ls rm x"));
+        assert!(!glob("ls", "ls -l"));
+    }
+
+    #[test]
+    fn read_only_allows_viewing_commands() {
+        for cmd in [
+            "ls -la /var/log", "cat /etc/hostname", "ps aux", "df -h", "tail -n 100 /var/log/syslog",
+            "grep -r error /var/log 2>/dev/null", "systemctl status nginx", "systemctl --no-pager status nginx",
+            "service nginx status", "date", "date +%s", "date -u", "date -Iseconds", "hostname", "hostname -f",
+            "sort -n file", "sort -rn -k2 file", "uniq -c file", "uniq -f 1 file", "find /var -name '*.log'",
+            "journalctl -u nginx -n 50", "ss -tlnp",
+        ] {
+            assert!(is_read_only(cmd), "{} should be read-only", cmd);
+        }
+    }
+
+    #[test]
+    fn read_only_rejects_writers() {
+        for cmd in [
+            "rm -rf /tmp/x", "echo x > /etc/motd", "date -s '2020-01-01'", "date 010100002020", "hostname newname",
+            "hostname -F /etc/hostname", "sort -o out file", "sort -oout file", "sort -ro out file", "sort --output=out file",
+            "uniq in out", "uniq -c in out", "find / -fprint0 out", "find / -fprint out", "find / -fprintf out %p",
+            "find / -fls out", "find / -delete", "find / -exec rm {} ;", "systemctl", "systemctl restart nginx",
+            "systemctl --now stop nginx", "service nginx restart", "journalctl --vacuum-size=1M", "ss -K dst 10.0.0.1",
+            "ss -tK", "vim /etc/hosts",
+        ] {
+            assert!(!is_read_only(cmd), "{} should not be read-only", cmd);
+        }
+    }
+
+    #[test]
+    fn check_command_applies_deny_allow_and_read_only() {
+        let deny = policy(CommandPolicy { deny: vec!["rm -rf *".into()], ..Default::default() });
+        assert!(deny.check_command("ls; sudo r''m -rf /").is_err());
+        assert!(deny.check_command("ls -l").is_ok());
+
+        let allow = policy(CommandPolicy { allow: vec!["ls*".into(), "uptime".into()], ..Default::default() });
+        assert!(allow.check_command("ls -l && uptime").is_ok());
+        assert_eq!(allow.check_command("ls | sh").unwrap_err().rule, "allow");
+        assert!(allow.check_command("ls $(reboot)").is_err());
+
+        let read_only = policy(CommandPolicy { read_only: true, ..Default::default() });
+        assert!(read_only.check_command("ps aux | grep nginx").is_ok());
+        assert_eq!(read_only.check_command("date -s now").unwrap_err().rule, "read_only");
+        assert!(read_only.check_command("cat `which x`").is_err());
+    }
+
+    #[test]
+    fn check_path_stays_inside_roots() {
+        let p = policy(CommandPolicy::default());
+        let roots = vec!["/var/log".to_string()];
+        assert!(p.check_path("/var/log", &roots).is_ok());
+        assert!(p.check_path("/var/log/nginx/access.log", &roots).is_ok());
+        assert!(p.check_path("/var/logs", &roots).is_err());
+        assert!(p.check_path("/etc/passwd", &roots).is_err());
+        assert!(p.check_path("/etc/passwd", &["/".to_string()]).is_ok());
+    }
+
+    #[test]
+    fn unavailable_policy_denies_everything() {
+        let p = Effective { server: Some("prod".into()), policy: CommandPolicy::default(), unavailable: Some("broken".into()) };
+        assert_eq!(p.check_command("ls").unwrap_err().rule, "unavailable");
+        assert!(p.check_action("1", "kill").is_err());
+        assert!(p.check_terminal().is_err());
+        assert!(p.check_path("/", &["/".to_string()]).is_err());
+    }
+}
@@ -0,0 +1,733 @@
+// Реестр Go relay-бэкендов: дашборд может работать с несколькими relay на разных хостах
+use axum::{
+    async_trait,
+    extract::{FromRequestParts, Query},
+    http::{header, request::Parts, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use chrono::{DateTime, Utc};
+use reqwest::{Client, Method, RequestBuilder, Url};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::json;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tracing::{debug, error, info, warn};
+
+use crate::config::{Config, RelayClientConfig, RelayKind};
+#[cfg(feature = "native-ssh")]
+use super::native_ssh::NativeRelay;
+use super::metrics;
+use super::ssh_sessions::{SshSessions, SSH_SESSION_HEADER};
+
+// Имя relay можно передать заголовком или ?relay=
+pub const RELAY_HEADER: &str = "x-relay";
+
+pub struct RelayRegistry {
+    backends: Vec<Arc<RelayBackend>>,
+    default: String,
+    http: Client,
+    policy: RelayClientConfig,
+    pub sessions: SshSessions,
+}
+
+// Куда отправить запрос: relay и SSH-сессия на нём
+pub struct RelayTarget {
+    pub backend: Arc<RelayBackend>,
+    // None — запрос без сессии (relay отвечает про своё единственное подключение)
+    pub session: Option<String>,
+    // id сессии пришёл в запросе, а не подставлен последней сессией relay
+    pub explicit: bool,
+}
+
+impl RelayTarget {
+    pub fn relay(backend: Arc<RelayBackend>) -> RelayTarget {
+        RelayTarget { backend, session: None, explicit: false }
+    }
+}
+
+pub struct RelayBackend {
+    pub name: String,
+    pub base_url: String,
+    health_url: String,
+    pub kind: RelayKind,
+    health: Mutex<RelayHealth>,
+    breaker: Mutex<Breaker>,
+    // Встроенный SSH-клиент вместо HTTP-запросов к Go relay
+    #[cfg(feature = "native-ssh")]
+    native: Option<NativeRelay>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BreakerState {
+    Closed,
+    Open,
+    // После cooldown пропускаем один пробный запрос
+    HalfOpen,
+}
+
+#[derive(Clone, Serialize)]
+pub struct Breaker {
+    pub state: BreakerState,
+    pub consecutive_failures: u32,
+    pub total_failures: u64,
+    pub last_error: Option<String>,
+    pub opened_at: Option<DateTime<Utc>>,
+    #[serde(skip)]
+    open_until: Option<Instant>,
+    #[serde(skip)]
+    trial_in_flight: bool,
+}
+
+impl Default for Breaker {
+    fn default() -> Self {
+        Breaker {
+            state: BreakerState::Closed,
+            consecutive_failures: 0,
+            total_failures: 0,
+            last_error: None,
+            opened_at: None,
+            open_until: None,
+            trial_in_flight: false,
+        }
+    }
+}
+
+#[derive(Clone, Default, Serialize)]
+pub struct RelayHealth {
+    // None — ещё ни разу не проверялся
+    pub healthy: Option<bool>,
+    pub last_checked: Option<DateTime<Utc>>,
+    pub latency_ms: Option<u64>,
+    pub error: Option<String>,
+}
+
+impl RelayBackend {
+    pub fn url(&self, path: &str) -> String {
+        format!("{}{}", self.base_url, path)
+    }
+
+    pub fn health(&self) -> RelayHealth {
+        self.health.lock().map(|h| h.clone()).unwrap_or_default()
+    }
+
+    pub fn breaker(&self) -> Breaker {
+        self.breaker.lock().map(|b| b.clone()).unwrap_or_default()
+    }
+
+    #[cfg(feature = "native-ssh")]
+    pub fn native(&self) -> Option<&NativeRelay> {
+        self.native.as_ref()
+    }
+
+    // Можно ли сейчас идти в relay; Err — сколько ждать до следующей попытки
+    fn admit(&self) -> Result<(), Duration> {
+        let Ok(mut b) = self.breaker.lock() else { return Ok(()) };
+        match b.state {
+            BreakerState::Closed => Ok(()),
+            BreakerState::Open => {
+                let now = Instant::now();
+                match b.open_until {
+                    Some(until) if until > now => Err(until - now),
+                    _ => {
+                        info!("relay {} breaker half-open, sending trial request", self.name);
+                        b.state = BreakerState::HalfOpen;
+                        b.trial_in_flight = true;
+                        Ok(())
+                    }
+                }
+            }
+            BreakerState::HalfOpen if b.trial_in_flight => Err(Duration::from_secs(1)),
+            BreakerState::HalfOpen => {
+                b.trial_in_flight = true;
+                Ok(())
+            }
+        }
+    }
+
+    fn record_success(&self) {
+        if let Ok(mut b) = self.breaker.lock() {
+            if b.state != BreakerState::Closed {
+                info!("relay {} breaker closed", self.name);
+            }
+            b.state = BreakerState::Closed;
+            b.consecutive_failures = 0;
+            b.open_until = None;
+            b.trial_in_flight = false;
+        }
+    }
+
+    fn record_failure(&self, policy: &RelayClientConfig, err: &str) {
+        if let Ok(mut b) = self.breaker.lock() {
+            b.consecutive_failures += 1;
+            b.total_failures += 1;
+            b.last_error = Some(err.to_string());
+            b.trial_in_flight = false;
+            let trip = b.state == BreakerState::HalfOpen || b.consecutive_failures >= policy.breaker_failures.max(1);
+            if trip && b.state != BreakerState::Open {
+                warn!("relay {} breaker open after {} failures: {}", self.name, b.consecutive_failures, err);
+                b.state = BreakerState::Open;
+                b.opened_at = Some(Utc::now());
+            }
+            if trip {
+                b.open_until = Some(Instant::now() + Duration::from_secs(policy.breaker_cooldown_secs));
+            }
+        }
+    }
+}
+
+impl RelayRegistry {
+    pub fn from_config(config: &Config, http: Client) -> RelayRegistry {
+        let backends = config.relays.iter().map(|r| {
+            let health_url = r.health_url.clone().unwrap_or_else(|| {
+                Url::parse(&r.url)
+                    .and_then(|u| u.join("/health"))
+                    .map(|u| u.to_string())
+                    .unwrap_or_else(|_| format!("{}/health", r.url))
+            });
+            let native = r.kind == RelayKind::Native;
+            Arc::new(RelayBackend {
+                name: r.name.clone(),
+                base_url: if native { "native".into() } else { r.url.clone() },
+                health_url,
+                kind: r.kind,
+                health: Mutex::new(RelayHealth::default()),
+                breaker: Mutex::new(Breaker::default()),
+                #[cfg(feature = "native-ssh")]
+                native: native.then(|| NativeRelay::new(&r.name, config.native_ssh.clone())),
+            })
+        }).collect();
+        RelayRegistry {
+            backends,
+            default: config.default_relay.clone().unwrap_or_default(),
+            http,
+            policy: config.relay_client.clone(),
+            sessions: SshSessions::new(config.ssh_sessions.clone()),
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<Arc<RelayBackend>> {
+        self.backends.iter().find(|b| b.name == name).cloned()
+    }
+
+    pub fn default_backend(&self) -> Arc<RelayBackend> {
+        self.get(&self.default).unwrap_or_else(|| self.backends[0].clone())
+    }
+
+    pub fn backends(&self) -> &[Arc<RelayBackend>] {
+        &self.backends
+    }
+
+    pub async fn check(&self, backend: &RelayBackend) -> RelayHealth {
+        let started = Instant::now();
+        // Встроенному клиенту проверять нечего: он в этом же процессе
+        let (healthy, error) = if backend.kind == RelayKind::Native {
+            (true, None)
+        } else {
+            match self.http.get(&backend.health_url).timeout(Duration::from_secs(3)).send().await {
+                Ok(r) if r.status().is_success() => (true, None),
+                Ok(r) => (false, Some(format!("status {}", r.status().as_u16()))),
+                Err(e) => (false, Some(e.to_string())),
+            }
+        };
+        let health = RelayHealth {
+            healthy: Some(healthy),
+            last_checked: Some(Utc::now()),
+            latency_ms: Some(started.elapsed().as_millis() as u64),
+            error,
+        };
+        if let Ok(mut h) = backend.health.lock() {
+            if h.healthy != health.healthy {
+                if healthy {
+                    info!("relay {} is healthy ({})", backend.name, backend.base_url);
+                } else {
+                    warn!("relay {} is down: {}", backend.name, health.error.as_deref().unwrap_or(""));
+                }
+            }
+            *h = health.clone();
+        }
+        health
+    }
+
+    pub async fn check_all(&self) {
+        for backend in &self.backends {
+            self.check(backend).await;
+        }
+    }
+
+    // GET к relay с разбором JSON-ответа в типизированную модель
+    pub async fn get_json<T: DeserializeOwned>(&self, target: &RelayTarget, path: &str) -> Result<T, RelayError> {
+        let text = self.call(target, Method::GET, path, None::<&()>).await?;
+        decode(&target.backend, path, &text)
+    }
+
+    pub async fn post_json<T: DeserializeOwned, B: Serialize>(&self, target: &RelayTarget, path: &str, body: Option<&B>) -> Result<T, RelayError> {
+        let text = self.call(target, Method::POST, path, body).await?;
+        decode(&target.backend, path, &text)
+    }
+
+    // Для эндпоинтов, которые отвечают обычным текстом (ssh/execute)
+    pub async fn post_text<B: Serialize>(&self, target: &RelayTarget, path: &str, body: &B) -> Result<String, RelayError> {
+        self.call(target, Method::POST, path, Some(body)).await
+    }
+
+    // Ответ relay без чтения тела — для потоковых маршрутов; общего таймаута нет, только на подключение
+    pub async fn open_stream<B: Serialize>(&self, target: &RelayTarget, path: &str, body: &B) -> Result<reqwest::Response, RelayError> {
+        let backend = &target.backend;
+        metrics::relay_request(&backend.name, path);
+        let result = self.send_stream(target, path, body).await;
+        if let Err(e) = &result {
+            metrics::relay_error(&backend.name, path, e.kind());
+        }
+        result
+    }
+
+    async fn send_stream<B: Serialize>(&self, target: &RelayTarget, path: &str, body: &B) -> Result<reqwest::Response, RelayError> {
+        let backend = &target.backend;
+        if let Err(wait) = backend.admit() {
+            return Err(RelayError::CircuitOpen(backend.name.clone(), (wait.as_secs_f64().ceil() as u64).max(1)));
+        }
+        let mut req = self.http.post(backend.url(path)).json(body);
+        if let Some(session) = &target.session {
+            req = req.header(SSH_SESSION_HEADER, session);
+        }
+        let resp = match req.send().await {
+            Ok(resp) => resp,
+            Err(e) => {
+                error!("relay {} POST {} error: {}", backend.name, path, e);
+                backend.record_failure(&self.policy, &e.to_string());
+                return Err(RelayError::Transport(e.to_string()));
+            }
+        };
+        backend.record_success();
+        let status = resp.status();
+        info!("relay {} POST {}: status={} (stream)", backend.name, path, status.as_u16());
+        if !status.is_success() {
+            let text = resp.text().await.unwrap_or_default();
+            return Err(RelayError::Upstream(status, text.trim().to_string()));
+        }
+        Ok(resp)
+    }
+
+    async fn call<B: Serialize>(&self, target: &RelayTarget, method: Method, path: &str, body: Option<&B>) -> Result<String, RelayError> {
+        let backend = &target.backend;
+        metrics::relay_request(&backend.name, path);
+        let result = self.call_with_retries(target, method, path, body).await;
+        if let Err(e) = &result {
+            metrics::relay_error(&backend.name, path, e.kind());
+        }
+        result
+    }
+
+    async fn call_with_retries<B: Serialize>(&self, target: &RelayTarget, method: Method, path: &str, body: Option<&B>) -> Result<String, RelayError> {
+        let backend = &target.backend;
+        if let Err(wait) = backend.admit() {
+            debug!("relay {} {} {}: breaker open", backend.name, method, path);
+            return Err(RelayError::CircuitOpen(backend.name.clone(), (wait.as_secs_f64().ceil() as u64).max(1)));
+        }
+        let timeout = self.policy.timeout_for(path);
+        // Повторяем только идемпотентные GET
+        let attempts = if method == Method::GET { self.policy.retries + 1 } else { 1 };
+        let mut attempt = 0;
+        loop {
+            attempt += 1;
+            match self.send_once(target, &method, path, body, timeout).await {
+                Err(e) if e.is_retryable() && attempt < attempts => {
+                    let backoff = Duration::from_millis(self.policy.retry_backoff_ms * 2u64.pow(attempt - 1));
+                    warn!("relay {} {} {} attempt {}/{} failed: {}; retrying in {:?}",
+                        backend.name, method, path, attempt, attempts, e.message(), backoff);
+                    tokio::time::sleep(backoff).await;
+                }
+                result => {
+                    // Ответ relay с ошибкой (например, "ssh not connected") означает, что сам relay жив
+                    match &result {
+                        Err(e) if e.is_retryable() => backend.record_failure(&self.policy, &e.message()),
+                        _ => backend.record_success(),
+                    }
+                    return result;
+                }
+            }
+        }
+    }
+
+    async fn send_once<B: Serialize>(&self, target: &RelayTarget, method: &Method, path: &str, body: Option<&B>, timeout: Duration) -> Result<String, RelayError> {
+        let backend = &target.backend;
+        #[cfg(feature = "native-ssh")]
+        if let Some(native) = &backend.native {
+            let body = body.map(serde_json::to_value).transpose()
+                .map_err(|e| RelayError::InvalidQuery(e.to_string()))?;
+            let result = native.handle(method, path, target.session.as_deref(), body, timeout).await;
+            if let Err(e) = &result {
+                info!("relay {} {} {}: {}", backend.name, method, path, e.message());
+            }
+            return result;
+        }
+        let url = backend.url(path);
+        let mut req: RequestBuilder = self.http.request(method.clone(), &url).timeout(timeout);
+        if let Some(session) = &target.session {
+            req = req.header(SSH_SESSION_HEADER, session);
+        }
+        if let Some(body) = body {
+            req = req.json(body);
+        }
+        let resp = req.send().await.map_err(|e| {
+            error!("relay {} {} {} error: {}", backend.name, method, path, e);
+            if e.is_timeout() {
+                RelayError::Timeout(timeout.as_millis() as u64)
+            } else {
+                RelayError::Transport(e.to_string())
+            }
+        })?;
+        let status = resp.status();
+        let text = match resp.text().await {
+            Ok(text) => text,
+            Err(e) if e.is_timeout() => return Err(RelayError::Timeout(timeout.as_millis() as u64)),
+            Err(e) => return Err(RelayError::Transport(e.to_string())),
+        };
+        info!("relay {} {} {}: status={} bytes={}", backend.name, method, path, status.as_u16(), text.len());
+        if !status.is_success() {
+            return Err(RelayError::Upstream(status, text.trim().to_string()));
+        }
+        Ok(text)
+    }
+
+    pub fn spawn_health_checks(self: &Arc<Self>, every: Duration) {
+        if every.is_zero() {
+            return;
+        }
+        let registry = self.clone();
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(every);
+            loop {
+                ticker.tick().await;
+                debug!("relay health check: {} backends", registry.backends.len());
+                registry.check_all().await;
+            }
+        });
+    }
+
+    // Состояние breaker для /rust/relay/health
+    pub fn breakers(&self) -> serde_json::Value {
+        let relays: Vec<_> = self.backends.iter().map(|b| {
+            let breaker = b.breaker();
+            let retry_after = breaker.open_until
+                .map(|until| until.saturating_duration_since(Instant::now()).as_secs_f64().ceil() as u64);
+            json!({
+                "name": b.name,
+                "url": b.base_url,
+                "breaker": breaker,
+                "retry_after": retry_after,
+                "health": b.health(),
+            })
+        }).collect();
+        json!({
+            "relays": relays,
+            "policy": {
+                "breaker_failures": self.policy.breaker_failures,
+                "breaker_cooldown_secs": self.policy.breaker_cooldown_secs,
+                "retries": self.policy.retries,
+            }
+        })
+    }
+
+    fn describe(&self, backend: &RelayBackend) -> serde_json::Value {
+        json!({
+            "name": backend.name,
+            "url": backend.base_url,
+            "kind": backend.kind,
+            "default": backend.name == self.default,
+            "health": backend.health(),
+            "sessions": self.sessions.on_relay(&backend.name),
+        })
+    }
+}
+
+fn decode<T: DeserializeOwned>(backend: &RelayBackend, path: &str, text: &str) -> Result<T, RelayError> {
+    serde_json::from_str(text).map_err(|e| {
+        error!("relay {} {}: invalid response: {}", backend.name, path, e);
+        metrics::relay_error(&backend.name, path, "invalid_response");
+        RelayError::InvalidResponse(e.to_string())
+    })
+}
+
+pub enum RelayError {
+    // relay недоступен
+    Transport(String),
+    // relay не ответил за отведённое время (мс)
+    Timeout(u64),
+    // breaker открыт: имя relay и через сколько секунд пробовать снова
+    CircuitOpen(String, u64),
+    // relay ответил ошибкой — пробрасываем её статус
+    Upstream(StatusCode, String),
+    // ответ relay не совпал с ожидаемой моделью
+    InvalidResponse(String),
+    InvalidQuery(String),
+}
+
+impl RelayError {
+    // Сетевые ошибки и перегрузка relay, а не ответы о неудачной SSH-команде
+    fn is_retryable(&self) -> bool {
+        match self {
+            RelayError::Transport(_) | RelayError::Timeout(_) => true,
+            RelayError::Upstream(status, _) => {
+                *status == StatusCode::SERVICE_UNAVAILABLE || *status == StatusCode::GATEWAY_TIMEOUT
+            }
+            _ => false,
+        }
+    }
+
+    // Метка kind в rust_server_relay_errors_total
+    pub fn kind(&self) -> &'static str {
+        match self {
+            RelayError::Transport(_) => "transport",
+            RelayError::Timeout(_) => "timeout",
+            RelayError::CircuitOpen(..) => "circuit_open",
+            RelayError::Upstream(..) => "upstream",
+            RelayError::InvalidResponse(_) => "invalid_response",
+            RelayError::InvalidQuery(_) => "invalid_query",
+        }
+    }
+
+    pub fn message(&self) -> String {
+        match self {
+            RelayError::Transport(e) => format!("connection error: {}", e),
+            RelayError::Timeout(ms) => format!("relay did not respond within {} ms", ms),
+            RelayError::CircuitOpen(name, secs) => {
+                format!("relay {} is unavailable (circuit open), retry in {}s", name, secs)
+            }
+            RelayError::Upstream(_, body) => body.clone(),
+            RelayError::InvalidResponse(e) => format!("invalid relay response: {}", e),
+            RelayError::InvalidQuery(e) => e.clone(),
+        }
+    }
+}
+
+impl IntoResponse for RelayError {
+    fn into_response(self) -> Response {
+        let msg = self.message();
+        if let RelayError::CircuitOpen(name, secs) = &self {
+            let headers = [(header::RETRY_AFTER, secs.to_string())];
+            let body = json!({"error": msg, "relay": name, "retry_after": secs});
+            return (StatusCode::SERVICE_UNAVAILABLE, headers, Json(body)).into_response();
+        }
+        let status = match self {
+            RelayError::Transport(_) => StatusCode::BAD_GATEWAY,
+            RelayError::Timeout(_) => StatusCode::GATEWAY_TIMEOUT,
+            RelayError::Upstream(status, _) => status,
+            RelayError::InvalidResponse(_) => StatusCode::BAD_GATEWAY,
+            RelayError::InvalidQuery(_) => StatusCode::BAD_REQUEST,
+            RelayError::CircuitOpen(..) => StatusCode::SERVICE_UNAVAILABLE,
+        };
+        (status, Json(json!({"error": msg}))).into_response()
+    }
+}
+
+// Состояние роутера, из которого экстрактор Relay берёт реестр
+pub trait RelayState {
+    fn relays(&self) -> &RelayRegistry;
+}
+
+// Экстрактор: SSH-сессия из X-SSH-Session / ?ssh_session= вместе с её relay, иначе выбранный relay
+// (заголовок X-Relay, затем ?relay=, иначе relay по умолчанию) и его сессия, если она на нём одна.
+// Несколько сессий без id — 409: команда не должна уйти на чужой хост
+pub struct Relay(pub RelayTarget);
+
+// То же без подстановки сессии: connect открывает новую, прогону runbook нужен только relay
+pub struct RelayOnly(pub RelayTarget);
+
+#[derive(Deserialize)]
+struct RelayQuery { relay: Option<String>, ssh_session: Option<String> }
+
+type Rejection = (StatusCode, Json<serde_json::Value>);
+
+fn choose(parts: &Parts, registry: &RelayRegistry) -> Result<RelayTarget, Rejection> {
+    let header = |name: &str| parts.headers.get(name)
+        .and_then(|v| v.to_str().ok())
+        .map(|s| s.to_string());
+    let query = Query::<RelayQuery>::try_from_uri(&parts.uri).map(|q| q.0).ok();
+    let not_found = |msg: String| (StatusCode::NOT_FOUND, Json(json!({"error": msg})));
+
+    let session = header(SSH_SESSION_HEADER)
+        .or_else(|| query.as_ref().and_then(|q| q.ssh_session.clone()))
+        .filter(|s| !s.is_empty());
+    if let Some(id) = session {
+        let info = registry.sessions.touch(&id).ok_or_else(|| not_found(format!("unknown ssh session: {}", id)))?;
+        let backend = registry.get(&info.relay).ok_or_else(|| not_found(format!("unknown relay: {}", info.relay)))?;
+        return Ok(RelayTarget { backend, session: Some(id), explicit: true });
+    }
+
+    let name = header(RELAY_HEADER)
+        .or_else(|| query.and_then(|q| q.relay))
+        .filter(|n| !n.is_empty());
+    let backend = match name {
+        None => registry.default_backend(),
+        Some(name) => registry.get(&name).ok_or_else(|| not_found(format!("unknown relay: {}", name)))?,
+    };
+    Ok(RelayTarget::relay(backend))
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for Relay
+where
+    S: RelayState + Send + Sync,
+{
+    type Rejection = Rejection;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let registry = state.relays();
+        let mut target = choose(parts, registry)?;
+        if target.explicit {
+            return Ok(Relay(target));
+        }
+        let sessions = registry.sessions.on_relay(&target.backend.name);
+        match sessions.as_slice() {
+            [] => {}
+            [id] => {
+                registry.sessions.touch(id);
+                target.session = Some(id.clone());
+            }
+            _ => {
+                let msg = format!("several ssh sessions on relay {}, pass X-SSH-Session", target.backend.name);
+                return Err((StatusCode::CONFLICT, Json(json!({"error": msg, "sessions": sessions}))));
+            }
+        }
+        Ok(Relay(target))
+    }
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for RelayOnly
+where
+    S: RelayState + Send + Sync,
+{
+    type Rejection = Rejection;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        choose(parts, state.relays()).map(RelayOnly)
+    }
+}
+
+#[derive(Deserialize)]
+pub struct ListRelaysQuery { pub check: Option<bool> }
+
+// GET /rust/relays[?check=true] — список relay и их состояние
+pub async fn list_relays(registry: &RelayRegistry, check: bool) -> Json<serde_json::Value> {
+    if check {
+        registry.check_all().await;
+    }
+    let relays: Vec<_> = registry.backends().iter().map(|b| registry.describe(b)).collect();
+    Json(json!({"default": registry.default, "relays": relays}))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{extract::State, routing::any, Router};
+    use crate::config::RelayConfig;
+    use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+
+    // Заглушка Go relay: считает запросы, отвечает 503, пока failing, и задерживает ответ на delay_ms
+    #[derive(Default)]
+    struct Stub {
+        hits: AtomicUsize,
+        failing: AtomicBool,
+        delay_ms: AtomicU64,
+    }
+
+    async fn stub_relay() -> (String, Arc<Stub>) {
+        let stub = Arc::new(Stub::default());
+        let app = Router::new()
+            .route("/go/*path", any(|State(stub): State<Arc<Stub>>| async move {
+                stub.hits.fetch_add(1, Ordering::SeqCst);
+                tokio::time::sleep(Duration::from_millis(stub.delay_ms.load(Ordering::SeqCst))).await;
+                if stub.failing.load(Ordering::SeqCst) {
+                    (StatusCode::SERVICE_UNAVAILABLE, "relay overloaded")
+                } else {
+                    (StatusCode::OK, "{\"ok\":true}")
+                }
+            }))
+            .with_state(stub.clone());
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/go", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        (url, stub)
+    }
+
+    fn registry(url: &str, retries: u32, breaker_failures: u32) -> RelayRegistry {
+        let config = Config {
+            relays: vec![RelayConfig { name: "go".into(), url: url.into(), kind: RelayKind::Http, health_url: None }],
+            default_relay: Some("go".into()),
+            relay_client: RelayClientConfig { retries, retry_backoff_ms: 1, breaker_failures, breaker_cooldown_secs: 1, ..RelayClientConfig::default() },
+            ..Config::default()
+        };
+        RelayRegistry::from_config(&config, Client::new())
+    }
+
+    async fn get(registry: &RelayRegistry) -> Result<String, RelayError> {
+        registry.call(&RelayTarget::relay(registry.default_backend()), Method::GET, "/data/resources", None::<&()>).await
+    }
+
+    fn state(registry: &RelayRegistry) -> BreakerState {
+        registry.default_backend().breaker().state
+    }
+
+    #[tokio::test]
+    async fn get_is_retried_and_post_is_not() {
+        let (url, stub) = stub_relay().await;
+        let registry = registry(&url, 2, 100);
+        stub.failing.store(true, Ordering::SeqCst);
+
+        assert!(matches!(get(&registry).await, Err(RelayError::Upstream(StatusCode::SERVICE_UNAVAILABLE, _))));
+        assert_eq!(stub.hits.swap(0, Ordering::SeqCst), 3);
+
+        let target = RelayTarget::relay(registry.default_backend());
+        let result = registry.post_text(&target, "/ssh/execute", &json!({"command": "uptime"})).await;
+        assert!(matches!(result, Err(RelayError::Upstream(StatusCode::SERVICE_UNAVAILABLE, _))));
+        assert_eq!(stub.hits.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn breaker_opens_short_circuits_and_recovers_through_one_trial() {
+        let (url, stub) = stub_relay().await;
+        let registry = registry(&url, 0, 2);
+        stub.failing.store(true, Ordering::SeqCst);
+
+        get(&registry).await.err().unwrap();
+        assert_eq!(state(&registry), BreakerState::Closed);
+        get(&registry).await.err().unwrap();
+        assert_eq!(state(&registry), BreakerState::Open);
+        assert_eq!(stub.hits.swap(0, Ordering::SeqCst), 2);
+
+        // Открытый breaker отвечает 503 сам, не обращаясь к relay
+        let Err(e) = get(&registry).await else { panic!("breaker let the request through") };
+        assert!(matches!(e, RelayError::CircuitOpen(_, _)));
+        assert_eq!(e.into_response().status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(stub.hits.load(Ordering::SeqCst), 0);
+
+        // Неудачный пробный запрос после cooldown снова открывает breaker
+        tokio::time::sleep(Duration::from_millis(1100)).await;
+        get(&registry).await.err().unwrap();
+        assert_eq!(stub.hits.swap(0, Ordering::SeqCst), 1);
+        assert_eq!(state(&registry), BreakerState::Open);
+        assert!(matches!(get(&registry).await, Err(RelayError::CircuitOpen(_, _))));
+
+        // Пока идёт пробный запрос, остальные получают отказ; удачный закрывает breaker
+        tokio::time::sleep(Duration::from_millis(1100)).await;
+        stub.failing.store(false, Ordering::SeqCst);
+        stub.delay_ms.store(300, Ordering::SeqCst);
+        let (trial, concurrent) = tokio::join!(get(&registry), async {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            assert_eq!(state(&registry), BreakerState::HalfOpen);
+            get(&registry).await
+        });
+        assert!(trial.is_ok());
+        assert!(matches!(concurrent, Err(RelayError::CircuitOpen(_, _))));
+        assert_eq!(stub.hits.swap(0, Ordering::SeqCst), 1);
+        assert_eq!(state(&registry), BreakerState::Closed);
+
+        stub.delay_ms.store(0, Ordering::SeqCst);
+        assert!(get(&registry).await.is_ok());
+        assert_eq!(registry.default_backend().breaker().consecutive_failures, 0);
+    }
+}
@@ -0,0 +1,360 @@
+// Типизированные ответы Go relay (servers/go/relay.go) и общая фильтрация/сортировка/пагинация
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+
+use super::relay::RelayError;
+
+// Go отдаёт числа в ps как строки ("12.5"), поэтому храним как есть и парсим при сортировке
+fn num(s: &str) -> f64 {
+    s.trim().parse().unwrap_or(0.0)
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ProcessRow {
+    pub pid: String,
+    pub user: String,
+    pub name: String,
+    #[serde(default)]
+    pub cmd: String,
+    pub cpu: String,
+    // МБ
+    pub rss: String,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ProcessList { pub rows: Vec<ProcessRow> }
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct PortRow {
+    pub proto: String,
+    pub local: String,
+    #[serde(default)]
+    pub pid: String,
+    #[serde(default)]
+    pub proc: String,
+}
+
+impl PortRow {
+    pub fn port(&self) -> f64 {
+        self.local.rsplit(':').next().map(num).unwrap_or(0.0)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct PortList { pub rows: Vec<PortRow> }
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ConnectionRow {
+    pub proto: String,
+    pub local: String,
+    pub peer: String,
+    pub state: String,
+    #[serde(default)]
+    pub pid: String,
+    #[serde(default)]
+    pub proc: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ServiceRow {
+    pub name: String,
+    pub load: String,
+    pub active: String,
+    pub sub: String,
+    #[serde(default)]
+    pub description: String,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ServiceList { pub services: Vec<ServiceRow> }
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct DiskUsageRow {
+    // Вывод du -sh: "1.2G", "640K", ...
+    pub size: String,
+    pub path: String,
+}
+
+impl DiskUsageRow {
+    pub fn size_bytes(&self) -> f64 {
+        let s = self.size.trim();
+        let (digits, unit) = s.split_at(s.find(|c: char| c.is_ascii_alphabetic()).unwrap_or(s.len()));
+        let mult = match unit.chars().next().map(|c| c.to_ascii_uppercase()) {
+            Some('K') => 1024f64,
+            Some('M') => 1024f64.powi(2),
+            Some('G') => 1024f64.powi(3),
+            Some('T') => 1024f64.powi(4),
+            Some('P') => 1024f64.powi(5),
+            _ => 1.0,
+        };
+        num(&digits.replace(',', ".")) * mult
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct DiskUsage { pub directories: Vec<DiskUsageRow> }
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct UserRow {
+    pub user: String,
+    #[serde(default)]
+    pub tty: String,
+    #[serde(default)]
+    pub time: String,
+    #[serde(default)]
+    pub ip: String,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct UserList { pub users: Vec<UserRow> }
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct SystemInfo {
+    pub uptime: String,
+    pub distro: String,
+    pub kernel: String,
+    pub arch: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct CpuStats { pub usage: f64, pub load: f64 }
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct GpuStats { pub usage: f64 }
+
+// ГБ
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct MemStats { pub used: f64, pub total: f64 }
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct DiskStats { pub util: f64, pub read: f64, pub write: f64 }
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct Resources {
+    pub cpu: CpuStats,
+    pub gpu: GpuStats,
+    pub ram: MemStats,
+    pub vram: MemStats,
+    pub ssd: DiskStats,
+}
+
+// Мбит/с
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct NetworkSample { pub t: i64, pub rx: f64, pub tx: f64 }
+
+pub type ProcessLogs = BTreeMap<String, Vec<String>>;
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SshConnectResult {
+    #[serde(default)]
+    pub ok: bool,
+    pub connected: bool,
+    #[serde(default)]
+    pub message: String,
+    // Индекс hop'а, на котором оборвалась цепочка: jump host'ы по порядку, затем цель
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub failed_hop: Option<usize>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SshDisconnectResult {
+    #[serde(default)]
+    pub ok: bool,
+    pub connected: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct SshStatus {
+    pub connected: bool,
+    #[serde(default)]
+    pub host: String,
+    #[serde(default)]
+    pub user: String,
+    #[serde(default)]
+    pub current_dir: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ProcessActionResult {
+    pub success: bool,
+    pub action: String,
+    pub pid: String,
+    #[serde(default)]
+    pub output: String,
+}
+
+pub enum SortKey<'a> {
+    Num(f64),
+    Text(&'a str),
+}
+
+impl SortKey<'_> {
+    fn cmp(&self, other: &SortKey) -> Ordering {
+        match (self, other) {
+            (SortKey::Num(a), SortKey::Num(b)) => a.partial_cmp(b).unwrap_or(Ordering::Equal),
+            (SortKey::Text(a), SortKey::Text(b)) => a.to_lowercase().cmp(&b.to_lowercase()),
+            (SortKey::Num(_), SortKey::Text(_)) => Ordering::Less,
+            (SortKey::Text(_), SortKey::Num(_)) => Ordering::Greater,
+        }
+    }
+}
+
+// Строка таблицы, которую можно фильтровать (?q=) и сортировать (?sort=)
+pub trait Row: Serialize {
+    const SORT_FIELDS: &'static [&'static str];
+    fn sort_key(&self, field: &str) -> Option<SortKey<'_>>;
+    fn text(&self) -> Vec<&str>;
+}
+
+impl Row for ProcessRow {
+    const SORT_FIELDS: &'static [&'static str] = &["pid", "user", "name", "cmd", "cpu", "rss"];
+    fn sort_key(&self, field: &str) -> Option<SortKey<'_>> {
+        Some(match field {
+            "pid" => SortKey::Num(num(&self.pid)),
+            "user" => SortKey::Text(&self.user),
+            "name" => SortKey::Text(&self.name),
+            "cmd" => SortKey::Text(&self.cmd),
+            "cpu" => SortKey::Num(num(&self.cpu)),
+            "rss" => SortKey::Num(num(&self.rss)),
+            _ => return None,
+        })
+    }
+    fn text(&self) -> Vec<&str> { vec![&self.pid, &self.user, &self.name, &self.cmd] }
+}
+
+impl Row for PortRow {
+    const SORT_FIELDS: &'static [&'static str] = &["proto", "local", "port", "pid", "proc"];
+    fn sort_key(&self, field: &str) -> Option<SortKey<'_>> {
+        Some(match field {
+            "proto" => SortKey::Text(&self.proto),
+            "local" => SortKey::Text(&self.local),
+            "port" => SortKey::Num(self.port()),
+            "pid" => SortKey::Num(num(&self.pid)),
+            "proc" => SortKey::Text(&self.proc),
+            _ => return None,
+        })
+    }
+    fn text(&self) -> Vec<&str> { vec![&self.proto, &self.local, &self.pid, &self.proc] }
+}
+
+impl Row for ConnectionRow {
+    const SORT_FIELDS: &'static [&'static str] = &["proto", "local", "peer", "state", "pid", "proc"];
+    fn sort_key(&self, field: &str) -> Option<SortKey<'_>> {
+        Some(match field {
+            "proto" => SortKey::Text(&self.proto),
+            "local" => SortKey::Text(&self.local),
+            "peer" => SortKey::Text(&self.peer),
+            "state" => SortKey::Text(&self.state),
+            "pid" => SortKey::Num(num(&self.pid)),
+            "proc" => SortKey::Text(&self.proc),
+            _ => return None,
+        })
+    }
+    fn text(&self) -> Vec<&str> { vec![&self.proto, &self.local, &self.peer, &self.state, &self.proc] }
+}
+
+impl Row for ServiceRow {
+    const SORT_FIELDS: &'static [&'static str] = &["name", "load", "active", "sub", "description"];
+    fn sort_key(&self, field: &str) -> Option<SortKey<'_>> {
+        Some(match field {
+            "name" => SortKey::Text(&self.name),
+            "load" => SortKey::Text(&self.load),
+            "active" => SortKey::Text(&self.active),
+            "sub" => SortKey::Text(&self.sub),
+            "description" => SortKey::Text(&self.description),
+            _ => return None,
+        })
+    }
+    fn text(&self) -> Vec<&str> { vec![&self.name, &self.active, &self.sub, &self.description] }
+}
+
+impl Row for DiskUsageRow {
+    const SORT_FIELDS: &'static [&'static str] = &["path", "size"];
+    fn sort_key(&self, field: &str) -> Option<SortKey<'_>> {
+        Some(match field {
+            "path" => SortKey::Text(&self.path),
+            "size" => SortKey::Num(self.size_bytes()),
+            _ => return None,
+        })
+    }
+    fn text(&self) -> Vec<&str> { vec![&self.path] }
+}
+
+impl Row for UserRow {
+    const SORT_FIELDS: &'static [&'static str] = &["user", "tty", "time", "ip"];
+    fn sort_key(&self, field: &str) -> Option<SortKey<'_>> {
+        Some(match field {
+            "user" => SortKey::Text(&self.user),
+            "tty" => SortKey::Text(&self.tty),
+            "time" => SortKey::Text(&self.time),
+            "ip" => SortKey::Text(&self.ip),
+            _ => return None,
+        })
+    }
+    fn text(&self) -> Vec<&str> { vec![&self.user, &self.tty, &self.ip] }
+}
+
+impl Row for String {
+    const SORT_FIELDS: &'static [&'static str] = &["value"];
+    fn sort_key(&self, field: &str) -> Option<SortKey<'_>> {
+        (field == "value").then_some(SortKey::Text(self))
+    }
+    fn text(&self) -> Vec<&str> { vec![self] }
+}
+
+// ?q=nginx&sort=cpu&order=desc&offset=0&limit=20
+#[derive(Deserialize, Default)]
+pub struct ListQuery {
+    pub q: Option<String>,
+    pub sort: Option<String>,
+    pub order: Option<String>,
+    pub offset: Option<usize>,
+    pub limit: Option<usize>,
+}
+
+pub struct Page<T> {
+    pub rows: Vec<T>,
+    // Количество строк после фильтра, до пагинации
+    pub total: usize,
+}
+
+impl ListQuery {
+    pub fn apply<T: Row>(&self, mut rows: Vec<T>) -> Result<Page<T>, RelayError> {
+        if let Some(q) = self.q.as_deref().map(str::to_lowercase).filter(|q| !q.is_empty()) {
+            rows.retain(|r| r.text().iter().any(|t| t.to_lowercase().contains(&q)));
+        }
+
+        if let Some(field) = self.sort.as_deref().filter(|s| !s.is_empty()) {
+            if !T::SORT_FIELDS.contains(&field) {
+                return Err(RelayError::InvalidQuery(format!(
+                    "unknown sort field {}, expected one of: {}",
+                    field,
+                    T::SORT_FIELDS.join(", ")
+                )));
+            }
+            let numeric = matches!(rows.first().and_then(|r| r.sort_key(field)), Some(SortKey::Num(_)));
+            // Числа по умолчанию по убыванию (top CPU), строки — по возрастанию
+            let desc = match self.order.as_deref() {
+                Some("asc") => false,
+                Some("desc") => true,
+                Some(other) => return Err(RelayError::InvalidQuery(format!("invalid order {}, expected asc or desc", other))),
+                None => numeric,
+            };
+            rows.sort_by(|a, b| {
+                let (ka, kb) = (a.sort_key(field), b.sort_key(field));
+                let ord = match (ka, kb) {
+                    (Some(ka), Some(kb)) => ka.cmp(&kb),
+                    _ => Ordering::Equal,
+                };
+                if desc { ord.reverse() } else { ord }
+            });
+        }
+
+        let total = rows.len();
+        let offset = self.offset.unwrap_or(0).min(total);
+        let limit = self.limit.unwrap_or(total);
+        let rows = rows.into_iter().skip(offset).take(limit).collect();
+        Ok(Page { rows, total })
+    }
+}
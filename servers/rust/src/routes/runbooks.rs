@@ -0,0 +1,542 @@
+// Runbook'и: именованные последовательности команд, которые выполняются на сохранённых серверах параллельно.
+// Каждый хост — своя SSH-сессия на выбранном relay, шаги идут по очереди, ошибка шага останавливает хост
+use axum::{http::StatusCode, response::{IntoResponse, Response}, Json};
+use chrono::{DateTime, Utc};
+use futures_util::StreamExt;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::{HashSet, VecDeque};
+use std::fs;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
+
+use crate::config::{CommandPolicy, RelayKind, RunbooksConfig};
+use super::audit::{exit_status_of, AuditEntry, AuditLog, AuditOutcome, ClientAddr};
+use super::policy;
+use super::relay::{RelayBackend, RelayRegistry, RelayTarget};
+use super::relay_models::{SshConnectResult, SshDisconnectResult};
+use super::servers::{Credentials, ServerStore, VaultError};
+use super::session_store::write_atomic;
+use super::ssh_sessions::{self, SshSessionInfo};
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct RunbookStep {
+    pub command: String,
+    // Ошибка этого шага не останавливает хост
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub continue_on_error: bool,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Runbook {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub description: String,
+    pub steps: Vec<RunbookStep>,
+    #[serde(default = "Utc::now")]
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+struct RunbooksFile {
+    runbooks: Vec<Runbook>,
+}
+
+pub enum RunbookError {
+    NotFound(String),
+    Invalid(String),
+    Storage(String),
+    // Задание ещё выполняется
+    Busy(String),
+    Vault(VaultError),
+}
+
+impl RunbookError {
+    pub fn message(&self) -> String {
+        match self {
+            RunbookError::NotFound(msg) | RunbookError::Invalid(msg) | RunbookError::Storage(msg) | RunbookError::Busy(msg) => msg.clone(),
+            RunbookError::Vault(e) => e.message(),
+        }
+    }
+}
+
+impl IntoResponse for RunbookError {
+    fn into_response(self) -> Response {
+        let status = match self {
+            RunbookError::NotFound(_) => StatusCode::NOT_FOUND,
+            RunbookError::Invalid(_) => StatusCode::BAD_REQUEST,
+            RunbookError::Storage(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            RunbookError::Busy(_) => StatusCode::CONFLICT,
+            RunbookError::Vault(e) => return e.into_response(),
+        };
+        (status, Json(json!({"error": self.message()}))).into_response()
+    }
+}
+
+pub struct RunbookStore {
+    path: PathBuf,
+    io: Mutex<()>,
+}
+
+impl RunbookStore {
+    pub fn open(path: PathBuf) -> RunbookStore {
+        RunbookStore { path, io: Mutex::new(()) }
+    }
+
+    fn read(&self) -> Result<RunbooksFile, RunbookError> {
+        if !self.path.exists() {
+            return Ok(RunbooksFile::default());
+        }
+        let text = fs::read_to_string(&self.path).map_err(|e| RunbookError::Storage(e.to_string()))?;
+        serde_json::from_str(&text).map_err(|e| RunbookError::Storage(format!("{}: {}", self.path.display(), e)))
+    }
+
+    fn write(&self, file: &RunbooksFile) -> Result<(), RunbookError> {
+        let json = serde_json::to_vec_pretty(file).map_err(|e| RunbookError::Storage(e.to_string()))?;
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir).map_err(|e| RunbookError::Storage(e.to_string()))?;
+        }
+        write_atomic(&self.path, &json).map_err(|e| RunbookError::Storage(e.to_string()))
+    }
+
+    pub fn list(&self) -> Result<Vec<Runbook>, RunbookError> {
+        let _io = self.io.lock().map_err(|_| RunbookError::Storage("lock poisoned".into()))?;
+        Ok(self.read()?.runbooks)
+    }
+
+    pub fn get(&self, name: &str) -> Result<Runbook, RunbookError> {
+        self.list()?.into_iter().find(|r| r.name == name)
+            .ok_or_else(|| RunbookError::NotFound(format!("runbook not found: {}", name)))
+    }
+
+    // Запись с тем же именем заменяется
+    pub fn save(&self, mut runbook: Runbook) -> Result<(), RunbookError> {
+        runbook.name = runbook.name.trim().to_string();
+        if runbook.name.is_empty() || runbook.name.len() > 64 {
+            return Err(RunbookError::Invalid("name: 1-64 characters".into()));
+        }
+        runbook.steps.retain(|s| !s.command.trim().is_empty());
+        if runbook.steps.is_empty() {
+            return Err(RunbookError::Invalid("runbook needs at least one non-empty step".into()));
+        }
+        runbook.updated_at = Utc::now();
+        let _io = self.io.lock().map_err(|_| RunbookError::Storage("lock poisoned".into()))?;
+        let mut file = self.read()?;
+        file.runbooks.retain(|r| r.name != runbook.name);
+        file.runbooks.push(runbook);
+        file.runbooks.sort_by(|a, b| a.name.cmp(&b.name));
+        self.write(&file)
+    }
+
+    pub fn delete(&self, name: &str) -> Result<(), RunbookError> {
+        let _io = self.io.lock().map_err(|_| RunbookError::Storage("lock poisoned".into()))?;
+        let mut file = self.read()?;
+        let before = file.runbooks.len();
+        file.runbooks.retain(|r| r.name != name);
+        if file.runbooks.len() == before {
+            return Err(RunbookError::NotFound(format!("runbook not found: {}", name)));
+        }
+        self.write(&file)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Status {
+    Pending,
+    Running,
+    Ok,
+    Failed,
+    // Хост не начат или шаг не выполнялся: запуск остановлен раньше
+    Skipped,
+    Cancelled,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct StepResult {
+    pub command: String,
+    pub status: Status,
+    pub exit_status: Option<i64>,
+    pub output: String,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub truncated: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    pub duration_ms: u64,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct HostReport {
+    pub server: String,
+    pub host: Option<String>,
+    pub status: Status,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    pub steps: Vec<StepResult>,
+    pub duration_ms: u64,
+}
+
+#[derive(Clone, Serialize)]
+pub struct RunReport {
+    pub id: String,
+    pub runbook: String,
+    pub relay: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub actor: Option<String>,
+    pub concurrency: usize,
+    pub stop_on_failure: bool,
+    pub status: Status,
+    pub started_at: DateTime<Utc>,
+    pub finished_at: Option<DateTime<Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
+    pub hosts: Vec<HostReport>,
+}
+
+impl RunReport {
+    fn summary(&self) -> serde_json::Value {
+        let count = |st: Status| self.hosts.iter().filter(|h| h.status == st).count();
+        json!({
+            "hosts": self.hosts.len(),
+            "ok": count(Status::Ok),
+            "failed": count(Status::Failed),
+            "skipped": count(Status::Skipped),
+            "cancelled": count(Status::Cancelled),
+            "running": count(Status::Running) + count(Status::Pending),
+        })
+    }
+}
+
+struct Run {
+    id: String,
+    stop_on_failure: bool,
+    report: Mutex<RunReport>,
+    // Новые шаги и хосты не начинаются: отмена или stop_on_failure
+    stop: AtomicBool,
+    cancelled: AtomicBool,
+}
+
+impl Run {
+    fn update(&self, f: impl FnOnce(&mut RunReport)) {
+        if let Ok(mut report) = self.report.lock() {
+            f(&mut report);
+        }
+    }
+
+    fn host(&self, i: usize, f: impl FnOnce(&mut HostReport)) {
+        self.update(|r| f(&mut r.hosts[i]));
+    }
+
+    fn snapshot(&self) -> Option<RunReport> {
+        self.report.lock().ok().map(|r| r.clone())
+    }
+
+    fn stopped(&self) -> bool {
+        self.stop.load(Ordering::SeqCst)
+    }
+}
+
+// Последние запуски в памяти, новые в конце
+pub struct Runs {
+    runs: Mutex<VecDeque<Arc<Run>>>,
+    keep: usize,
+}
+
+impl Runs {
+    pub fn new(keep: usize) -> Runs {
+        Runs { runs: Mutex::new(VecDeque::new()), keep: keep.max(1) }
+    }
+
+    fn find(&self, id: &str) -> Option<Arc<Run>> {
+        self.runs.lock().ok()?.iter().find(|r| r.id == id).cloned()
+    }
+
+    fn push(&self, run: Arc<Run>) {
+        if let Ok(mut runs) = self.runs.lock() {
+            // Вытесняются только завершённые
+            while runs.len() >= self.keep {
+                let Some(i) = runs.iter().position(|r| r.snapshot().is_some_and(|s| s.finished_at.is_some())) else { break };
+                runs.remove(i);
+            }
+            runs.push_back(run);
+        }
+    }
+
+    // GET /rust/runbooks/runs — без вывода шагов
+    pub fn describe(&self) -> serde_json::Value {
+        let runs: Vec<_> = self.runs.lock().map(|r| r.iter().rev().cloned().collect::<Vec<_>>()).unwrap_or_default()
+            .iter()
+            .filter_map(|r| r.snapshot())
+            .map(|r| json!({
+                "id": r.id,
+                "runbook": r.runbook,
+                "relay": r.relay,
+                "actor": r.actor,
+                "status": r.status,
+                "started_at": r.started_at,
+                "finished_at": r.finished_at,
+                "summary": r.summary(),
+            }))
+            .collect();
+        json!({"runs": runs})
+    }
+
+    // GET /rust/runbooks/report?id=
+    pub fn report(&self, id: &str) -> Result<serde_json::Value, RunbookError> {
+        let report = self.find(id).and_then(|r| r.snapshot())
+            .ok_or_else(|| RunbookError::NotFound(format!("unknown run: {}", id)))?;
+        let mut v = json!(report);
+        v["summary"] = report.summary();
+        Ok(v)
+    }
+
+    pub fn cancel(&self, id: &str) -> Result<(), RunbookError> {
+        let run = self.find(id).ok_or_else(|| RunbookError::NotFound(format!("unknown run: {}", id)))?;
+        run.cancelled.store(true, Ordering::SeqCst);
+        run.stop.store(true, Ordering::SeqCst);
+        Ok(())
+    }
+}
+
+// Всё, что нужно запуску от дашборда
+pub struct Runner {
+    pub relays: Arc<RelayRegistry>,
+    pub servers: Arc<ServerStore>,
+    pub policy: CommandPolicy,
+    pub audit: Arc<AuditLog>,
+    pub config: RunbooksConfig,
+}
+
+pub struct RunRequest {
+    pub runbook: Runbook,
+    pub servers: Vec<String>,
+    pub backend: Arc<RelayBackend>,
+    pub concurrency: Option<usize>,
+    pub stop_on_failure: bool,
+    pub client: ClientAddr,
+}
+
+// Проверяет серверы и запускает в фоне; отчёт — по id, итоговый — из JoinHandle
+pub fn start(runner: Arc<Runner>, runs: &Runs, req: RunRequest) -> Result<(RunReport, JoinHandle<Option<RunReport>>), RunbookError> {
+    let mut seen = HashSet::new();
+    let mut servers = req.servers;
+    servers.retain(|s| seen.insert(s.clone()));
+    if servers.is_empty() {
+        return Err(RunbookError::Invalid("servers: at least one saved server is required".into()));
+    }
+    let known = runner.servers.list().map_err(RunbookError::Vault)?;
+    if let Some(name) = servers.iter().find(|n| !known.iter().any(|s| &s.name == *n)) {
+        return Err(RunbookError::NotFound(format!("server not found: {}", name)));
+    }
+    if runner.servers.is_locked() {
+        return Err(RunbookError::Vault(VaultError::Locked));
+    }
+    let mut concurrency = req.concurrency.unwrap_or(runner.config.default_concurrency).clamp(1, runner.config.max_concurrency.max(1));
+    let mut note = None;
+    // Go relay держит одно подключение: хосты по очереди, а пока его занимает сессия дашборда, connect отклоняется
+    if req.backend.kind == RelayKind::Http {
+        concurrency = 1;
+        note = Some(format!("relay {} holds one SSH connection: hosts run one at a time and fail as busy while another session holds it", req.backend.name));
+    }
+    let report = RunReport {
+        id: ssh_sessions::new_id(),
+        runbook: req.runbook.name.clone(),
+        relay: req.backend.name.clone(),
+        actor: req.client.user.as_ref().map(|u| u.name.clone()),
+        concurrency,
+        stop_on_failure: req.stop_on_failure,
+        status: Status::Running,
+        started_at: Utc::now(),
+        finished_at: None,
+        note,
+        hosts: servers.iter().map(|s| HostReport {
+            server: s.clone(),
+            host: None,
+            status: Status::Pending,
+            error: None,
+            steps: vec![],
+            duration_ms: 0,
+        }).collect(),
+    };
+    let run = Arc::new(Run {
+        id: report.id.clone(),
+        stop_on_failure: req.stop_on_failure,
+        report: Mutex::new(report.clone()),
+        stop: AtomicBool::new(false),
+        cancelled: AtomicBool::new(false),
+    });
+    runs.push(run.clone());
+    info!("runbook {}: run {} on {} servers via {} (concurrency {})", report.runbook, report.id, servers.len(), report.relay, concurrency);
+
+    let steps = Arc::new(req.runbook.steps);
+    let (backend, client) = (req.backend, req.client);
+    let handle = tokio::spawn(async move {
+        futures_util::stream::iter(servers.into_iter().enumerate())
+            .for_each_concurrent(concurrency, |(i, server)| {
+                run_host(&runner, &run, i, server, backend.clone(), &steps, &client)
+            })
+            .await;
+        run.update(|r| {
+            r.finished_at = Some(Utc::now());
+            r.status = if run.cancelled.load(Ordering::SeqCst) {
+                Status::Cancelled
+            } else if r.hosts.iter().any(|h| h.status == Status::Failed) {
+                Status::Failed
+            } else {
+                Status::Ok
+            };
+            info!("runbook {}: run {} finished: {:?}", r.runbook, r.id, r.summary());
+        });
+        run.snapshot()
+    });
+    Ok((report, handle))
+}
+
+fn skipped(command: &str) -> StepResult {
+    StepResult { command: command.to_string(), status: Status::Skipped, exit_status: None, output: String::new(), truncated: false, error: None, duration_ms: 0 }
+}
+
+fn truncate(mut output: String, max: usize) -> (String, bool) {
+    if output.len() <= max {
+        return (output, false);
+    }
+    let mut end = max;
+    while !output.is_char_boundary(end) {
+        end -= 1;
+    }
+    output.truncate(end);
+    (output, true)
+}
+
+async fn run_host(runner: &Runner, run: &Run, i: usize, server: String, backend: Arc<RelayBackend>, steps: &[RunbookStep], client: &ClientAddr) {
+    let started = Instant::now();
+    if run.stopped() {
+        run.host(i, |h| {
+            h.status = Status::Skipped;
+            h.steps = steps.iter().map(|s| skipped(&s.command)).collect();
+        });
+        return;
+    }
+    run.host(i, |h| h.status = Status::Running);
+    let target = match connect(runner, &server, backend).await {
+        Ok(target) => target,
+        Err((host, error)) => {
+            warn!("runbook: {} connect failed: {}", server, error);
+            run.host(i, |h| {
+                h.host = host;
+                h.status = Status::Failed;
+                h.error = Some(error);
+                h.steps = steps.iter().map(|s| skipped(&s.command)).collect();
+                h.duration_ms = started.elapsed().as_millis() as u64;
+            });
+            if run.stop_on_failure {
+                run.stop.store(true, Ordering::SeqCst);
+            }
+            return;
+        }
+    };
+    let info = target.session.as_deref().and_then(|id| runner.relays.sessions.get(id));
+    run.host(i, |h| h.host = info.as_ref().map(|s| s.host.clone()));
+    let effective = policy::for_target(&runner.relays, &runner.servers, &runner.policy, &target);
+    let max_output = runner.config.max_output_kb * 1024;
+    let mut status = Status::Ok;
+    for step in steps {
+        if status != Status::Ok || run.stopped() {
+            if status == Status::Ok {
+                status = Status::Cancelled;
+            }
+            run.host(i, |h| h.steps.push(skipped(&step.command)));
+            continue;
+        }
+        let step_started = Instant::now();
+        // Долгий запуск не должен попасть под закрытие простаивающих сессий
+        if let Some(id) = &target.session {
+            runner.relays.sessions.touch(id);
+        }
+        let entry = AuditEntry::new("runbook", client).on(&runner.relays, &target).command(&step.command);
+        let (result, entry) = match effective.check_command(&step.command) {
+            Err(denied) => (Err(denied.error.clone()), entry.failed(AuditOutcome::Denied, &denied.error)),
+            Ok(()) => match runner.relays.post_text(&target, "/ssh/execute", &json!({"command": step.command})).await {
+                Ok(output) => {
+                    let entry = entry.ok(output.len()).exit(Some(0));
+                    (Ok(output), entry)
+                }
+                Err(e) => (Err(e.message()), entry.failed(AuditOutcome::Error, &e.message())),
+            },
+        };
+        runner.audit.record(entry);
+        let result = match result {
+            Ok(output) => {
+                let (output, truncated) = truncate(output, max_output);
+                StepResult { command: step.command.clone(), status: Status::Ok, exit_status: Some(0), output, truncated, error: None, duration_ms: 0 }
+            }
+            Err(error) => {
+                if !step.continue_on_error {
+                    status = Status::Failed;
+                }
+                StepResult { command: step.command.clone(), status: Status::Failed, exit_status: exit_status_of(&error), output: String::new(), truncated: false, error: Some(error), duration_ms: 0 }
+            }
+        };
+        let result = StepResult { duration_ms: step_started.elapsed().as_millis() as u64, ..result };
+        run.host(i, |h| h.steps.push(result));
+    }
+    disconnect(runner, &target).await;
+    if status == Status::Failed && run.stop_on_failure {
+        run.stop.store(true, Ordering::SeqCst);
+    }
+    run.host(i, |h| {
+        h.status = status;
+        h.duration_ms = started.elapsed().as_millis() as u64;
+    });
+}
+
+// Новая сессия на relay по сохранённому серверу; ошибка — (хост, если известен, текст)
+async fn connect(runner: &Runner, server: &str, backend: Arc<RelayBackend>) -> Result<RelayTarget, (Option<String>, String)> {
+    let creds = runner.servers.credentials(server).map_err(|e| (None, e.message()))?;
+    let host = Some(creds.host.clone());
+    let Some(auth) = creds.auth.clone() else {
+        return Err((host, "saved server has no credentials".into()));
+    };
+    let payload = creds.relay_payload().map_err(|e| (host.clone(), e))?;
+    let id = ssh_sessions::new_id();
+    // Go relay с чужой сессией не трогаем
+    let _claim = match backend.kind {
+        RelayKind::Http => Some(runner.relays.sessions.claim(&backend.name, &id)
+            .map_err(|other| (host.clone(), format!("relay {} is busy with session {}", backend.name, other)))?),
+        RelayKind::Native => None,
+    };
+    let target = RelayTarget { backend, session: Some(id.clone()), explicit: true };
+    let result: SshConnectResult = runner.relays.post_json(&target, "/ssh/connect", Some(&payload)).await
+        .map_err(|e| (host.clone(), e.message()))?;
+    if !result.connected {
+        return Err((host, result.message));
+    }
+    let now = Utc::now();
+    runner.relays.sessions.insert(SshSessionInfo {
+        id,
+        relay: target.backend.name.clone(),
+        host: creds.host,
+        user: creds.user,
+        port: creds.port,
+        auth: auth.kind(),
+        server: Some(server.to_string()),
+        jumps: creds.jumps.iter().map(Credentials::label).collect(),
+        created_at: now,
+        last_used: now,
+    });
+    Ok(target)
+}
+
+async fn disconnect(runner: &Runner, target: &RelayTarget) {
+    let result: Result<SshDisconnectResult, _> = runner.relays.post_json(target, "/ssh/disconnect", None::<&()>).await;
+    if let Err(e) = result {
+        warn!("runbook: disconnect {:?} failed: {}", target.session, e.message());
+    }
+    if let Some(id) = &target.session {
+        runner.relays.sessions.remove(id);
+    }
+}
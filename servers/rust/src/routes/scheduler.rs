@@ -0,0 +1,607 @@
+// Задания по расписанию: команда или runbook на сохранённых серверах. Запуск идёт через runbooks::start,
+// поэтому политика, аудит и отчёт те же; последние N результатов каждого задания хранятся на диске
+use chrono::{DateTime, Datelike, Duration as ChronoDuration, Timelike, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::time::MissedTickBehavior;
+use tracing::{error, info, warn};
+
+use crate::config::{RelayKind, SchedulerConfig};
+use super::audit::ClientAddr;
+use super::relay::RelayBackend;
+use super::auth::{AuthUser, Role};
+use super::runbooks::{self, HostReport, RunRequest, Runbook, RunbookError, RunbookStep, RunbookStore, Runner, Runs, Status};
+use super::session_store::write_atomic;
+
+const MAX_KEEP: usize = 200;
+const MIN_EVERY_SECS: i64 = 10;
+const MONTHS: &[&str] = &["jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec"];
+const WEEKDAYS: &[&str] = &["sun", "mon", "tue", "wed", "thu", "fri", "sat"];
+
+// 5 полей cron (минута час день месяц день_недели) по UTC, @hourly/@daily/@weekly/@monthly/@yearly или @every 30s|5m|2h
+#[derive(Clone, Debug)]
+pub enum Schedule {
+    Cron {
+        minutes: u64,
+        hours: u32,
+        days: u32,
+        months: u16,
+        weekdays: u8,
+        // * в дне месяца или дне недели: как в cron, при двух заданных полях достаточно совпасть одному
+        any_day: bool,
+        any_weekday: bool,
+    },
+    Every(ChronoDuration),
+}
+
+// Одно поле: *, */n, a, a-b, a-b/n, a/n и списки через запятую; names — jan..dec, sun..sat
+fn field(spec: &str, min: u32, max: u32, names: &[&str]) -> Result<u64, String> {
+    let value = |s: &str| -> Result<u32, String> {
+        let lower = s.to_ascii_lowercase();
+        // jan = 1, sun = 0: имена начинаются с нижней границы поля
+        if let Some(i) = names.iter().position(|n| *n == lower) {
+            return Ok(i as u32 + min);
+        }
+        s.parse::<u32>().ok().filter(|v| (min..=max).contains(v)).ok_or_else(|| format!("{:?} is not in {}-{}", s, min, max))
+    };
+    let mut mask = 0u64;
+    for item in spec.split(',') {
+        let (range, step) = match item.split_once('/') {
+            Some((r, s)) => (r, s.parse::<u32>().ok().filter(|s| *s > 0).ok_or_else(|| format!("invalid step in {:?}", item))?),
+            None => (item, 1),
+        };
+        let (from, to) = match range {
+            "*" => (min, max),
+            r => match r.split_once('-') {
+                Some((a, b)) => (value(a)?, value(b)?),
+                // a/n — от a до конца диапазона
+                None if step > 1 => (value(r)?, max),
+                None => (value(r)?, value(r)?),
+            },
+        };
+        if from > to {
+            return Err(format!("invalid range {:?}", item));
+        }
+        for v in (from..=to).step_by(step as usize) {
+            mask |= 1 << v;
+        }
+    }
+    Ok(mask)
+}
+
+fn parse_every(spec: &str) -> Result<Schedule, String> {
+    let (num, unit) = spec.split_at(spec.find(|c: char| !c.is_ascii_digit()).unwrap_or(spec.len()));
+    let n: i64 = num.parse().map_err(|_| format!("invalid @every {:?}", spec))?;
+    let secs = match unit {
+        "s" => n,
+        "m" => n * 60,
+        "h" => n * 3600,
+        _ => return Err(format!("invalid @every {:?}: use 30s, 5m or 2h", spec)),
+    };
+    if secs < MIN_EVERY_SECS {
+        return Err(format!("@every must be at least {}s", MIN_EVERY_SECS));
+    }
+    Ok(Schedule::Every(ChronoDuration::seconds(secs)))
+}
+
+impl Schedule {
+    pub fn parse(spec: &str) -> Result<Schedule, String> {
+        let spec = spec.trim();
+        let expanded = match spec {
+            "@hourly" => "0 * * * *",
+            "@daily" | "@midnight" => "0 0 * * *",
+            "@weekly" => "0 0 * * 0",
+            "@monthly" => "0 0 1 * *",
+            "@yearly" | "@annually" => "0 0 1 1 *",
+            s => s,
+        };
+        if let Some(every) = expanded.strip_prefix("@every ") {
+            return parse_every(every.trim());
+        }
+        let f: Vec<&str> = expanded.split_whitespace().collect();
+        if f.len() != 5 {
+            return Err(format!("schedule {:?}: expected 5 fields (minute hour day month weekday) or @every", spec));
+        }
+        let err = |name: &str, e: String| format!("schedule {:?}: {}: {}", spec, name, e);
+        let weekdays = field(f[4], 0, 7, WEEKDAYS).map_err(|e| err("weekday", e))?;
+        let schedule = Schedule::Cron {
+            minutes: field(f[0], 0, 59, &[]).map_err(|e| err("minute", e))?,
+            hours: field(f[1], 0, 23, &[]).map_err(|e| err("hour", e))? as u32,
+            days: field(f[2], 1, 31, &[]).map_err(|e| err("day", e))? as u32,
+            months: field(f[3], 1, 12, MONTHS).map_err(|e| err("month", e))? as u16,
+            // 7 — тоже воскресенье
+            weekdays: weekdays as u8 | (weekdays >> 7) as u8 & 1,
+            any_day: f[2] == "*",
+            any_weekday: f[4] == "*",
+        };
+        if schedule.next_after(Utc::now()).is_none() {
+            return Err(format!("schedule {:?} never fires", spec));
+        }
+        Ok(schedule)
+    }
+
+    pub fn next_after(&self, t: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let (minutes, hours, days, months, weekdays, any_day, any_weekday) = match self {
+            Schedule::Every(d) => return Some(t + *d),
+            Schedule::Cron { minutes, hours, days, months, weekdays, any_day, any_weekday } => {
+                (*minutes, *hours, *days, *months, *weekdays, *any_day, *any_weekday)
+            }
+        };
+        let day_matches = |m: DateTime<Utc>| {
+            let dom = days & (1 << m.day()) != 0;
+            let dow = weekdays & (1 << m.weekday().num_days_from_sunday()) != 0;
+            let day = match (any_day, any_weekday) {
+                (true, true) => true,
+                (true, false) => dow,
+                (false, true) => dom,
+                (false, false) => dom || dow,
+            };
+            day && months & (1 << m.month()) != 0
+        };
+        let mut m = t.with_second(0)?.with_nanosecond(0)? + ChronoDuration::minutes(1);
+        let limit = t + ChronoDuration::days(366 * 5);
+        while m < limit {
+            if !day_matches(m) {
+                m = m.date_naive().succ_opt()?.and_hms_opt(0, 0, 0)?.and_utc();
+            } else if hours & (1 << m.hour()) == 0 {
+                m = m.with_minute(0)? + ChronoDuration::hours(1);
+            } else if minutes & (1 << m.minute()) == 0 {
+                m += ChronoDuration::minutes(1);
+            } else {
+                return Some(m);
+            }
+        }
+        None
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Job {
+    pub name: String,
+    pub schedule: String,
+    // Либо command, либо runbook
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub command: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub runbook: Option<String>,
+    // Имена сохранённых серверов
+    pub servers: Vec<String>,
+    // None — relay по умолчанию
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub relay: Option<String>,
+    #[serde(default = "enabled")]
+    pub enabled: bool,
+    // Сколько запусков хранить; None — [scheduler].keep_history
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub keep: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub concurrency: Option<usize>,
+    // В отличие от ручного запуска runbook по умолчанию выключено: проверка должна дойти до всех хостов
+    #[serde(default)]
+    pub stop_on_failure: bool,
+    #[serde(default = "Utc::now")]
+    pub updated_at: DateTime<Utc>,
+}
+
+fn enabled() -> bool {
+    true
+}
+
+// Результат одного запуска задания
+#[derive(Clone, Serialize, Deserialize)]
+pub struct JobRun {
+    pub started_at: DateTime<Utc>,
+    pub finished_at: DateTime<Utc>,
+    // schedule или manual
+    pub trigger: String,
+    pub status: Status,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub run_id: Option<String>,
+    // Запуск не начался: хранилище заблокировано, runbook удалён и т. п.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    pub hosts: Vec<HostReport>,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+struct JobsFile {
+    jobs: Vec<Job>,
+}
+
+struct Entry {
+    job: Job,
+    schedule: Schedule,
+    next: Option<DateTime<Utc>>,
+    last: Option<(DateTime<Utc>, Status)>,
+}
+
+pub struct Scheduler {
+    dir: PathBuf,
+    config: SchedulerConfig,
+    runner: Arc<Runner>,
+    runs: Arc<Runs>,
+    runbooks: Arc<RunbookStore>,
+    jobs: Mutex<Vec<Entry>>,
+    // Задание не запускается второй раз, пока идёт прошлый запуск
+    running: Mutex<HashSet<String>>,
+    io: Mutex<()>,
+}
+
+fn valid_name(name: &str) -> bool {
+    !name.is_empty() && name.len() <= 64 && !name.starts_with('.')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || "._-".contains(c))
+}
+
+// От имени кого запуск по расписанию попадает в аудит и отчёт
+fn scheduler_client(job: &str) -> ClientAddr {
+    ClientAddr {
+        ip: None,
+        forwarded_for: None,
+        user: Some(AuthUser { name: format!("scheduler:{}", job), role: Role::Operator, token: None }),
+    }
+}
+
+impl Scheduler {
+    pub fn open(dir: PathBuf, config: SchedulerConfig, runner: Arc<Runner>, runs: Arc<Runs>, runbooks: Arc<RunbookStore>) -> Scheduler {
+        let scheduler = Scheduler {
+            dir,
+            config,
+            runner,
+            runs,
+            runbooks,
+            jobs: Mutex::new(vec![]),
+            running: Mutex::new(HashSet::new()),
+            io: Mutex::new(()),
+        };
+        let file = match scheduler.read() {
+            Ok(file) => file,
+            Err(e) => {
+                error!("scheduler: {}", e.message());
+                JobsFile::default()
+            }
+        };
+        let now = Utc::now();
+        let mut entries = vec![];
+        for job in file.jobs {
+            match Schedule::parse(&job.schedule) {
+                Ok(schedule) => {
+                    let last = scheduler.history(&job.name, 1).ok().and_then(|h| h.first().map(|r| (r.started_at, r.status)));
+                    entries.push(Entry { next: schedule.next_after(now), schedule, last, job });
+                }
+                Err(e) => error!("scheduler: job {} skipped: {}", job.name, e),
+            }
+        }
+        info!("scheduler: {} jobs in {}", entries.len(), scheduler.dir.display());
+        if let Ok(mut jobs) = scheduler.jobs.lock() {
+            *jobs = entries;
+        }
+        scheduler
+    }
+
+    fn jobs_path(&self) -> PathBuf {
+        self.dir.join("jobs.json")
+    }
+
+    fn history_path(&self, name: &str) -> PathBuf {
+        self.dir.join("history").join(format!("{}.json", name))
+    }
+
+    fn read(&self) -> Result<JobsFile, RunbookError> {
+        let path = self.jobs_path();
+        if !path.exists() {
+            return Ok(JobsFile::default());
+        }
+        let text = fs::read_to_string(&path).map_err(|e| RunbookError::Storage(e.to_string()))?;
+        serde_json::from_str(&text).map_err(|e| RunbookError::Storage(format!("{}: {}", path.display(), e)))
+    }
+
+    fn write_json<T: Serialize>(path: &Path, value: &T) -> Result<(), RunbookError> {
+        let json = serde_json::to_vec_pretty(value).map_err(|e| RunbookError::Storage(e.to_string()))?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).map_err(|e| RunbookError::Storage(e.to_string()))?;
+        }
+        write_atomic(path, &json).map_err(|e| RunbookError::Storage(e.to_string()))
+    }
+
+    // Под замком jobs: файл всегда совпадает с памятью
+    fn persist(&self, entries: &[Entry]) -> Result<(), RunbookError> {
+        let file = JobsFile { jobs: entries.iter().map(|e| e.job.clone()).collect() };
+        Self::write_json(&self.jobs_path(), &file)
+    }
+
+    pub fn spawn(self: &Arc<Self>) {
+        if !self.config.enabled {
+            info!("scheduler disabled: jobs run only via /rust/jobs/run");
+            return;
+        }
+        let scheduler = self.clone();
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(Duration::from_secs(1));
+            ticker.set_missed_tick_behavior(MissedTickBehavior::Skip);
+            loop {
+                ticker.tick().await;
+                scheduler.tick();
+            }
+        });
+    }
+
+    fn tick(self: &Arc<Self>) {
+        let now = Utc::now();
+        let due: Vec<Job> = match self.jobs.lock() {
+            Ok(mut jobs) => jobs.iter_mut()
+                .filter(|e| e.job.enabled && e.next.is_some_and(|n| n <= now))
+                .map(|e| {
+                    e.next = e.schedule.next_after(now);
+                    e.job.clone()
+                })
+                .collect(),
+            Err(_) => return,
+        };
+        for job in due {
+            if let Err(e) = self.start(job.clone(), "schedule", scheduler_client(&job.name)) {
+                warn!("scheduler: job {}: {}", job.name, e.message());
+            }
+        }
+    }
+
+    // Запуск в фоне; возвращает id запуска runbook (/rust/runbooks/report?id=)
+    pub fn start(self: &Arc<Self>, job: Job, trigger: &str, client: ClientAddr) -> Result<String, RunbookError> {
+        if let Ok(mut running) = self.running.lock() {
+            if !running.insert(job.name.clone()) {
+                return Err(RunbookError::Busy(format!("job {} is still running", job.name)));
+            }
+        }
+        let started_at = Utc::now();
+        let started = self.begin(&job, client);
+        let (report, handle) = match started {
+            Ok(started) => started,
+            Err(e) => {
+                self.finish(&job, JobRun {
+                    started_at,
+                    finished_at: Utc::now(),
+                    trigger: trigger.to_string(),
+                    status: Status::Failed,
+                    run_id: None,
+                    error: Some(e.message()),
+                    hosts: vec![],
+                });
+                return Err(e);
+            }
+        };
+        info!("scheduler: job {} started ({}), run {}", job.name, trigger, report.id);
+        let scheduler = self.clone();
+        let (trigger, run_id) = (trigger.to_string(), report.id.clone());
+        tokio::spawn(async move {
+            let report = handle.await.ok().flatten().unwrap_or(report);
+            scheduler.finish(&job, JobRun {
+                started_at,
+                finished_at: report.finished_at.unwrap_or_else(Utc::now),
+                trigger,
+                status: report.status,
+                run_id: Some(report.id),
+                error: None,
+                hosts: report.hosts,
+            });
+        });
+        Ok(run_id)
+    }
+
+    fn backend(&self, job: &Job) -> Result<Arc<RelayBackend>, RunbookError> {
+        match &job.relay {
+            Some(name) => self.runner.relays.get(name).ok_or_else(|| RunbookError::NotFound(format!("unknown relay: {}", name))),
+            None => Ok(self.runner.relays.default_backend()),
+        }
+    }
+
+    fn begin(&self, job: &Job, client: ClientAddr) -> Result<(runbooks::RunReport, tokio::task::JoinHandle<Option<runbooks::RunReport>>), RunbookError> {
+        let runbook = match (&job.command, &job.runbook) {
+            (Some(command), _) => Runbook {
+                name: format!("job:{}", job.name),
+                description: String::new(),
+                steps: vec![RunbookStep { command: command.clone(), continue_on_error: false }],
+                updated_at: job.updated_at,
+            },
+            (None, Some(name)) => self.runbooks.get(name)?,
+            (None, None) => return Err(RunbookError::Invalid("job has neither command nor runbook".into())),
+        };
+        let backend = self.backend(job)?;
+        // Go relay держит одно подключение: пока его занимает сессия оператора, запуск записывается ошибкой,
+        // а хосты идут по одному через runbooks::start
+        if backend.kind == RelayKind::Http {
+            if let Some(other) = self.runner.relays.sessions.holder(&backend.name) {
+                return Err(RunbookError::Busy(format!("relay {} is busy with session {}", backend.name, other)));
+            }
+        }
+        runbooks::start(self.runner.clone(), &self.runs, RunRequest {
+            runbook,
+            servers: job.servers.clone(),
+            backend,
+            concurrency: job.concurrency,
+            stop_on_failure: job.stop_on_failure,
+            client,
+        })
+    }
+
+    fn finish(&self, job: &Job, run: JobRun) {
+        if let Ok(mut running) = self.running.lock() {
+            running.remove(&job.name);
+        }
+        if run.status != Status::Ok {
+            warn!("scheduler: job {} finished: {:?} {}", job.name, run.status, run.error.as_deref().unwrap_or(""));
+        }
+        if let Ok(mut jobs) = self.jobs.lock() {
+            if let Some(e) = jobs.iter_mut().find(|e| e.job.name == job.name) {
+                e.last = Some((run.started_at, run.status));
+            }
+        }
+        let keep = job.keep.unwrap_or(self.config.keep_history).clamp(1, MAX_KEEP);
+        let Ok(_io) = self.io.lock() else { return };
+        let path = self.history_path(&job.name);
+        let mut history: Vec<JobRun> = fs::read_to_string(&path).ok()
+            .and_then(|text| serde_json::from_str(&text).ok())
+            .unwrap_or_default();
+        history.push(run);
+        if history.len() > keep {
+            history.drain(..history.len() - keep);
+        }
+        if let Err(e) = Self::write_json(&path, &history) {
+            error!("scheduler: job {} history: {}", job.name, e.message());
+        }
+    }
+
+    pub fn save(&self, mut job: Job) -> Result<(), RunbookError> {
+        job.name = job.name.trim().to_string();
+        if !valid_name(&job.name) {
+            return Err(RunbookError::Invalid("name: 1-64 characters of a-z, 0-9, . _ -".into()));
+        }
+        let schedule = Schedule::parse(&job.schedule).map_err(RunbookError::Invalid)?;
+        job.command = job.command.filter(|c| !c.trim().is_empty());
+        match (&job.command, &job.runbook) {
+            (Some(_), Some(_)) | (None, None) => return Err(RunbookError::Invalid("exactly one of command or runbook is required".into())),
+            (None, Some(name)) => {
+                self.runbooks.get(name)?;
+            }
+            _ => {}
+        }
+        if job.servers.is_empty() {
+            return Err(RunbookError::Invalid("servers: at least one saved server is required".into()));
+        }
+        self.backend(&job)?;
+        job.updated_at = Utc::now();
+        let mut jobs = self.jobs.lock().map_err(|_| RunbookError::Storage("lock poisoned".into()))?;
+        let last = jobs.iter().find(|e| e.job.name == job.name).and_then(|e| e.last);
+        let entry = Entry { next: schedule.next_after(Utc::now()), schedule, last, job };
+        match jobs.iter_mut().find(|e| e.job.name == entry.job.name) {
+            Some(e) => *e = entry,
+            None => jobs.push(entry),
+        }
+        jobs.sort_by(|a, b| a.job.name.cmp(&b.job.name));
+        self.persist(&jobs)
+    }
+
+    pub fn delete(&self, name: &str) -> Result<(), RunbookError> {
+        let mut jobs = self.jobs.lock().map_err(|_| RunbookError::Storage("lock poisoned".into()))?;
+        let before = jobs.len();
+        jobs.retain(|e| e.job.name != name);
+        if jobs.len() == before {
+            return Err(RunbookError::NotFound(format!("job not found: {}", name)));
+        }
+        self.persist(&jobs)?;
+        let _io = self.io.lock();
+        let _ = fs::remove_file(self.history_path(name));
+        Ok(())
+    }
+
+    pub fn get(&self, name: &str) -> Result<Job, RunbookError> {
+        self.jobs.lock().ok()
+            .and_then(|jobs| jobs.iter().find(|e| e.job.name == name).map(|e| e.job.clone()))
+            .ok_or_else(|| RunbookError::NotFound(format!("job not found: {}", name)))
+    }
+
+    // GET /rust/jobs/list
+    pub fn describe(&self) -> serde_json::Value {
+        let running = self.running.lock().map(|r| r.clone()).unwrap_or_default();
+        let jobs: Vec<_> = self.jobs.lock().map(|jobs| jobs.iter().map(|e| {
+            let mut v = json!(e.job);
+            v["next_run"] = json!(e.next.filter(|_| e.job.enabled && self.config.enabled));
+            v["running"] = json!(running.contains(&e.job.name));
+            v["last_run"] = json!(e.last.map(|(at, status)| json!({"started_at": at, "status": status})));
+            v
+        }).collect()).unwrap_or_default();
+        json!({"jobs": jobs, "scheduler_enabled": self.config.enabled})
+    }
+
+    // GET /rust/jobs/history?name=&limit= — новые первыми
+    pub fn history(&self, name: &str, limit: usize) -> Result<Vec<JobRun>, RunbookError> {
+        let _io = self.io.lock().map_err(|_| RunbookError::Storage("lock poisoned".into()))?;
+        let path = self.history_path(name);
+        if !path.exists() {
+            return Ok(vec![]);
+        }
+        let text = fs::read_to_string(&path).map_err(|e| RunbookError::Storage(e.to_string()))?;
+        let mut history: Vec<JobRun> = serde_json::from_str(&text)
+            .map_err(|e| RunbookError::Storage(format!("{}: {}", path.display(), e)))?;
+        history.reverse();
+        history.truncate(limit);
+        Ok(history)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(s: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(s).unwrap().with_timezone(&Utc)
+    }
+
+    fn next(spec: &str, after: &str) -> DateTime<Utc> {
+        Schedule::parse(spec).unwrap().next_after(at(after)).unwrap()
+    }
+
+    // 2026-10-18 — воскресенье
+    const NOW: &str = "2026-10-18T10:07:30Z";
+
+    #[test]
+    fn ranges_and_steps() {
+        assert_eq!(next("*/15 * * * *", NOW), at("2026-10-18T10:15:00Z"));
+        assert_eq!(next("@hourly", NOW), at("2026-10-18T11:00:00Z"));
+        assert_eq!(next("@every 30s", NOW), at("2026-10-18T10:08:00Z"));
+
+        let schedule = Schedule::parse("10-20/5 3 * * *").unwrap();
+        let mut t = at(NOW);
+        let fired: Vec<_> = (0..4).map(|_| {
+            t = schedule.next_after(t).unwrap();
+            t
+        }).collect();
+        assert_eq!(fired, [
+            at("2026-10-19T03:10:00Z"),
+            at("2026-10-19T03:15:00Z"),
+            at("2026-10-19T03:20:00Z"),
+            at("2026-10-20T03:10:00Z"),
+        ]);
+        assert_eq!(next("50/5,1 * * * *", NOW), at("2026-10-18T10:50:00Z"));
+    }
+
+    #[test]
+    fn month_and_weekday_names() {
+        assert_eq!(next("30 9 * JAN mon-fri", NOW), at("2027-01-01T09:30:00Z"));
+        assert_eq!(next("0 0 * * sun", NOW), at("2026-10-25T00:00:00Z"));
+        // 7 — тоже воскресенье
+        assert_eq!(next("0 0 * * 7", NOW), at("2026-10-25T00:00:00Z"));
+    }
+
+    #[test]
+    fn day_of_month_or_day_of_week() {
+        assert_eq!(next("0 0 13 * *", NOW), at("2026-11-13T00:00:00Z"));
+        assert_eq!(next("0 0 * * fri", NOW), at("2026-10-23T00:00:00Z"));
+        // Оба поля заданы: пятница или 13-е, что раньше
+        assert_eq!(next("0 0 13 * fri", NOW), at("2026-10-23T00:00:00Z"));
+        assert_eq!(next("0 0 13 * fri", "2026-12-12T00:00:01Z"), at("2026-12-13T00:00:00Z"));
+    }
+
+    #[test]
+    fn invalid_fields_are_rejected() {
+        for spec in [
+            "60 * * * *", "* 24 * * *", "* * 0 * *", "* * * 13 *", "* * * * 8",
+            "*/0 * * * *", "5-1 * * * *", "* * * foo *", "* * * *", "* * * * * *",
+            "@every 5s", "@every 10x", "@every",
+        ] {
+            assert!(Schedule::parse(spec).is_err(), "{:?} should be rejected", spec);
+        }
+        let e = Schedule::parse("61 * * * *").unwrap_err();
+        assert!(e.contains("minute"), "{}", e);
+    }
+
+    #[test]
+    fn schedule_that_never_fires() {
+        let e = Schedule::parse("0 0 30 feb *").unwrap_err();
+        assert!(e.contains("never fires"), "{}", e);
+        assert!(Schedule::parse("0 0 29 feb *").is_ok());
+    }
+}
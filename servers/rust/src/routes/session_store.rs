@@ -0,0 +1,335 @@
+// Файловое хранилище состояния дашборда: один JSON-файл на ключ, запись через временный файл + rename
+use chrono::{DateTime, Duration as ChronoDuration, Utc};
+use serde::{Deserialize, Deserializer, Serialize};
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tracing::{debug, info, warn};
+
+use crate::config::SessionsConfig;
+
+// 2 — точки cpu/gpu с временем и хостом
+const FORMAT_VERSION: u32 = 2;
+
+#[derive(Default, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct SessionData {
+    pub network_history: Vec<NetworkPoint>,
+    #[serde(deserialize_with = "points")]
+    pub cpu_history: Vec<MetricPoint>,
+    #[serde(deserialize_with = "points")]
+    pub gpu_history: Vec<MetricPoint>,
+    pub current_host: String,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct NetworkPoint {
+    pub rx: f64,
+    pub tx: f64,
+    pub timestamp: u64,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub host: String,
+}
+
+// timestamp — unix ms, 0 — неизвестно (старые сохранения)
+#[derive(Serialize, Deserialize, Clone)]
+pub struct MetricPoint {
+    pub timestamp: u64,
+    pub value: f64,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub host: String,
+}
+
+// Дашборд до версии 2 присылал голые числа
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum PointIn {
+    Point(MetricPoint),
+    Bare(f64),
+}
+
+fn points<'de, D: Deserializer<'de>>(d: D) -> Result<Vec<MetricPoint>, D::Error> {
+    let raw = Vec::<PointIn>::deserialize(d)?;
+    Ok(raw.into_iter().map(|p| match p {
+        PointIn::Point(p) => p,
+        PointIn::Bare(value) => MetricPoint { timestamp: 0, value, host: String::new() },
+    }).collect())
+}
+
+// Формат файла на диске
+#[derive(Serialize, Deserialize)]
+struct StoredSession {
+    version: u32,
+    key: String,
+    updated_at: DateTime<Utc>,
+    data: SessionData,
+}
+
+// Старый формат — голый SessionData, как его присылает дашборд
+enum OnDisk {
+    Current(StoredSession),
+    Legacy(SessionData),
+}
+
+const LEGACY_FIELDS: &[&str] = &["network_history", "cpu_history", "gpu_history", "current_host"];
+
+// Формат определяется явно: есть "version" — текущий, иначе старый, в котором должно быть хотя бы одно
+// из полей SessionData. Любой другой JSON — не файл сессии, а не пустая сессия
+fn parse(text: &str) -> Result<OnDisk, String> {
+    let value: serde_json::Value = serde_json::from_str(text).map_err(|e| e.to_string())?;
+    let Some(obj) = value.as_object() else { return Err("not a JSON object".into()) };
+    if obj.contains_key("version") {
+        let stored: StoredSession = serde_json::from_value(value).map_err(|e| e.to_string())?;
+        if stored.version > FORMAT_VERSION {
+            return Err(format!("unsupported format version {}", stored.version));
+        }
+        return Ok(OnDisk::Current(stored));
+    }
+    if !LEGACY_FIELDS.iter().any(|f| obj.contains_key(*f)) {
+        return Err("neither a versioned nor a legacy session".into());
+    }
+    serde_json::from_value(value).map(OnDisk::Legacy).map_err(|e| e.to_string())
+}
+
+pub struct SessionStore {
+    dir: PathBuf,
+    limits: SessionsConfig,
+    // Сериализует запись и чистку
+    write_lock: Mutex<()>,
+}
+
+pub struct Restored {
+    pub data: SessionData,
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+// Ключ превращается в имя файла: всё, кроме [A-Za-z0-9._-], заменяем на '_'
+fn file_name(key: &str) -> String {
+    let safe: String = key.chars()
+        .map(|c| if c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_') { c } else { '_' })
+        .take(120)
+        .collect();
+    format!("{}.json", safe)
+}
+
+// Атомарная запись: пишем во временный файл рядом и переименовываем поверх
+pub fn write_atomic(path: &Path, bytes: &[u8]) -> io::Result<()> {
+    let tmp = path.with_extension(format!("tmp{}", std::process::id()));
+    {
+        let mut f = fs::File::create(&tmp)?;
+        f.write_all(bytes)?;
+        f.sync_all()?;
+    }
+    fs::rename(&tmp, path)
+}
+
+fn trim_front<T>(v: &mut Vec<T>, max: usize) {
+    if v.len() > max {
+        v.drain(..v.len() - max);
+    }
+}
+
+impl SessionStore {
+    pub fn open(dir: PathBuf, limits: SessionsConfig) -> SessionStore {
+        if let Err(e) = fs::create_dir_all(&dir) {
+            warn!("session store: cannot create {}: {}", dir.display(), e);
+        }
+        let store = SessionStore { dir, limits, write_lock: Mutex::new(()) };
+        store.migrate_legacy();
+        store.prune();
+        store
+    }
+
+    fn path(&self, key: &str) -> PathBuf {
+        self.dir.join(file_name(key))
+    }
+
+    pub fn save(&self, key: &str, mut data: SessionData) -> Result<(), String> {
+        let max = self.limits.max_points;
+        trim_front(&mut data.network_history, max);
+        trim_front(&mut data.cpu_history, max);
+        trim_front(&mut data.gpu_history, max);
+        // Точки без хоста относятся к текущему хосту сессии
+        let host = data.current_host.clone();
+        for p in data.cpu_history.iter_mut().chain(data.gpu_history.iter_mut()).filter(|p| p.host.is_empty()) {
+            p.host = host.clone();
+        }
+        for p in data.network_history.iter_mut().filter(|p| p.host.is_empty()) {
+            p.host = host.clone();
+        }
+        let stored = StoredSession { version: FORMAT_VERSION, key: key.to_string(), updated_at: Utc::now(), data };
+        let json = serde_json::to_vec_pretty(&stored).map_err(|e| e.to_string())?;
+        {
+            let _guard = self.write_lock.lock().map_err(|_| "session store lock poisoned".to_string())?;
+            write_atomic(&self.path(key), &json).map_err(|e| e.to_string())?;
+        }
+        self.prune();
+        Ok(())
+    }
+
+    pub fn load(&self, key: &str) -> Result<Option<Restored>, String> {
+        let path = self.path(key);
+        if !path.exists() {
+            return Ok(None);
+        }
+        let text = fs::read_to_string(&path).map_err(|e| e.to_string())?;
+        match parse(&text) {
+            Ok(OnDisk::Current(s)) => Ok(Some(Restored { data: s.data, updated_at: Some(s.updated_at) })),
+            Ok(OnDisk::Legacy(data)) => Ok(Some(Restored { data, updated_at: None })),
+            Err(e) => Err(format!("session {}: {}; {}", key, e, self.set_aside(&path, &e))),
+        }
+    }
+
+    // Файлы в старом формате переписываем в текущий при старте, нечитаемые убираем в сторону
+    fn migrate_legacy(&self) {
+        for (path, _) in self.files() {
+            let Ok(text) = fs::read_to_string(&path) else { continue };
+            match parse(&text) {
+                Ok(OnDisk::Current(_)) => {}
+                Ok(OnDisk::Legacy(data)) => {
+                    let key = path.file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or_default();
+                    info!("session store: migrating legacy file {}", path.display());
+                    if let Err(e) = self.save(&key, data) {
+                        warn!("session store: migration of {} failed: {}", path.display(), e);
+                    }
+                }
+                Err(e) => {
+                    self.set_aside(&path, &e);
+                }
+            }
+        }
+    }
+
+    // Нечитаемый файл не перезаписывается следующим save: <ключ>.json -> <ключ>.json.unreadable-<unix ms>
+    fn set_aside(&self, path: &Path, reason: &str) -> String {
+        let mut aside = path.to_path_buf().into_os_string();
+        aside.push(format!(".unreadable-{}", Utc::now().timestamp_millis()));
+        let aside = PathBuf::from(aside);
+        let Ok(_guard) = self.write_lock.lock() else { return "session store lock poisoned".into() };
+        match fs::rename(path, &aside) {
+            Ok(()) => {
+                warn!("session store: {} is unreadable ({}), moved to {}", path.display(), reason, aside.display());
+                format!("moved to {}", aside.file_name().unwrap_or_default().to_string_lossy())
+            }
+            Err(e) => {
+                warn!("session store: {} is unreadable ({}) and cannot be moved: {}", path.display(), reason, e);
+                format!("cannot move it aside: {}", e)
+            }
+        }
+    }
+
+    fn files(&self) -> Vec<(PathBuf, std::time::SystemTime)> {
+        let Ok(entries) = fs::read_dir(&self.dir) else { return vec![] };
+        entries.filter_map(|e| e.ok())
+            .map(|e| e.path())
+            .filter(|p| p.extension().is_some_and(|ext| ext == "json"))
+            .filter_map(|p| {
+                let modified = fs::metadata(&p).and_then(|m| m.modified()).ok()?;
+                Some((p, modified))
+            })
+            .collect()
+    }
+
+    // Удаляет сессии старше max_age_days и самые старые сверх max_sessions
+    pub fn prune(&self) {
+        let Ok(_guard) = self.write_lock.lock() else { return };
+        let mut files = self.files();
+        files.sort_by_key(|f| std::cmp::Reverse(f.1));
+        let cutoff = Utc::now() - ChronoDuration::days(self.limits.max_age_days as i64);
+        for (i, (path, modified)) in files.iter().enumerate() {
+            let modified: DateTime<Utc> = (*modified).into();
+            let expired = self.limits.max_age_days > 0 && modified < cutoff;
+            if expired || i >= self.limits.max_sessions {
+                debug!("session store: removing {}", path.display());
+                if let Err(e) = fs::remove_file(path) {
+                    warn!("session store: cannot remove {}: {}", path.display(), e);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn store_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("rust-server-sessions-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    // Файлы, убранные в сторону: имя -> содержимое
+    fn set_aside_files(dir: &Path) -> Vec<(String, String)> {
+        let mut files: Vec<_> = fs::read_dir(dir).unwrap()
+            .map(|e| e.unwrap().path())
+            .filter(|p| p.to_string_lossy().contains(".json.unreadable-"))
+            .map(|p| (p.file_name().unwrap().to_string_lossy().to_string(), fs::read_to_string(&p).unwrap()))
+            .collect();
+        files.sort();
+        files
+    }
+
+    #[test]
+    fn legacy_session_is_migrated() {
+        let dir = store_dir("legacy");
+        let legacy = r#"{"cpu_history": [1.5, 2.0], "current_host": "web-1",
+            "network_history": [{"rx": 10.0, "tx": 20.0, "timestamp": 1700000000000}]}"#;
+        fs::write(dir.join("session-old.json"), legacy).unwrap();
+
+        let store = SessionStore::open(dir.clone(), SessionsConfig::default());
+        let text = fs::read_to_string(dir.join("session-old.json")).unwrap();
+        let Ok(OnDisk::Current(stored)) = parse(&text) else { panic!("not migrated: {}", text) };
+        assert_eq!((stored.version, stored.key.as_str()), (FORMAT_VERSION, "session-old"));
+
+        let restored = store.load("session-old").unwrap().unwrap();
+        assert!(restored.updated_at.is_some());
+        let cpu: Vec<_> = restored.data.cpu_history.iter().map(|p| (p.value, p.timestamp, p.host.as_str())).collect();
+        assert_eq!(cpu, [(1.5, 0, "web-1"), (2.0, 0, "web-1")]);
+        assert_eq!(restored.data.network_history[0].host, "web-1");
+        assert!(set_aside_files(&dir).is_empty());
+    }
+
+    #[test]
+    fn foreign_file_is_set_aside_not_overwritten() {
+        let dir = store_dir("foreign");
+        fs::write(dir.join("session-a.json"), r#"{"settings": {"theme": "dark"}}"#).unwrap();
+        fs::write(dir.join("session-b.json"), "not json").unwrap();
+        let store = SessionStore::open(dir.clone(), SessionsConfig::default());
+
+        let aside = set_aside_files(&dir);
+        assert_eq!(aside.len(), 2);
+        assert!(aside[0].0.starts_with("session-a.json.unreadable-"));
+        assert_eq!(aside[0].1, r#"{"settings": {"theme": "dark"}}"#);
+        assert_eq!(aside[1].1, "not json");
+        assert!(store.load("session-a").unwrap().is_none());
+
+        // Новое сохранение под тем же ключом не трогает отложенный файл
+        store.save("session-a", SessionData { current_host: "web-2".into(), ..SessionData::default() }).unwrap();
+        assert_eq!(store.load("session-a").unwrap().unwrap().data.current_host, "web-2");
+        assert_eq!(set_aside_files(&dir), aside);
+
+        // Появившийся после старта чужой файл убирается при чтении
+        fs::write(dir.join("session-c.json"), "[1, 2, 3]").unwrap();
+        let e = store.load("session-c").err().unwrap();
+        assert!(e.contains("moved to session-c.json.unreadable-"), "{}", e);
+        assert!(!dir.join("session-c.json").exists());
+    }
+
+    #[test]
+    fn newer_version_is_rejected() {
+        let newer = r#"{"version": 3, "key": "session-n", "updated_at": "2026-10-18T00:00:00Z", "data": {}}"#;
+        let Err(e) = parse(newer) else { panic!("version 3 accepted") };
+        assert_eq!(e, "unsupported format version 3");
+
+        let dir = store_dir("newer");
+        fs::write(dir.join("session-n.json"), newer).unwrap();
+        let store = SessionStore::open(dir.clone(), SessionsConfig::default());
+        assert!(store.load("session-n").unwrap().is_none());
+        let aside = set_aside_files(&dir);
+        assert_eq!(aside.len(), 1);
+        assert_eq!(aside[0].1, newer);
+    }
+}
@@ -1,9 +1,11 @@
-use axum::{Router, routing::{get, post}, Json};
+use axum::{Router, routing::{get, post}, extract::State, Json};
 use serde::{Deserialize, Serialize};
 use serde_json::json;
-use std::{fs, path::Path, io};
+use std::{fs, path::{Path, PathBuf}, io, sync::Arc};
 
-const SNIPPETS_PATH: &str = "public/snippets.json";
+use crate::config::Config;
+
+struct SnippetsState { path: PathBuf }
 
 #[derive(Serialize, Deserialize, Clone)]
 struct Snippet {
@@ -21,36 +23,37 @@ struct SnippetPayload {
     code: String,
 }
 
-pub fn create_routes() -> Router {
+pub fn create_routes(config: &Config) -> Router {
     Router::new()
         .route("/rust/snippets/list", get(list))
         .route("/rust/snippets/save", post(save))
         .route("/rust/snippets/delete", post(delete_snippet))
+        .with_state(Arc::new(SnippetsState { path: config.snippets_path() }))
 }
 
-fn read_snippets() -> io::Result<Vec<Snippet>> {
-    if !Path::new(SNIPPETS_PATH).exists() {
+fn read_snippets(path: &Path) -> io::Result<Vec<Snippet>> {
+    if !path.exists() {
         return Ok(vec![]);
     }
-    let data = fs::read_to_string(SNIPPETS_PATH)?;
+    let data = fs::read_to_string(path)?;
     let parsed: Vec<Snippet> = serde_json::from_str(&data).unwrap_or_default();
     Ok(parsed)
 }
 
-fn write_snippets(all: &[Snippet]) -> io::Result<()> {
+fn write_snippets(path: &Path, all: &[Snippet]) -> io::Result<()> {
     let text = serde_json::to_string_pretty(all).unwrap_or("[]".into());
-    fs::write(SNIPPETS_PATH, text)
+    fs::write(path, text)
 }
 
-async fn list() -> Json<serde_json::Value> {
-    match read_snippets() {
+async fn list(State(state): State<Arc<SnippetsState>>) -> Json<serde_json::Value> {
+    match read_snippets(&state.path) {
         Ok(v) => Json(json!({"snippets": v})),
         Err(e) => Json(json!({"snippets": [], "error": e.to_string()})),
     }
 }
 
-async fn save(Json(payload): Json<SnippetPayload>) -> Json<serde_json::Value> {
-    let mut all = read_snippets().unwrap_or_default();
+async fn save(State(state): State<Arc<SnippetsState>>, Json(payload): Json<SnippetPayload>) -> Json<serde_json::Value> {
+    let mut all = read_snippets(&state.path).unwrap_or_default();
     let id = payload.id.unwrap_or_else(|| chrono::Utc::now().timestamp_millis());
 
     if let Some(existing) = all.iter_mut().find(|s| s.id == id) {
@@ -61,7 +64,7 @@ async fn save(Json(payload): Json<SnippetPayload>) -> Json<serde_json::Value> {
         all.insert(0, Snippet { id, name: payload.name, language: payload.language, code: payload.code });
     }
 
-    if let Err(e) = write_snippets(&all) {
+    if let Err(e) = write_snippets(&state.path, &all) {
         return Json(json!({"ok": false, "error": e.to_string()}));
     }
     Json(json!({"ok": true, "id": id}))
@@ -70,14 +73,14 @@ async fn save(Json(payload): Json<SnippetPayload>) -> Json<serde_json::Value> {
 #[derive(Deserialize)]
 struct DeletePayload { id: i64 }
 
-async fn delete_snippet(Json(payload): Json<DeletePayload>) -> Json<serde_json::Value> {
-    let mut all = read_snippets().unwrap_or_default();
+async fn delete_snippet(State(state): State<Arc<SnippetsState>>, Json(payload): Json<DeletePayload>) -> Json<serde_json::Value> {
+    let mut all = read_snippets(&state.path).unwrap_or_default();
     let before = all.len();
     all.retain(|s| s.id != payload.id);
     if all.len() == before {
         return Json(json!({"ok": false, "error": "not found"}));
     }
-    if let Err(e) = write_snippets(&all) {
+    if let Err(e) = write_snippets(&state.path, &all) {
         return Json(json!({"ok": false, "error": e.to_string()}));
     }
     Json(json!({"ok": true}))
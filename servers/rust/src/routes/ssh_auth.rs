@@ -0,0 +1,225 @@
+// Способы SSH-аутентификации: пароль, ключ (PEM или файл, с passphrase), ssh-agent
+use axum::{http::StatusCode, response::{IntoResponse, Response}, Json};
+use base64::{engine::general_purpose::STANDARD as B64, Engine};
+use serde::{Deserialize, Serialize};
+use once_cell::sync::OnceCell;
+use serde_json::{json, Value};
+use std::fs;
+use std::path::{Path, PathBuf};
+use tracing::debug;
+
+// Ключи больше этого — явно не ключи
+const MAX_KEY_BYTES: u64 = 64 * 1024;
+
+// Одна ошибка на все случаи: по ответу нельзя узнать, есть ли такой файл на сервере
+const KEY_FILE_UNAVAILABLE: &str = "key file is not available: key_path must name a private key in ssh_key_dir";
+
+// ssh_key_dir из конфига; задаётся один раз при старте
+static KEY_DIR: OnceCell<Option<PathBuf>> = OnceCell::new();
+
+pub fn set_key_dir(dir: Option<PathBuf>) {
+    let _ = KEY_DIR.set(dir);
+}
+
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize, Default, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum AuthKind {
+    #[default]
+    Password,
+    Key,
+    KeyFile,
+    Agent,
+}
+
+impl AuthKind {
+    fn parse(s: &str) -> Option<AuthKind> {
+        match s {
+            "password" => Some(AuthKind::Password),
+            "key" => Some(AuthKind::Key),
+            "key_file" => Some(AuthKind::KeyFile),
+            "agent" => Some(AuthKind::Agent),
+            _ => None,
+        }
+    }
+}
+
+// Поля аутентификации как они приходят в JSON (вкладываются через #[serde(flatten)])
+#[derive(Deserialize, Default)]
+pub struct AuthReq {
+    // password | key | key_file | agent; без него выбирается по заполненным полям
+    pub auth: Option<String>,
+    pub password: Option<String>,
+    pub private_key: Option<String>,
+    pub key_path: Option<String>,
+    pub passphrase: Option<String>,
+    pub agent_socket: Option<String>,
+}
+
+#[derive(Clone)]
+pub enum SshAuth {
+    Password(String),
+    Key { pem: String, passphrase: Option<String> },
+    KeyFile { path: PathBuf, passphrase: Option<String> },
+    // None — SSH_AUTH_SOCK той стороны, что подключается
+    Agent { socket: Option<PathBuf> },
+}
+
+#[derive(Serialize)]
+pub struct FieldError {
+    pub field: &'static str,
+    pub message: String,
+}
+
+pub struct AuthError(pub Vec<FieldError>);
+
+impl AuthError {
+    pub fn one(field: &'static str, message: impl Into<String>) -> AuthError {
+        AuthError(vec![FieldError { field, message: message.into() }])
+    }
+
+    pub fn message(&self) -> String {
+        self.0.iter().map(|e| format!("{}: {}", e.field, e.message)).collect::<Vec<_>>().join("; ")
+    }
+}
+
+impl IntoResponse for AuthError {
+    fn into_response(self) -> Response {
+        (StatusCode::BAD_REQUEST, Json(json!({
+            "ok": false,
+            "error": format!("invalid ssh auth: {}", self.message()),
+            "fields": self.0,
+        }))).into_response()
+    }
+}
+
+fn non_empty(v: &Option<String>) -> Option<&str> {
+    v.as_deref().map(str::trim).filter(|s| !s.is_empty())
+}
+
+impl AuthReq {
+    pub fn kind(&self) -> Result<AuthKind, AuthError> {
+        if let Some(kind) = non_empty(&self.auth) {
+            return AuthKind::parse(kind)
+                .ok_or_else(|| AuthError::one("auth", format!("unknown auth {:?}, expected password, key, key_file or agent", kind)));
+        }
+        Ok(if non_empty(&self.private_key).is_some() {
+            AuthKind::Key
+        } else if non_empty(&self.key_path).is_some() {
+            AuthKind::KeyFile
+        } else if non_empty(&self.agent_socket).is_some() {
+            AuthKind::Agent
+        } else {
+            AuthKind::Password
+        })
+    }
+
+    pub fn validate(&self) -> Result<SshAuth, AuthError> {
+        let passphrase = non_empty(&self.passphrase).map(String::from);
+        let auth = match self.kind()? {
+            AuthKind::Password => match self.password.as_deref().filter(|p| !p.is_empty()) {
+                Some(p) => SshAuth::Password(p.to_string()),
+                None => return Err(AuthError::one("password", "password is required for password auth")),
+            },
+            AuthKind::Key => {
+                let pem = non_empty(&self.private_key)
+                    .ok_or_else(|| AuthError::one("private_key", "private_key is required for key auth"))?;
+                check_pem("private_key", pem, passphrase.is_some())?;
+                SshAuth::Key { pem: format!("{}\n", pem), passphrase }
+            }
+            AuthKind::KeyFile => {
+                let path = non_empty(&self.key_path)
+                    .ok_or_else(|| AuthError::one("key_path", "key_path is required for key_file auth"))?;
+                let path = PathBuf::from(path);
+                read_key_file(&path, passphrase.is_some())?;
+                SshAuth::KeyFile { path, passphrase }
+            }
+            AuthKind::Agent => {
+                let socket = non_empty(&self.agent_socket).map(PathBuf::from);
+                if let Some(s) = &socket {
+                    if !s.is_absolute() {
+                        return Err(AuthError::one("agent_socket", "agent_socket must be an absolute path"));
+                    }
+                }
+                SshAuth::Agent { socket }
+            }
+        };
+        Ok(auth)
+    }
+}
+
+impl SshAuth {
+    pub fn kind(&self) -> AuthKind {
+        match self {
+            SshAuth::Password(_) => AuthKind::Password,
+            SshAuth::Key { .. } => AuthKind::Key,
+            SshAuth::KeyFile { .. } => AuthKind::KeyFile,
+            SshAuth::Agent { .. } => AuthKind::Agent,
+        }
+    }
+
+    // Поля для /ssh/connect relay. Файл ключа читается здесь: relay может работать на другой машине
+    pub fn relay_fields(&self) -> Result<Value, AuthError> {
+        Ok(match self {
+            SshAuth::Password(p) => json!({"password": p}),
+            SshAuth::Key { pem, passphrase } => json!({"private_key": pem, "passphrase": passphrase}),
+            SshAuth::KeyFile { path, passphrase } => {
+                let pem = read_key_file(path, passphrase.is_some())?;
+                json!({"private_key": pem, "passphrase": passphrase})
+            }
+            SshAuth::Agent { socket } => json!({"agent": true, "agent_socket": socket}),
+        })
+    }
+}
+
+// Относительный key_path — от ssh_key_dir; после разрешения симлинков файл должен остаться внутри каталога
+fn read_key_file(path: &Path, has_passphrase: bool) -> Result<String, AuthError> {
+    let Some(dir) = KEY_DIR.get().and_then(Option::as_ref) else {
+        return Err(AuthError::one("key_path", "key_file auth is disabled: ssh_key_dir is not configured"));
+    };
+    let unavailable = |reason: String| {
+        debug!("key_path {}: {}", path.display(), reason);
+        AuthError::one("key_path", KEY_FILE_UNAVAILABLE)
+    };
+    let dir = dir.canonicalize().map_err(|e| unavailable(format!("ssh_key_dir {}: {}", dir.display(), e)))?;
+    let file = dir.join(path).canonicalize().map_err(|e| unavailable(e.to_string()))?;
+    if !file.starts_with(&dir) {
+        return Err(unavailable(format!("{} is outside {}", file.display(), dir.display())));
+    }
+    let meta = fs::metadata(&file).map_err(|e| unavailable(e.to_string()))?;
+    if !meta.is_file() || meta.len() > MAX_KEY_BYTES {
+        return Err(unavailable("not a file or too large for a private key".into()));
+    }
+    let pem = fs::read_to_string(&file).map_err(|e| unavailable(e.to_string()))?;
+    check_pem("key_path", &pem, has_passphrase)?;
+    Ok(pem)
+}
+
+// Проверка формы ключа без разбора: PEM-обёртка и признак шифрования (нужен ли passphrase)
+fn check_pem(field: &'static str, pem: &str, has_passphrase: bool) -> Result<(), AuthError> {
+    let pem = pem.trim();
+    let Some(header) = pem.lines().next().filter(|l| l.starts_with("-----BEGIN ") && l.ends_with("PRIVATE KEY-----")) else {
+        return Err(AuthError::one(field, "not a PEM private key (expected -----BEGIN ... PRIVATE KEY-----)"));
+    };
+    let footer = header.replacen("BEGIN", "END", 1);
+    if !pem.ends_with(&footer) {
+        return Err(AuthError::one(field, format!("PEM key is truncated: missing {}", footer)));
+    }
+    let encrypted = if header.contains("OPENSSH") {
+        openssh_cipher(pem).is_some_and(|c| c != "none")
+    } else {
+        header.contains("ENCRYPTED") || pem.contains("Proc-Type: 4,ENCRYPTED")
+    };
+    if encrypted && !has_passphrase {
+        return Err(AuthError::one("passphrase", "private key is encrypted, passphrase is required"));
+    }
+    Ok(())
+}
+
+// openssh-key-v1: magic, затем string ciphername
+fn openssh_cipher(pem: &str) -> Option<String> {
+    let body: String = pem.lines().filter(|l| !l.starts_with("-----")).collect();
+    let raw = B64.decode(body.trim()).ok()?;
+    let rest = raw.strip_prefix(b"openssh-key-v1\0")?;
+    let len = u32::from_be_bytes(rest.get(..4)?.try_into().ok()?) as usize;
+    String::from_utf8(rest.get(4..4 + len)?.to_vec()).ok()
+}
@@ -0,0 +1,174 @@
+// SSH-сессии дашборда: id выдаётся в /rust/ssh/connect, дальше запросы передают его заголовком X-SSH-Session
+// (или ?ssh_session=), а rust-server — relay тем же заголовком
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::time::MissedTickBehavior;
+use tracing::{info, warn};
+
+use crate::config::SshSessionsConfig;
+use super::relay::{RelayRegistry, RelayTarget};
+use super::relay_models::SshDisconnectResult;
+use super::ssh_auth::AuthKind;
+
+pub const SSH_SESSION_HEADER: &str = "x-ssh-session";
+
+#[derive(Clone, Serialize)]
+pub struct SshSessionInfo {
+    pub id: String,
+    pub relay: String,
+    pub host: String,
+    pub user: String,
+    pub port: u16,
+    pub auth: AuthKind,
+    // Имя сохранённого сервера, если подключались по нему
+    pub server: Option<String>,
+    // Цепочка jump host'ов
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub jumps: Vec<String>,
+    pub created_at: DateTime<Utc>,
+    pub last_used: DateTime<Utc>,
+}
+
+pub struct SshSessions {
+    // В порядке подключения
+    sessions: Mutex<Vec<SshSessionInfo>>,
+    // relay с одним подключением → id сессии, которая сейчас к нему подключается
+    claims: Mutex<HashMap<String, String>>,
+    config: SshSessionsConfig,
+}
+
+// Занятый connect'ом relay; освобождается при drop
+pub struct Claim<'a> {
+    sessions: &'a SshSessions,
+    relay: String,
+    id: String,
+}
+
+impl Drop for Claim<'_> {
+    fn drop(&mut self) {
+        if let Ok(mut claims) = self.sessions.claims.lock() {
+            if claims.get(&self.relay) == Some(&self.id) {
+                claims.remove(&self.relay);
+            }
+        }
+    }
+}
+
+pub fn new_id() -> String {
+    format!("{:016x}", rand::random::<u64>())
+}
+
+impl SshSessions {
+    pub fn new(config: SshSessionsConfig) -> SshSessions {
+        SshSessions { sessions: Mutex::new(vec![]), claims: Mutex::new(HashMap::new()), config }
+    }
+
+    // Relay с одним подключением (Go relay) не делится между сессиями: connect новой сессии, пока relay
+    // держит другую, получает отказ вместо того, чтобы закрыть чужую. Err — id занявшей relay сессии
+    pub fn claim(&self, relay: &str, id: &str) -> Result<Claim<'_>, String> {
+        let sessions = self.sessions.lock().map_err(|_| String::new())?;
+        let mut claims = self.claims.lock().map_err(|_| String::new())?;
+        if let Some(other) = sessions.iter().find(|s| s.relay == relay && s.id != id) {
+            return Err(other.id.clone());
+        }
+        if let Some(other) = claims.get(relay).filter(|other| *other != id) {
+            return Err(other.clone());
+        }
+        claims.insert(relay.to_string(), id.to_string());
+        Ok(Claim { sessions: self, relay: relay.to_string(), id: id.to_string() })
+    }
+
+    // Кто сейчас занимает relay: открытая сессия или идущий connect
+    pub fn holder(&self, relay: &str) -> Option<String> {
+        if let Some(s) = self.sessions.lock().ok()?.iter().find(|s| s.relay == relay) {
+            return Some(s.id.clone());
+        }
+        self.claims.lock().ok()?.get(relay).cloned()
+    }
+
+    pub fn insert(&self, info: SshSessionInfo) {
+        let Ok(mut sessions) = self.sessions.lock() else { return };
+        sessions.retain(|s| s.id != info.id);
+        sessions.push(info);
+    }
+
+    // Запрос пользователя продлевает сессию; фоновый опрос метрик — нет
+    pub fn touch(&self, id: &str) -> Option<SshSessionInfo> {
+        let mut sessions = self.sessions.lock().ok()?;
+        let s = sessions.iter_mut().find(|s| s.id == id)?;
+        s.last_used = Utc::now();
+        Some(s.clone())
+    }
+
+    pub fn get(&self, id: &str) -> Option<SshSessionInfo> {
+        self.sessions.lock().ok()?.iter().find(|s| s.id == id).cloned()
+    }
+
+    pub fn remove(&self, id: &str) -> Option<SshSessionInfo> {
+        let mut sessions = self.sessions.lock().ok()?;
+        let i = sessions.iter().position(|s| s.id == id)?;
+        Some(sessions.remove(i))
+    }
+
+    pub fn list(&self) -> Vec<SshSessionInfo> {
+        self.sessions.lock().map(|s| s.clone()).unwrap_or_default()
+    }
+
+    pub fn on_relay(&self, relay: &str) -> Vec<String> {
+        self.list().into_iter().filter(|s| s.relay == relay).map(|s| s.id).collect()
+    }
+
+    fn take_idle(&self) -> Vec<SshSessionInfo> {
+        if self.config.idle_timeout_secs == 0 {
+            return vec![];
+        }
+        let cutoff = Utc::now() - chrono::Duration::seconds(self.config.idle_timeout_secs as i64);
+        let Ok(mut sessions) = self.sessions.lock() else { return vec![] };
+        let (idle, active): (Vec<_>, Vec<_>) = sessions.drain(..).partition(|s| s.last_used < cutoff);
+        *sessions = active;
+        idle
+    }
+
+    // GET /rust/ssh/sessions
+    pub fn describe(&self) -> Value {
+        let now = Utc::now();
+        let timeout = self.config.idle_timeout_secs as i64;
+        let sessions: Vec<_> = self.list().into_iter().map(|s| {
+            let idle = (now - s.last_used).num_seconds().max(0);
+            let mut v = json!(s);
+            v["idle_secs"] = json!(idle);
+            v["expires_in_secs"] = if timeout > 0 { json!((timeout - idle).max(0)) } else { Value::Null };
+            v
+        }).collect();
+        json!({"sessions": sessions, "idle_timeout_secs": self.config.idle_timeout_secs})
+    }
+}
+
+// Закрывает сессии, простаивающие дольше idle_timeout_secs
+pub fn spawn_reaper(registry: Arc<RelayRegistry>, config: &SshSessionsConfig) {
+    if config.idle_timeout_secs == 0 || config.reap_interval_secs == 0 {
+        info!("ssh session reaper disabled");
+        return;
+    }
+    let every = Duration::from_secs(config.reap_interval_secs);
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(every);
+        ticker.set_missed_tick_behavior(MissedTickBehavior::Skip);
+        loop {
+            ticker.tick().await;
+            for s in registry.sessions.take_idle() {
+                info!("ssh session {} ({}@{} via {}) idle since {}, closing", s.id, s.user, s.host, s.relay, s.last_used);
+                let Some(backend) = registry.get(&s.relay) else { continue };
+                let target = RelayTarget { backend, session: Some(s.id.clone()), explicit: true };
+                let result: Result<SshDisconnectResult, _> = registry.post_json(&target, "/ssh/disconnect", None::<&()>).await;
+                if let Err(e) = result {
+                    warn!("ssh session {}: disconnect failed: {}", s.id, e.message());
+                }
+            }
+        }
+    });
+}
@@ -0,0 +1,128 @@
+// Интерактивный терминал /rust/ssh/terminal: WebSocket <-> shell с PTY встроенного SSH-клиента.
+// Браузер -> сервер: бинарные кадры — ввод как есть, текстовые — JSON {"type":"input","data"} или {"type":"resize","cols","rows"}.
+// Сервер -> браузер: бинарные кадры — вывод PTY, текстовые — JSON {"type":"ready"|"error"|"exit", ...}
+use axum::{
+    extract::ws::WebSocketUpgrade,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::Deserialize;
+use serde_json::json;
+use std::sync::Arc;
+
+use super::relay::{RelayRegistry, RelayTarget};
+
+#[cfg_attr(not(feature = "native-ssh"), allow(dead_code))]
+#[derive(Deserialize)]
+pub struct TerminalQuery {
+    pub cols: Option<u32>,
+    pub rows: Option<u32>,
+    pub term: Option<String>,
+}
+
+#[cfg_attr(not(feature = "native-ssh"), allow(dead_code))]
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum ClientMsg {
+    Input { data: String },
+    Resize { cols: u32, rows: u32 },
+}
+
+#[cfg_attr(not(feature = "native-ssh"), allow(dead_code))]
+fn size(cols: Option<u32>, rows: Option<u32>) -> (u32, u32) {
+    (cols.unwrap_or(80).clamp(10, 500), rows.unwrap_or(24).clamp(5, 200))
+}
+
+fn unsupported(target: &RelayTarget) -> Response {
+    let msg = format!("relay {} has no terminal support, use a relay with kind = \"native\"", target.backend.name);
+    (StatusCode::NOT_IMPLEMENTED, Json(json!({"error": msg}))).into_response()
+}
+
+#[cfg(not(feature = "native-ssh"))]
+pub async fn open(_registry: Arc<RelayRegistry>, target: RelayTarget, _q: TerminalQuery, _ws: WebSocketUpgrade) -> Response {
+    unsupported(&target)
+}
+
+// Shell открывается до upgrade, чтобы "ssh not connected" и прочие ошибки ушли обычным JSON-ответом
+#[cfg(feature = "native-ssh")]
+pub async fn open(registry: Arc<RelayRegistry>, target: RelayTarget, q: TerminalQuery, ws: WebSocketUpgrade) -> Response {
+    let Some(native) = target.backend.native() else { return unsupported(&target) };
+    let (cols, rows) = size(q.cols, q.rows);
+    let term = q.term.filter(|t| !t.is_empty() && t.chars().all(|c| c.is_ascii_alphanumeric() || c == '-'))
+        .unwrap_or_else(|| "xterm-256color".into());
+    match native.open_shell(target.session.as_deref(), &term, cols, rows).await {
+        Ok(channel) => ws.on_upgrade(move |socket| bridge::run(socket, channel, registry, target.session)),
+        Err(e) => e.into_response(),
+    }
+}
+
+#[cfg(feature = "native-ssh")]
+mod bridge {
+    use axum::extract::ws::{Message, WebSocket};
+    use russh::ChannelMsg;
+    use serde_json::json;
+    use std::sync::Arc;
+    use tracing::{debug, info};
+
+    use super::{size, ClientMsg};
+    use crate::routes::native_ssh::ShellChannel;
+    use crate::routes::relay::RelayRegistry;
+
+    fn event(value: serde_json::Value) -> Message {
+        Message::Text(value.to_string())
+    }
+
+    pub async fn run(mut socket: WebSocket, channel: ShellChannel, registry: Arc<RelayRegistry>, session: Option<String>) {
+        let (mut output, input) = channel.split();
+        let _ = socket.send(event(json!({"type": "ready", "session": session}))).await;
+        let mut exit_code = None;
+        let reason = loop {
+            // Открытый терминал — активность: сессию не закрывает reaper
+            if let Some(id) = &session {
+                registry.sessions.touch(id);
+            }
+            tokio::select! {
+                msg = socket.recv() => {
+                    let sent = match msg {
+                        Some(Ok(Message::Binary(data))) => input.data_bytes(data).await,
+                        Some(Ok(Message::Text(text))) => match serde_json::from_str::<ClientMsg>(&text) {
+                            Ok(ClientMsg::Input { data }) => input.data_bytes(data.into_bytes()).await,
+                            Ok(ClientMsg::Resize { cols, rows }) => {
+                                let (cols, rows) = size(Some(cols), Some(rows));
+                                debug!("terminal: resize {}x{}", cols, rows);
+                                input.window_change(cols, rows, 0, 0).await
+                            }
+                            Err(e) => {
+                                let _ = socket.send(event(json!({"type": "error", "message": format!("bad message: {}", e)}))).await;
+                                Ok(())
+                            }
+                        },
+                        Some(Ok(Message::Close(_))) | None => break "client closed".to_string(),
+                        Some(Err(e)) => break format!("websocket error: {}", e),
+                        // ping/pong axum отвечает сам
+                        Some(Ok(_)) => Ok(()),
+                    };
+                    if let Err(e) = sent {
+                        break format!("ssh write failed: {}", e);
+                    }
+                }
+                msg = output.wait() => match msg {
+                    Some(ChannelMsg::Data { data }) | Some(ChannelMsg::ExtendedData { data, .. }) => {
+                        if socket.send(Message::Binary(data.to_vec())).await.is_err() {
+                            break "client gone".to_string();
+                        }
+                    }
+                    Some(ChannelMsg::ExitStatus { exit_status }) => exit_code = Some(exit_status),
+                    Some(ChannelMsg::Close) | None => break "shell exited".to_string(),
+                    Some(_) => {}
+                },
+            }
+        };
+        let _ = input.eof().await;
+        let _ = input.close().await;
+        let _ = socket.send(event(json!({"type": "exit", "code": exit_code, "reason": reason}))).await;
+        let _ = socket.send(Message::Close(None)).await;
+        info!("terminal: session {:?} closed ({}), exit code {:?}", session, reason, exit_code);
+    }
+}